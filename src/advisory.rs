@@ -0,0 +1,185 @@
+//! Matching installed packages against the Arch Linux security tracker
+//!
+//! Reads the tracker's JSON export (`https://security.archlinux.org/issues/all.json`)
+//! from a saved file, so no network access is needed.
+
+use {
+    crate::{Pkg, json::Json, version::Version},
+    smol_str::SmolStr,
+    std::path::Path,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Unknown,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Unknown,
+    Vulnerable,
+    /// A fix is in the testing repositories
+    Testing,
+    Fixed,
+    NotAffected,
+}
+
+/// An advisory group (`AVG-…`) of the security tracker
+#[derive(Debug, Clone)]
+pub struct Advisory {
+    /// Like `AVG-2843`
+    pub name: SmolStr,
+    /// Names of the affected packages
+    pub packages: Vec<SmolStr>,
+    pub status: Status,
+    pub severity: Severity,
+    /// Kind of vulnerability, like `arbitrary code execution`
+    pub kind: SmolStr,
+    /// Version the issues were found in
+    pub affected: Version,
+    /// First version with the issues fixed
+    pub fixed: Option<Version>,
+    /// CVE identifiers
+    pub issues: Vec<SmolStr>,
+    /// Published advisories (`ASA-…`)
+    pub advisories: Vec<SmolStr>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AdvisoryError {
+    #[error("Io error")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid JSON at byte {0}")]
+    Json(usize),
+    #[error("Invalid advisory: {0}")]
+    Invalid(String),
+}
+
+/// Read a saved copy of the tracker's `all.json`, see [`parse`]
+pub fn read(path: impl AsRef<Path>) -> Result<(Vec<Advisory>, Vec<AdvisoryError>), AdvisoryError> {
+    parse(&std::fs::read_to_string(path)?)
+}
+
+/// Parse the tracker's `all.json` export
+///
+/// Groups that can't be read, like ones with an invalid version, don't fail the
+/// whole file: they are returned as errors next to the advisories.
+pub fn parse(src: &str) -> Result<(Vec<Advisory>, Vec<AdvisoryError>), AdvisoryError> {
+    let json = Json::parse(src).map_err(|e| AdvisoryError::Json(e.0))?;
+    let Json::Array(groups) = json else {
+        return Err(AdvisoryError::Invalid("expected an array of groups".into()));
+    };
+    let mut advisories = Vec::new();
+    let mut invalid = Vec::new();
+    for group in &groups {
+        match parse_group(group) {
+            Ok(advisory) => advisories.push(advisory),
+            Err(e) => invalid.push(e),
+        }
+    }
+    Ok((advisories, invalid))
+}
+
+fn parse_group(group: &Json) -> Result<Advisory, AdvisoryError> {
+    let name = group.get("name").and_then(Json::as_str).unwrap_or_default();
+    let field = |key| group.get(key).and_then(Json::as_str);
+    let required =
+        |key| field(key).ok_or_else(|| AdvisoryError::Invalid(format!("{name}: missing {key}")));
+    let version = |key, version: &str| {
+        version
+            .parse()
+            .map_err(|_| AdvisoryError::Invalid(format!("{name}: invalid {key} version")))
+    };
+    let list = |key| {
+        group
+            .get(key)
+            .map(Json::as_array)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(Json::as_str)
+            .map(SmolStr::from)
+            .collect()
+    };
+    Ok(Advisory {
+        name: required("name")?.into(),
+        packages: list("packages"),
+        status: match required("status")? {
+            "Vulnerable" => Status::Vulnerable,
+            "Testing" => Status::Testing,
+            "Fixed" => Status::Fixed,
+            "Not affected" => Status::NotAffected,
+            _ => Status::Unknown,
+        },
+        severity: match field("severity") {
+            Some("Low") => Severity::Low,
+            Some("Medium") => Severity::Medium,
+            Some("High") => Severity::High,
+            Some("Critical") => Severity::Critical,
+            _ => Severity::Unknown,
+        },
+        kind: field("type").unwrap_or_default().into(),
+        affected: version("affected", required("affected")?)?,
+        fixed: field("fixed")
+            .map(|fixed| version("fixed", fixed))
+            .transpose()?,
+        issues: list("issues"),
+        advisories: list("advisories"),
+    })
+}
+
+/// An installed package that an advisory applies to
+#[derive(Debug)]
+pub struct Match<'pkg, 'adv> {
+    pub pkg: &'pkg Pkg,
+    pub advisory: &'adv Advisory,
+    /// The installed version is at or past the fixed version
+    pub fixed: bool,
+}
+
+impl Match<'_, '_> {
+    #[must_use]
+    pub fn is_vulnerable(&self) -> bool {
+        !self.fixed
+    }
+}
+
+/// Match installed packages against advisories
+///
+/// Like `arch-audit`, every advisory for an installed package is reported,
+/// unless the tracker marks it as not affected. Whether the installed version
+/// has the fix is decided by comparing it with the fixed version.
+/// Results are sorted by severity (highest first), then by package name.
+#[must_use]
+pub fn matches<'pkg, 'adv>(
+    pkgs: &'pkg [Pkg],
+    advisories: &'adv [Advisory],
+) -> Vec<Match<'pkg, 'adv>> {
+    let mut matches: Vec<Match> = advisories
+        .iter()
+        .filter(|advisory| advisory.status != Status::NotAffected)
+        .flat_map(|advisory| {
+            pkgs.iter()
+                .filter(|pkg| advisory.packages.contains(&pkg.desc.name))
+                .map(move |pkg| Match {
+                    pkg,
+                    advisory,
+                    fixed: advisory
+                        .fixed
+                        .as_ref()
+                        .is_some_and(|fixed| pkg.desc.version >= *fixed),
+                })
+        })
+        .collect();
+    matches.sort_by(|a, b| {
+        b.advisory
+            .severity
+            .cmp(&a.advisory.severity)
+            .then_with(|| a.pkg.desc.name.cmp(&b.pkg.desc.name))
+            .then_with(|| a.advisory.name.cmp(&b.advisory.name))
+    });
+    matches
+}
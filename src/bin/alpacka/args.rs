@@ -0,0 +1,276 @@
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Query,
+    Sync,
+    Files,
+}
+
+/// Parsed command line
+#[derive(Debug, Default)]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "one field per command line flag"
+)]
+pub struct Args {
+    pub op: Option<Op>,
+    /// `-i`, can be given twice
+    pub info: u8,
+    pub list: bool,
+    pub owns: bool,
+    pub search: bool,
+    pub explicit: bool,
+    pub deps: bool,
+    /// `-t`, can be given twice
+    pub unrequired: u8,
+    pub foreign: bool,
+    pub native: bool,
+    pub upgrades: bool,
+    /// `-k`, can be given twice
+    pub check: u8,
+    pub groups: bool,
+    pub quiet: bool,
+    pub help: bool,
+    pub version: bool,
+    pub json: bool,
+    /// `--format` template
+    pub format: Option<String>,
+    pub root: Option<PathBuf>,
+    pub db_path: Option<PathBuf>,
+    pub config: Option<PathBuf>,
+    pub targets: Vec<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArgError {
+    #[error("invalid option '{0}'")]
+    Invalid(String),
+    #[error("option '{0}' requires an argument")]
+    MissingValue(String),
+    #[error("only one operation may be used at a time")]
+    MultipleOps,
+    #[error("no operation specified (use -h for help)")]
+    NoOp,
+    #[error("option '{0}' is not supported with this operation")]
+    WrongOp(String),
+    #[error("only read-only operations are supported")]
+    NotReadOnly,
+}
+
+impl Args {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, ArgError> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                parsed.targets.extend(args.by_ref());
+                break;
+            }
+            if let Some(long) = arg.strip_prefix("--") {
+                let (name, value) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_owned())),
+                    None => (long, None),
+                };
+                if name == "format" {
+                    let value = value
+                        .or_else(|| args.next())
+                        .ok_or_else(|| ArgError::MissingValue(arg.clone()))?;
+                    parsed.format = Some(value);
+                } else if let Some(path) = parsed.path_option(name) {
+                    let value = value
+                        .or_else(|| args.next())
+                        .ok_or_else(|| ArgError::MissingValue(arg.clone()))?;
+                    *path = Some(value.into());
+                } else if value.is_some() {
+                    return Err(ArgError::Invalid(arg));
+                } else if name == "json" {
+                    parsed.json = true;
+                } else {
+                    parsed
+                        .flag(long_to_short(name).ok_or_else(|| ArgError::Invalid(arg.clone()))?)?;
+                }
+            } else if let Some(shorts) = arg.strip_prefix('-')
+                && !shorts.is_empty()
+            {
+                for (i, c) in shorts.char_indices() {
+                    let path = match c {
+                        'r' => &mut parsed.root,
+                        'b' => &mut parsed.db_path,
+                        _ => {
+                            parsed.flag(c)?;
+                            continue;
+                        }
+                    };
+                    // The value is either the rest of the argument, or the next one
+                    let rest = &shorts[i + 1..];
+                    let value = if rest.is_empty() {
+                        args.next()
+                            .ok_or_else(|| ArgError::MissingValue(format!("-{c}")))?
+                    } else {
+                        rest.to_owned()
+                    };
+                    *path = Some(value.into());
+                    break;
+                }
+            } else {
+                parsed.targets.push(arg);
+            }
+        }
+        if !parsed.help && !parsed.version {
+            parsed.validate()?;
+        }
+        Ok(parsed)
+    }
+    fn path_option(&mut self, name: &str) -> Option<&mut Option<PathBuf>> {
+        match name {
+            "root" => Some(&mut self.root),
+            "dbpath" => Some(&mut self.db_path),
+            "config" => Some(&mut self.config),
+            _ => None,
+        }
+    }
+    fn set_op(&mut self, op: Op) -> Result<(), ArgError> {
+        if self.op.is_some_and(|prev| prev != op) {
+            return Err(ArgError::MultipleOps);
+        }
+        self.op = Some(op);
+        Ok(())
+    }
+    fn flag(&mut self, c: char) -> Result<(), ArgError> {
+        match c {
+            'Q' => self.set_op(Op::Query)?,
+            'S' => self.set_op(Op::Sync)?,
+            'F' => self.set_op(Op::Files)?,
+            'i' => self.info = self.info.saturating_add(1),
+            'l' => self.list = true,
+            'o' => self.owns = true,
+            's' => self.search = true,
+            'e' => self.explicit = true,
+            'd' => self.deps = true,
+            't' => self.unrequired = self.unrequired.saturating_add(1),
+            'm' => self.foreign = true,
+            'n' => self.native = true,
+            'u' => self.upgrades = true,
+            'k' => self.check = self.check.saturating_add(1),
+            'g' => self.groups = true,
+            'q' => self.quiet = true,
+            'h' => self.help = true,
+            'V' => self.version = true,
+            _ => return Err(ArgError::Invalid(format!("-{c}"))),
+        }
+        Ok(())
+    }
+    /// Check that the given options make sense for the operation
+    fn validate(&self) -> Result<(), ArgError> {
+        let op = self.op.ok_or(ArgError::NoOp)?;
+        let flags = [
+            ('i', self.info > 0),
+            ('l', self.list),
+            ('o', self.owns),
+            ('s', self.search),
+            ('e', self.explicit),
+            ('d', self.deps),
+            ('t', self.unrequired > 0),
+            ('m', self.foreign),
+            ('n', self.native),
+            ('u', self.upgrades),
+            ('k', self.check > 0),
+            ('g', self.groups),
+        ];
+        let allowed = match op {
+            Op::Query => "ilosedtmnukg",
+            Op::Sync => "isgl",
+            Op::Files => "l",
+        };
+        if let Some((c, _)) = flags.iter().find(|(c, set)| *set && !allowed.contains(*c)) {
+            return Err(ArgError::WrongOp(format!("-{c}")));
+        }
+        // Plain `-S` with targets would install them
+        if op == Op::Sync && !(self.info > 0 || self.search || self.groups || self.list) {
+            return Err(ArgError::NotReadOnly);
+        }
+        Ok(())
+    }
+}
+
+fn long_to_short(name: &str) -> Option<char> {
+    let c = match name {
+        "query" => 'Q',
+        "sync" => 'S',
+        "files" => 'F',
+        "info" => 'i',
+        "list" => 'l',
+        "owns" => 'o',
+        "search" => 's',
+        "explicit" => 'e',
+        "deps" => 'd',
+        "unrequired" => 't',
+        "foreign" => 'm',
+        "native" => 'n',
+        "upgrades" => 'u',
+        "check" => 'k',
+        "groups" => 'g',
+        "quiet" => 'q',
+        "help" => 'h',
+        "version" => 'V',
+        _ => return None,
+    };
+    Some(c)
+}
+
+pub const HELP: &str = "\
+usage: alpacka <operation> [options] [targets]
+
+operations:
+    -Q, --query   query the local database
+    -S, --sync    query the sync databases
+    -F, --files   query the files databases
+
+query options (-Q):
+    -i, --info        view package information (-ii for backup files)
+    -l, --list        list the files owned by the package
+    -o, --owns        query the package that owns <file>
+    -s, --search      search installed packages for matching strings
+    -e, --explicit    list packages explicitly installed
+    -d, --deps        list packages installed as dependencies
+    -t, --unrequired  list packages not required by any package (-tt includes optional)
+    -m, --foreign     list packages not found in the sync databases
+    -n, --native      list packages only found in the sync databases
+    -u, --upgrades    list outdated packages
+    -k, --check       check that package files exist (-kk also checks their metadata)
+    -g, --groups      view all members of a package group
+
+sync options (-S):
+    -i, --info    view package information
+    -s, --search  search remote repositories for matching strings
+    -g, --groups  view all members of a package group
+    -l, --list    view a list of packages in a repo
+
+files options (-F):
+    -l, --list    list the files owned by the queried package
+
+general options:
+    -r, --root <path>    set an alternate installation root
+    -b, --dbpath <path>  set an alternate database location
+        --config <path>  set an alternate configuration file
+    -q, --quiet          show less information
+        --json           print results as a JSON document (schema version 1)
+        --format <fmt>   print each result using an expac style template
+    -h, --help           show this help
+    -V, --version        show the version
+
+format fields:
+    %n name           %v version        %e base           %d description
+    %a architecture   %u url            %r repository     %L licenses
+    %G groups         %P provides       %S provides (names only)
+    %D depends        %E depends (names only)
+    %O optdepends     %o optdepends (without reasons)
+    %C conflicts      %H conflicts (names only)
+    %R replaces       %T replaces (names only)
+    %m installed size %k download size  %p packager       %b build date
+    %l install date   %w install reason %i install script %f filename
+    %h sha256sum      %V validation     %N required by    %F files
+    %B backup files   %% a literal %
+    Lists are separated by two spaces. \\n and \\t are newline and tab.
+";
@@ -0,0 +1,850 @@
+#![forbid(unsafe_code)]
+#![warn(clippy::pedantic)]
+
+mod args;
+mod output;
+
+use {
+    alpacka::{
+        InstallReason, Pkg,
+        config::{Config, ConfigError, DEFAULT_CONFIG_PATH},
+        dep::PkgDepsExt,
+        display::{DateFormat, PkgInfo},
+        group,
+        mtree::{self, Mismatch},
+    },
+    args::{Args, Op},
+    output::{FileCheck, Output, Record},
+    smol_str::SmolStr,
+    std::{
+        error::Error,
+        path::{Path, PathBuf},
+        process::ExitCode,
+    },
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if args.help {
+        print!("{}", args::HELP);
+        return ExitCode::SUCCESS;
+    }
+    if args.version {
+        println!("alpacka {}", env!("CARGO_PKG_VERSION"));
+        return ExitCode::SUCCESS;
+    }
+    let result = Ctx::new(&args).and_then(|ctx| {
+        let (name, result) = match args.op {
+            Some(Op::Query) => ("query", query(&ctx)),
+            Some(Op::Sync) => ("sync", sync(&ctx)),
+            Some(Op::Files) => ("files", files(&ctx)),
+            None => unreachable!("validated by Args::parse"),
+        };
+        if result.is_ok() {
+            ctx.out.finish(name);
+        }
+        result
+    });
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {}", error_chain(&*e));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Width to wrap output at, like pacman: `$COLUMNS`, else 80 on a terminal
+/// and no wrapping otherwise
+fn terminal_cols() -> usize {
+    if let Some(cols) = std::env::var("COLUMNS")
+        .ok()
+        .and_then(|cols| cols.parse().ok())
+    {
+        return cols;
+    }
+    if std::io::IsTerminal::is_terminal(&std::io::stdout()) {
+        80
+    } else {
+        0
+    }
+}
+
+/// Join an error with all its sources
+fn error_chain(e: &dyn Error) -> String {
+    let mut msg = e.to_string();
+    let mut source = e.source();
+    while let Some(e) = source {
+        msg.push_str(": ");
+        msg.push_str(&e.to_string());
+        source = e.source();
+    }
+    msg
+}
+
+struct Ctx<'a> {
+    args: &'a Args,
+    root: PathBuf,
+    db_path: PathBuf,
+    config: Config,
+    /// Sync databases, in configuration order
+    repos: Vec<SmolStr>,
+    tz: jiff::tz::TimeZone,
+    date_format: DateFormat,
+    /// Terminal width for wrapping `-i` output, 0 if not wrapping
+    cols: usize,
+    out: Output,
+}
+
+impl<'a> Ctx<'a> {
+    fn new(args: &'a Args) -> Result<Self> {
+        let config = match &args.config {
+            Some(path) => Some(Config::read(path)?),
+            None => match Config::read(DEFAULT_CONFIG_PATH) {
+                Ok(config) => Some(config),
+                Err(ConfigError::Io { source, .. })
+                    if source.kind() == std::io::ErrorKind::NotFound =>
+                {
+                    None
+                }
+                Err(e) => return Err(e.into()),
+            },
+        };
+        let found_config = config.is_some();
+        let config = config.unwrap_or_default();
+        // Like pacman, command line > pacman.conf > inside the root
+        let root = args
+            .root
+            .clone()
+            .or_else(|| config.root_dir.clone())
+            .unwrap_or_else(|| "/".into());
+        let db_path = args
+            .db_path
+            .clone()
+            .or_else(|| config.db_path.clone())
+            .unwrap_or_else(|| root.join("var/lib/pacman"));
+        let repos = if found_config {
+            config.repo_names().map(Into::into).collect()
+        } else {
+            // Without a configuration, use whatever databases were synced
+            let mut repos: Vec<SmolStr> = std::fs::read_dir(db_path.join("sync"))
+                .into_iter()
+                .flatten()
+                .filter_map(|entry| {
+                    let name = entry.ok()?.file_name();
+                    name.to_str()?.strip_suffix(".db").map(Into::into)
+                })
+                .collect();
+            repos.sort();
+            repos
+        };
+        Ok(Self {
+            args,
+            root,
+            db_path,
+            config,
+            repos,
+            tz: jiff::tz::TimeZone::system(),
+            date_format: DateFormat::from_env(),
+            cols: terminal_cols(),
+            out: Output::new(args.json, args.format.as_deref())?,
+        })
+    }
+    fn load_local(&self) -> Result<Vec<Pkg>> {
+        let (mut pkgs, format) =
+            alpacka::read_local_db_with_format(&self.db_path).map_err(|e| {
+                format!(
+                    "failed to read local database in {}: {}",
+                    self.db_path.display(),
+                    error_chain(&e)
+                )
+            })?;
+        // Local databases without a version are from before pacman 4.2
+        if format.is_legacy() || format.version.is_none() {
+            eprintln!(
+                "warning: local database in {} uses an old format ({})",
+                self.db_path.display(),
+                format
+                    .version
+                    .map_or_else(|| "unversioned".into(), |v| format!("version {v}"))
+            );
+        }
+        pkgs.sort_by(|a, b| a.desc.name.cmp(&b.desc.name));
+        Ok(pkgs)
+    }
+    /// Load the sync databases (`ext` "db") or files databases (`ext` "files")
+    fn load_sync(&self, ext: &str) -> Result<Vec<(SmolStr, Vec<Pkg>)>> {
+        let mut dbs = Vec::new();
+        for repo in &self.repos {
+            let path = self.db_path.join(format!("sync/{repo}.{ext}"));
+            if !path.exists() {
+                let op = if ext == "files" { "-Fy" } else { "-Sy" };
+                eprintln!(
+                    "warning: database file for '{repo}' does not exist (use '{op}' to download)"
+                );
+                continue;
+            }
+            let mut pkgs = alpacka::read_syncdb_file(&path)
+                .map_err(|e| format!("failed to read {}: {}", path.display(), error_chain(&e)))?;
+            pkgs.sort_by(|a, b| a.desc.name.cmp(&b.desc.name));
+            dbs.push((repo.clone(), pkgs));
+        }
+        Ok(dbs)
+    }
+}
+
+/// Find a package by name, or by something it provides
+fn find_satisfier<'a>(pkgs: &'a [Pkg], target: &str) -> Option<&'a Pkg> {
+    pkgs.iter().find(|pkg| pkg.desc.name == target).or_else(|| {
+        pkgs.iter()
+            .find(|pkg| pkg.desc.provides.iter().any(|prov| prov.name == target))
+    })
+}
+
+fn find_in_sync<'a>(sync: &'a [(SmolStr, Vec<Pkg>)], name: &str) -> Option<(&'a str, &'a Pkg)> {
+    sync.iter().find_map(|(repo, pkgs)| {
+        pkgs.iter()
+            .find(|pkg| pkg.desc.name == name)
+            .map(|pkg| (repo.as_str(), pkg))
+    })
+}
+
+/// Case-insensitive match of all `targets` against the name and description
+fn search_matches(pkg: &Pkg, targets: &[String]) -> bool {
+    let name = pkg.desc.name.to_lowercase();
+    let desc = pkg.desc.desc.as_deref().unwrap_or_default().to_lowercase();
+    targets.iter().all(|target| {
+        let target = target.to_lowercase();
+        name.contains(&target) || desc.contains(&target)
+    })
+}
+
+fn groups_suffix(pkg: &Pkg) -> String {
+    if pkg.desc.groups.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", pkg.desc.groups.join(" "))
+    }
+}
+
+fn query(ctx: &Ctx) -> Result<bool> {
+    let args = ctx.args;
+    let local = ctx.load_local()?;
+    if args.groups {
+        return Ok(query_groups(ctx, &local));
+    }
+    if args.owns {
+        return query_owns(ctx, &local);
+    }
+    if args.search {
+        return Ok(query_search(ctx, &local));
+    }
+    let sync = if args.foreign || args.native || args.upgrades {
+        ctx.load_sync("db")?
+    } else {
+        Vec::new()
+    };
+    let mut ok = true;
+    let selected: Vec<&Pkg> = if args.targets.is_empty() {
+        local.iter().collect()
+    } else {
+        args.targets
+            .iter()
+            .filter_map(|target| {
+                let pkg = find_satisfier(&local, target);
+                if pkg.is_none() {
+                    eprintln!("error: package '{target}' was not found");
+                    ok = false;
+                }
+                pkg
+            })
+            .collect()
+    };
+    let mut any = false;
+    for pkg in selected {
+        let upgrade = find_in_sync(&sync, &pkg.desc.name)
+            .map(|(_, sync_pkg)| sync_pkg)
+            .filter(|sync_pkg| args.upgrades && pkg.desc.version < sync_pkg.desc.version);
+        let keep = (!args.explicit || pkg.desc.install_reason == InstallReason::Explicit)
+            && (!args.deps || pkg.desc.install_reason == InstallReason::Dep)
+            && (args.unrequired == 0 || pkg.required_by(local.iter()).next().is_none())
+            && (args.unrequired < 2 || pkg.optional_for(local.iter()).next().is_none())
+            && (!args.foreign || find_in_sync(&sync, &pkg.desc.name).is_none())
+            && (!args.native || find_in_sync(&sync, &pkg.desc.name).is_some())
+            && (!args.upgrades || upgrade.is_some());
+        if !keep {
+            continue;
+        }
+        any = true;
+        ok &= query_output(ctx, pkg, &local, upgrade);
+    }
+    Ok(ok && any)
+}
+
+/// Print a package selected by `-Q`, returning whether its checks passed
+fn query_output(ctx: &Ctx, pkg: &Pkg, local: &[Pkg], upgrade: Option<&Pkg>) -> bool {
+    let args = ctx.args;
+    let mut ok = true;
+    if !ctx.out.is_text() {
+        let mut record = Record::new(pkg, None);
+        record.local = Some(local);
+        record.new_version = upgrade.map(|upgrade| upgrade.desc.version.as_str());
+        if args.list {
+            record.files = Some(
+                pkg.files
+                    .iter()
+                    .map(|file| ctx.root.join(&**file).display().to_string())
+                    .collect(),
+            );
+        }
+        if args.check > 0 {
+            let check = check_files(ctx, pkg);
+            ok &= check.problems.is_empty();
+            record.check = Some(check);
+        }
+        record.backup_root = (args.info > 1).then_some(ctx.root.as_path());
+        ctx.out.record(&record, &ctx.tz);
+        return ok;
+    }
+    if args.info > 0 {
+        let mut info = PkgInfo::local(pkg, local)
+            .cols(ctx.cols)
+            .time_zone(ctx.tz.clone())
+            .date_format(ctx.date_format.clone());
+        if args.info > 1 {
+            info = info.backup_files(&ctx.root);
+        }
+        print!("{info}");
+    }
+    if args.list {
+        for file in &pkg.files {
+            let path = ctx.root.join(&**file);
+            if args.quiet {
+                println!("{}", path.display());
+            } else {
+                println!("{} {}", pkg.desc.name, path.display());
+            }
+        }
+    }
+    if args.check > 0 {
+        let check = check_files(ctx, pkg);
+        print_check(ctx, pkg, &check);
+        ok &= check.problems.is_empty();
+    }
+    if args.info > 0 || args.list || args.check > 0 {
+        return ok;
+    }
+    if args.quiet {
+        println!("{}", pkg.desc.name);
+    } else if let Some(upgrade) = upgrade {
+        let ignored = if ctx.config.ignore_pkgs.contains(&pkg.desc.name) {
+            " [ignored]"
+        } else {
+            ""
+        };
+        println!(
+            "{} {} -> {}{ignored}",
+            pkg.desc.name, pkg.desc.version, upgrade.desc.version
+        );
+    } else {
+        println!("{} {}", pkg.desc.name, pkg.desc.version);
+    }
+    ok
+}
+
+/// Check that the files of `pkg` exist (`-k`), and match its `mtree` file (`-kk`)
+///
+/// Without an `mtree` file, `-kk` only checks the file types.
+/// The contents of backup files are expected to change, so only their type
+/// and permissions are checked.
+fn check_files(ctx: &Ctx, pkg: &Pkg) -> FileCheck {
+    let mut problems = Vec::new();
+    let io_problem = |e: &std::io::Error| match e.kind() {
+        std::io::ErrorKind::NotFound => "No such file or directory".to_owned(),
+        _ => e.to_string(),
+    };
+    let mtree = if ctx.args.check > 1 {
+        mtree::read_local(&ctx.db_path, &pkg.desc).unwrap_or_else(|e| {
+            let path = ctx.db_path.join(format!(
+                "local/{}-{}/mtree",
+                pkg.desc.name, pkg.desc.version
+            ));
+            problems.push((path, io_problem(&e)));
+            None
+        })
+    } else {
+        None
+    };
+    if let Some(mtree) = mtree {
+        for entry in mtree.iter().filter(|entry| !entry.is_metadata()) {
+            let path = ctx.root.join(&*entry.path);
+            let is_backup = pkg.backup.iter().any(|backup| *backup.path == *entry.path);
+            match entry.compare(&ctx.root) {
+                Err(e) => problems.push((path, io_problem(&e))),
+                Ok(mismatches) => problems.extend(
+                    mismatches
+                        .into_iter()
+                        .filter(|mismatch| {
+                            !is_backup
+                                || !matches!(mismatch, Mismatch::Size { .. } | Mismatch::Checksum)
+                        })
+                        .map(|mismatch| (path.clone(), mismatch.to_string())),
+                ),
+            }
+        }
+    } else {
+        for file in &pkg.files {
+            let path = ctx.root.join(&**file);
+            let problem = match path.symlink_metadata() {
+                Err(e) => Some(io_problem(&e)),
+                Ok(meta) if ctx.args.check > 1 && meta.is_dir() != file.ends_with('/') => {
+                    Some("File type mismatch".to_owned())
+                }
+                Ok(_) => None,
+            };
+            if let Some(problem) = problem {
+                problems.push((path, problem));
+            }
+        }
+    }
+    FileCheck {
+        total: pkg.files.len(),
+        problems,
+    }
+}
+
+fn print_check(ctx: &Ctx, pkg: &Pkg, check: &FileCheck) {
+    for (path, problem) in &check.problems {
+        if ctx.args.quiet {
+            println!("{} {}", pkg.desc.name, path.display());
+        } else {
+            eprintln!("warning: {}: {} ({problem})", pkg.desc.name, path.display());
+        }
+    }
+    if !ctx.args.quiet {
+        let kind = if ctx.args.check > 1 {
+            "altered"
+        } else {
+            "missing"
+        };
+        println!(
+            "{}: {} total files, {} {kind} files",
+            pkg.desc.name,
+            check.total,
+            check.problems.len()
+        );
+    }
+}
+
+fn query_groups(ctx: &Ctx, local: &[Pkg]) -> bool {
+    let groups = group::groups(local.iter());
+    let mut ok = true;
+    let print = |group: &str, members: &[&Pkg]| {
+        for pkg in members {
+            if !ctx.out.is_text() {
+                let mut record = Record::new(pkg, None);
+                record.group = Some(group);
+                ctx.out.record(&record, &ctx.tz);
+            } else if ctx.args.quiet {
+                println!("{}", pkg.desc.name);
+            } else {
+                println!("{group} {}", pkg.desc.name);
+            }
+        }
+    };
+    if ctx.args.targets.is_empty() {
+        for (group, members) in &groups {
+            print(group, members);
+        }
+    }
+    for target in &ctx.args.targets {
+        let Some(members) = groups.get(target.as_str()) else {
+            eprintln!("error: group '{target}' was not found");
+            ok = false;
+            continue;
+        };
+        print(target, members);
+    }
+    ok
+}
+
+/// Resolve a `-Qo` target to an absolute path, searching `PATH` for bare names
+///
+/// Like pacman, symlinks and `..` are resolved in the parent directory,
+/// but the file name is kept, so a symlink is looked up as itself.
+fn owns_target_path(target: &str) -> Result<PathBuf> {
+    let path = if target.contains('/') {
+        std::env::current_dir()?.join(target)
+    } else {
+        let found = std::env::var_os("PATH").and_then(|paths| {
+            std::env::split_paths(&paths)
+                .map(|dir| dir.join(target))
+                .find(|path| path.exists())
+        });
+        found.ok_or_else(|| format!("failed to find '{target}' in PATH"))?
+    };
+    let real_path = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => parent.canonicalize().map(|parent| parent.join(name)),
+        // The root itself, or a path ending in `..`
+        _ => path.canonicalize(),
+    };
+    real_path.map_err(|e| format!("cannot determine real path for '{target}': {e}").into())
+}
+
+fn query_owns(ctx: &Ctx, local: &[Pkg]) -> Result<bool> {
+    if ctx.args.targets.is_empty() {
+        return Err("no targets specified".into());
+    }
+    let root = ctx.root.canonicalize().unwrap_or_else(|_| ctx.root.clone());
+    let mut ok = true;
+    for target in &ctx.args.targets {
+        let path = match owns_target_path(target) {
+            Ok(path) => path,
+            Err(e) => {
+                eprintln!("error: {e}");
+                ok = false;
+                continue;
+            }
+        };
+        let Ok(rel) = path.strip_prefix(&root) else {
+            eprintln!("error: {} is not in the root", path.display());
+            ok = false;
+            continue;
+        };
+        let rel = rel.to_string_lossy();
+        let rel_dir = format!("{rel}/");
+        let mut owned = false;
+        for pkg in local {
+            if pkg
+                .files
+                .iter()
+                .any(|file| **file == *rel || **file == rel_dir)
+            {
+                owned = true;
+                if !ctx.out.is_text() {
+                    let mut record = Record::new(pkg, None);
+                    record.files = Some(vec![path.display().to_string()]);
+                    ctx.out.record(&record, &ctx.tz);
+                } else if ctx.args.quiet {
+                    println!("{}", pkg.desc.name);
+                } else {
+                    println!(
+                        "{} is owned by {} {}",
+                        path.display(),
+                        pkg.desc.name,
+                        pkg.desc.version
+                    );
+                }
+            }
+        }
+        if !owned {
+            eprintln!("error: No package owns {}", path.display());
+            ok = false;
+        }
+    }
+    Ok(ok)
+}
+
+fn query_search(ctx: &Ctx, local: &[Pkg]) -> bool {
+    let mut any = false;
+    for pkg in local
+        .iter()
+        .filter(|pkg| search_matches(pkg, &ctx.args.targets))
+    {
+        any = true;
+        if !ctx.out.is_text() {
+            let mut record = Record::new(pkg, None);
+            record.local = Some(local);
+            ctx.out.record(&record, &ctx.tz);
+        } else if ctx.args.quiet {
+            println!("{}", pkg.desc.name);
+        } else {
+            println!(
+                "local/{} {}{}",
+                pkg.desc.name,
+                pkg.desc.version,
+                groups_suffix(pkg)
+            );
+            println!("    {}", pkg.desc.desc.as_deref().unwrap_or_default());
+        }
+    }
+    any
+}
+
+fn sync(ctx: &Ctx) -> Result<bool> {
+    let args = ctx.args;
+    let sync = ctx.load_sync("db")?;
+    if args.groups {
+        return sync_groups(ctx, &sync);
+    }
+    if args.list {
+        return sync_list(ctx, &sync);
+    }
+    if args.search {
+        return sync_search(ctx, &sync);
+    }
+    let local = ctx.load_local()?;
+    if args.targets.is_empty() {
+        for (repo, pkgs) in &sync {
+            for pkg in pkgs {
+                sync_info(ctx, pkg, repo, &local);
+            }
+        }
+        return Ok(true);
+    }
+    let mut ok = true;
+    for target in &args.targets {
+        let (repo_filter, name) = match target.split_once('/') {
+            Some((repo, name)) => (Some(repo), name),
+            None => (None, target.as_str()),
+        };
+        let found = sync
+            .iter()
+            .filter(|(repo, _)| repo_filter.is_none_or(|filter| filter == repo))
+            .find_map(|(repo, pkgs)| {
+                pkgs.iter()
+                    .find(|pkg| pkg.desc.name == name)
+                    .map(|pkg| (repo, pkg))
+            });
+        let Some((repo, pkg)) = found else {
+            eprintln!("error: package '{target}' was not found");
+            ok = false;
+            continue;
+        };
+        sync_info(ctx, pkg, repo, &local);
+    }
+    Ok(ok)
+}
+
+fn sync_info(ctx: &Ctx, pkg: &Pkg, repo: &str, local: &[Pkg]) {
+    if ctx.out.is_text() {
+        let info = PkgInfo::sync(pkg, repo)
+            .local_pkgs(local)
+            .cols(ctx.cols)
+            .time_zone(ctx.tz.clone())
+            .date_format(ctx.date_format.clone());
+        print!("{info}");
+    } else {
+        ctx.out.record(&Record::new(pkg, Some(repo)), &ctx.tz);
+    }
+}
+
+/// Suffix marking a sync package as installed, like pacman's `-Ss` and `-Sl`
+fn installed_suffix(pkg: &Pkg, local: &[Pkg]) -> String {
+    match local.iter().find(|local| local.desc.name == pkg.desc.name) {
+        Some(local) if local.desc.version == pkg.desc.version => " [installed]".into(),
+        Some(local) => format!(" [installed: {}]", local.desc.version),
+        None => String::new(),
+    }
+}
+
+fn sync_search(ctx: &Ctx, sync: &[(SmolStr, Vec<Pkg>)]) -> Result<bool> {
+    let local = ctx.load_local()?;
+    let mut any = false;
+    for (repo, pkgs) in sync {
+        for pkg in pkgs
+            .iter()
+            .filter(|pkg| search_matches(pkg, &ctx.args.targets))
+        {
+            any = true;
+            if !ctx.out.is_text() {
+                ctx.out.record(&Record::new(pkg, Some(repo)), &ctx.tz);
+            } else if ctx.args.quiet {
+                println!("{}", pkg.desc.name);
+            } else {
+                println!(
+                    "{repo}/{} {}{}{}",
+                    pkg.desc.name,
+                    pkg.desc.version,
+                    groups_suffix(pkg),
+                    installed_suffix(pkg, &local)
+                );
+                println!("    {}", pkg.desc.desc.as_deref().unwrap_or_default());
+            }
+        }
+    }
+    Ok(any)
+}
+
+fn sync_groups(ctx: &Ctx, sync: &[(SmolStr, Vec<Pkg>)]) -> Result<bool> {
+    let all = || sync.iter().flat_map(|(_, pkgs)| pkgs);
+    let groups = group::groups(all());
+    if ctx.args.targets.is_empty() && !ctx.out.is_text() {
+        for (group, members) in &groups {
+            for pkg in members {
+                let repo = find_in_sync(sync, &pkg.desc.name).map(|(repo, _)| repo);
+                let mut record = Record::new(pkg, repo);
+                record.group = Some(group);
+                ctx.out.record(&record, &ctx.tz);
+            }
+        }
+        return Ok(true);
+    }
+    if ctx.args.targets.is_empty() {
+        for group in groups.keys() {
+            println!("{group}");
+        }
+        return Ok(true);
+    }
+    let local = ctx.load_local()?;
+    let mut ok = true;
+    for target in &ctx.args.targets {
+        let Some(members) = groups.get(target.as_str()) else {
+            eprintln!("error: group '{target}' was not found");
+            ok = false;
+            continue;
+        };
+        let missing: Vec<_> = group::missing_group_members(target, all(), local.iter()).collect();
+        for pkg in members {
+            if !ctx.out.is_text() {
+                let repo = find_in_sync(sync, &pkg.desc.name).map(|(repo, _)| repo);
+                let mut record = Record::new(pkg, repo);
+                record.group = Some(target);
+                ctx.out.record(&record, &ctx.tz);
+                continue;
+            }
+            if ctx.args.quiet {
+                println!("{}", pkg.desc.name);
+                continue;
+            }
+            let installed = if missing.iter().any(|m| m.desc.name == pkg.desc.name) {
+                ""
+            } else {
+                " [installed]"
+            };
+            println!("{target} {}{installed}", pkg.desc.name);
+        }
+    }
+    Ok(ok)
+}
+
+fn sync_list(ctx: &Ctx, sync: &[(SmolStr, Vec<Pkg>)]) -> Result<bool> {
+    let mut ok = true;
+    for target in &ctx.args.targets {
+        if !sync.iter().any(|(repo, _)| repo == target) {
+            eprintln!("error: repository \"{target}\" was not found.");
+            ok = false;
+        }
+    }
+    let local = ctx.load_local()?;
+    for (repo, pkgs) in sync {
+        if !ctx.args.targets.is_empty() && !ctx.args.targets.iter().any(|t| t == repo) {
+            continue;
+        }
+        for pkg in pkgs {
+            if !ctx.out.is_text() {
+                ctx.out.record(&Record::new(pkg, Some(repo)), &ctx.tz);
+            } else if ctx.args.quiet {
+                println!("{}", pkg.desc.name);
+            } else {
+                println!(
+                    "{repo} {} {}{}",
+                    pkg.desc.name,
+                    pkg.desc.version,
+                    installed_suffix(pkg, &local)
+                );
+            }
+        }
+    }
+    Ok(ok)
+}
+
+fn files(ctx: &Ctx) -> Result<bool> {
+    let args = ctx.args;
+    let dbs = ctx.load_sync("files")?;
+    if args.list {
+        return Ok(files_list(ctx, &dbs));
+    }
+    if args.targets.is_empty() {
+        return Err("no targets specified".into());
+    }
+    let mut any = false;
+    for target in &args.targets {
+        let target = target.strip_prefix('/').unwrap_or(target);
+        let file_matches = |file: &str| {
+            if target.contains('/') {
+                file == target
+            } else {
+                !file.ends_with('/') && Path::new(file).file_name().is_some_and(|n| n == target)
+            }
+        };
+        for (repo, pkgs) in &dbs {
+            for pkg in pkgs {
+                let mut matches = pkg
+                    .files
+                    .iter()
+                    .filter(|file| file_matches(file))
+                    .peekable();
+                if matches.peek().is_none() {
+                    continue;
+                }
+                any = true;
+                if !ctx.out.is_text() {
+                    let mut record = Record::new(pkg, Some(repo));
+                    record.files = Some(matches.map(ToString::to_string).collect());
+                    ctx.out.record(&record, &ctx.tz);
+                    continue;
+                }
+                if args.quiet {
+                    println!("{repo}/{}", pkg.desc.name);
+                    continue;
+                }
+                println!(
+                    "{repo}/{} {}{}",
+                    pkg.desc.name,
+                    pkg.desc.version,
+                    groups_suffix(pkg)
+                );
+                for file in matches {
+                    println!("    {file}");
+                }
+            }
+        }
+    }
+    Ok(any)
+}
+
+fn files_list(ctx: &Ctx, dbs: &[(SmolStr, Vec<Pkg>)]) -> bool {
+    let print = |repo: &str, pkg: &Pkg| {
+        if !ctx.out.is_text() {
+            let mut record = Record::new(pkg, Some(repo));
+            record.files = Some(pkg.files.iter().map(ToString::to_string).collect());
+            ctx.out.record(&record, &ctx.tz);
+            return;
+        }
+        for file in &pkg.files {
+            if ctx.args.quiet {
+                println!("{file}");
+            } else {
+                println!("{} {file}", pkg.desc.name);
+            }
+        }
+    };
+    if ctx.args.targets.is_empty() {
+        for (repo, pkgs) in dbs {
+            for pkg in pkgs {
+                print(repo, pkg);
+            }
+        }
+        return true;
+    }
+    let mut ok = true;
+    for target in &ctx.args.targets {
+        let Some((repo, pkg)) = find_in_sync(dbs, target) else {
+            eprintln!("error: package '{target}' was not found");
+            ok = false;
+            continue;
+        };
+        print(repo, pkg);
+    }
+    ok
+}
@@ -0,0 +1,368 @@
+//! Machine readable output: `--json` and `--format`
+
+use {
+    alpacka::{
+        BackupStatus, InstallReason, Pkg, PkgDesc, Validation,
+        dep::PkgDepsExt,
+        display::{self, DateFormat},
+        json::Json,
+    },
+    std::{
+        cell::RefCell,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Version of the `--json` document layout.
+/// Bump when fields are removed or change meaning; adding fields is compatible.
+pub const JSON_SCHEMA_VERSION: u64 = 1;
+
+/// Result of checking the files of a package (`-Qk`)
+pub struct FileCheck {
+    pub total: usize,
+    /// Paths with problems, and a description of the problem
+    pub problems: Vec<(PathBuf, String)>,
+}
+
+/// A package in the results of an operation, with operation specific details
+pub struct Record<'a> {
+    pub pkg: &'a Pkg,
+    /// Repository of a sync package, `None` for installed packages
+    pub repo: Option<&'a str>,
+    /// Installed packages, for required by/optional for of local packages
+    pub local: Option<&'a [Pkg]>,
+    /// The group the package was listed for (`-g`)
+    pub group: Option<&'a str>,
+    /// Newer version in the sync databases (`-Qu`)
+    pub new_version: Option<&'a str>,
+    /// Listed (`-l`) or matched (`-o`, `-F`) files
+    pub files: Option<Vec<String>>,
+    pub check: Option<FileCheck>,
+    /// Root to check the state of backup files under (`-ii`)
+    pub backup_root: Option<&'a Path>,
+}
+
+impl<'a> Record<'a> {
+    pub fn new(pkg: &'a Pkg, repo: Option<&'a str>) -> Self {
+        Self {
+            pkg,
+            repo,
+            local: None,
+            group: None,
+            new_version: None,
+            files: None,
+            check: None,
+            backup_root: None,
+        }
+    }
+}
+
+pub enum Output {
+    Text,
+    /// Records are collected, and printed as one document at the end
+    Json(RefCell<Vec<Json>>),
+    Format(Vec<Token>),
+}
+
+impl Output {
+    pub fn new(json: bool, format: Option<&str>) -> Result<Self, String> {
+        match (json, format) {
+            (true, Some(_)) => Err("--json and --format can't be used together".into()),
+            (true, None) => Ok(Self::Json(RefCell::default())),
+            (false, Some(format)) => Ok(Self::Format(parse_template(format)?)),
+            (false, None) => Ok(Self::Text),
+        }
+    }
+    pub fn is_text(&self) -> bool {
+        matches!(self, Self::Text)
+    }
+    pub fn record(&self, record: &Record, tz: &jiff::tz::TimeZone) {
+        match self {
+            Self::Text => {}
+            Self::Json(records) => records.borrow_mut().push(record_json(record)),
+            Self::Format(tokens) => println!("{}", format_record(tokens, record, tz)),
+        }
+    }
+    /// Print the collected JSON document, if any
+    pub fn finish(&self, operation: &str) {
+        if let Self::Json(records) = self {
+            let doc = Json::object(vec![
+                ("schema_version", Json::uint(JSON_SCHEMA_VERSION)),
+                ("operation", Json::Str(operation.into())),
+                ("results", Json::Array(records.take())),
+            ]);
+            println!("{doc}");
+        }
+    }
+}
+
+fn validation_name(validation: Validation) -> &'static str {
+    match validation {
+        Validation::Pgp => "pgp",
+        Validation::Sha256 => "sha256",
+        Validation::Md5 => "md5",
+    }
+}
+
+fn backup_status_name(status: BackupStatus) -> &'static str {
+    match status {
+        BackupStatus::Unmodified => "unmodified",
+        BackupStatus::Modified => "modified",
+        BackupStatus::Missing => "missing",
+        BackupStatus::Unreadable => "unreadable",
+    }
+}
+
+/// Fields describing the package itself
+fn desc_json(desc: &PkgDesc, repo: Option<&str>) -> Vec<(&'static str, Json)> {
+    let local = repo.is_none();
+    let num_if = |cond: bool, n: u64| if cond { Json::uint(n) } else { Json::Null };
+    vec![
+        ("name", Json::Str(desc.name.to_string())),
+        ("version", Json::Str(desc.version.to_string())),
+        ("base", Json::opt_str(desc.base.as_deref())),
+        ("description", Json::opt_str(desc.desc.as_deref())),
+        ("arch", Json::Str(desc.arch.to_string())),
+        ("url", Json::opt_str(desc.url.as_deref())),
+        ("repository", Json::opt_str(repo)),
+        ("licenses", Json::strs(&desc.licenses)),
+        ("groups", Json::strs(&desc.groups)),
+        ("provides", Json::strs(&desc.provides)),
+        ("depends", Json::strs(&desc.depends)),
+        (
+            "optdepends",
+            Json::Array(
+                desc.opt_depends
+                    .iter()
+                    .map(|opt| {
+                        Json::object(vec![
+                            ("depend", Json::Str(opt.dep.to_string())),
+                            ("reason", Json::opt_str(opt.reason.as_deref())),
+                        ])
+                    })
+                    .collect(),
+            ),
+        ),
+        ("makedepends", Json::strs(&desc.make_depends)),
+        ("checkdepends", Json::strs(&desc.check_depends)),
+        ("conflicts", Json::strs(&desc.conflicts)),
+        ("replaces", Json::strs(&desc.replaces)),
+        ("installed_size", Json::uint(desc.size)),
+        ("download_size", num_if(!local, desc.c_size)),
+        ("packager", Json::opt_str(desc.packager.as_deref())),
+        ("build_date", Json::uint(desc.build_date)),
+        ("install_date", num_if(local, desc.install_date)),
+        (
+            "install_reason",
+            match (local, desc.install_reason) {
+                (false, _) => Json::Null,
+                (true, InstallReason::Explicit) => Json::Str("explicit".into()),
+                (true, InstallReason::Dep) => Json::Str("dependency".into()),
+            },
+        ),
+        ("install_script", Json::Bool(desc.install_script)),
+        (
+            "validated_by",
+            Json::Array(
+                desc.validations
+                    .iter()
+                    .map(|v| Json::Str(validation_name(*v).into()))
+                    .collect(),
+            ),
+        ),
+        ("filename", Json::opt_str(desc.filename.as_deref())),
+        ("sha256sum", Json::opt_str(desc.sha256sum.as_deref())),
+    ]
+}
+
+fn record_json(record: &Record) -> Json {
+    let desc = &record.pkg.desc;
+    let mut fields = desc_json(desc, record.repo);
+    if let Some(local) = record.local {
+        let names = |pkgs: Vec<&Pkg>| {
+            Json::Array(
+                pkgs.iter()
+                    .map(|pkg| Json::Str(pkg.desc.name.to_string()))
+                    .collect(),
+            )
+        };
+        fields.push((
+            "required_by",
+            names(desc.required_by(local.iter()).collect()),
+        ));
+        fields.push((
+            "optional_for",
+            names(desc.optional_for(local.iter()).collect()),
+        ));
+    }
+    if let Some(group) = record.group {
+        fields.push(("group", Json::Str(group.into())));
+    }
+    if let Some(new_version) = record.new_version {
+        fields.push(("new_version", Json::Str(new_version.into())));
+    }
+    if let Some(files) = &record.files {
+        fields.push(("files", Json::strs(files)));
+    }
+    if let Some(check) = &record.check {
+        let problems = check
+            .problems
+            .iter()
+            .map(|(path, problem)| {
+                Json::object(vec![
+                    ("path", Json::Str(path.display().to_string())),
+                    ("problem", Json::Str(problem.clone())),
+                ])
+            })
+            .collect();
+        fields.push((
+            "check",
+            Json::object(vec![
+                ("total_files", Json::uint(check.total as u64)),
+                ("problems", Json::Array(problems)),
+            ]),
+        ));
+    }
+    if let Some(root) = record.backup_root {
+        let backup = record
+            .pkg
+            .backup
+            .iter()
+            .map(|backup| {
+                Json::object(vec![
+                    ("path", Json::Str(format!("/{}", backup.path))),
+                    (
+                        "status",
+                        Json::Str(backup_status_name(backup.status(root)).into()),
+                    ),
+                ])
+            })
+            .collect();
+        fields.push(("backup", Json::Array(backup)));
+    }
+    Json::object(fields)
+}
+
+/// A piece of a `--format` template
+pub enum Token {
+    Literal(String),
+    Field(char),
+}
+
+/// Fields supported in `--format` templates, following expac
+const FIELDS: &str = "nvedaurLGPSDEOoCHRTmkpblwifhVNFB";
+
+fn parse_template(src: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut literal = String::new();
+    let mut chars = src.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => match chars.next() {
+                Some('%') => literal.push('%'),
+                Some(field) if FIELDS.contains(field) => {
+                    if !literal.is_empty() {
+                        tokens.push(Token::Literal(std::mem::take(&mut literal)));
+                    }
+                    tokens.push(Token::Field(field));
+                }
+                Some(field) => return Err(format!("invalid format field '%{field}'")),
+                None => return Err("format ends with a lone '%'".into()),
+            },
+            '\\' => match chars.next() {
+                Some('n') => literal.push('\n'),
+                Some('t') => literal.push('\t'),
+                Some('\\') | None => literal.push('\\'),
+                Some(other) => {
+                    literal.push('\\');
+                    literal.push(other);
+                }
+            },
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        tokens.push(Token::Literal(literal));
+    }
+    Ok(tokens)
+}
+
+/// Separator between list items, like expac's default
+const LIST_SEP: &str = "  ";
+
+fn join<T: ToString>(items: impl IntoIterator<Item = T>) -> String {
+    items
+        .into_iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(LIST_SEP)
+}
+
+/// Name part of a dependency string like `foo>=1.0`
+fn dep_name(dep: &str) -> &str {
+    dep.split(['<', '>', '=']).next().unwrap_or_default()
+}
+
+/// Dates in templates always use the same style, so they are easy to parse
+fn format_date(secs: u64, tz: &jiff::tz::TimeZone) -> String {
+    display::format_date(secs, tz, &DateFormat::EnUs).unwrap_or_default()
+}
+
+fn format_record(tokens: &[Token], record: &Record, tz: &jiff::tz::TimeZone) -> String {
+    let desc = &record.pkg.desc;
+    let mut out = String::new();
+    for token in tokens {
+        let field = match token {
+            Token::Literal(s) => {
+                out.push_str(s);
+                continue;
+            }
+            Token::Field(field) => *field,
+        };
+        let value = match field {
+            'n' => desc.name.to_string(),
+            'v' => desc.version.to_string(),
+            'e' => desc.base.as_deref().unwrap_or_default().into(),
+            'd' => desc.desc.as_deref().unwrap_or_default().into(),
+            'a' => desc.arch.to_string(),
+            'u' => desc.url.as_deref().unwrap_or_default().into(),
+            'r' => record.repo.unwrap_or("local").into(),
+            'L' => join(&desc.licenses),
+            'G' => join(&desc.groups),
+            'P' => join(&desc.provides),
+            'S' => join(desc.provides.iter().map(|dep| &dep.name)),
+            'D' => join(&desc.depends),
+            'E' => join(desc.depends.iter().map(|dep| &dep.name)),
+            'O' => join(&desc.opt_depends),
+            'o' => join(desc.opt_depends.iter().map(|opt| &opt.dep)),
+            'C' => join(&desc.conflicts),
+            'H' => join(desc.conflicts.iter().map(|dep| dep_name(dep))),
+            'R' => join(&desc.replaces),
+            'T' => join(desc.replaces.iter().map(|dep| dep_name(dep))),
+            'm' => desc.size.to_string(),
+            'k' => desc.c_size.to_string(),
+            'p' => desc.packager.as_deref().unwrap_or_default().into(),
+            'b' => format_date(desc.build_date, tz),
+            'l' if record.repo.is_none() => format_date(desc.install_date, tz),
+            'w' if record.repo.is_none() => match desc.install_reason {
+                InstallReason::Explicit => "explicit".into(),
+                InstallReason::Dep => "dependency".into(),
+            },
+            'i' => if desc.install_script { "yes" } else { "no" }.into(),
+            'f' => desc.filename.as_deref().unwrap_or_default().into(),
+            'h' => desc.sha256sum.as_deref().unwrap_or_default().into(),
+            'V' => join(desc.validations.iter().copied().map(validation_name)),
+            'N' => record.local.map_or_else(String::new, |local| {
+                join(desc.required_by(local.iter()).map(|pkg| &pkg.desc.name))
+            }),
+            'F' => match &record.files {
+                Some(files) => join(files),
+                None => join(&record.pkg.files),
+            },
+            'B' => join(record.pkg.backup.iter().map(|backup| &backup.path)),
+            _ => String::new(),
+        };
+        out.push_str(&value);
+    }
+    out
+}
@@ -0,0 +1,392 @@
+//! Inventory and cleanup planning for the package cache (like `paccache`)
+
+use {
+    crate::{Pkg, version::Version},
+    sha2::{Digest, Sha256},
+    smol_str::SmolStr,
+    std::{
+        collections::BTreeMap,
+        io::Read,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Default location of the package cache
+pub const DEFAULT_CACHE_DIR: &str = "/var/cache/pacman/pkg";
+
+const PKG_EXTS: [&str; 10] = [
+    ".pkg.tar",
+    ".pkg.tar.gz",
+    ".pkg.tar.bz2",
+    ".pkg.tar.xz",
+    ".pkg.tar.zst",
+    ".pkg.tar.lz4",
+    ".pkg.tar.lrz",
+    ".pkg.tar.lzo",
+    ".pkg.tar.lz",
+    ".pkg.tar.Z",
+];
+
+/// The components of a package file name, like `foo-1:2.0-1-x86_64.pkg.tar.zst`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PkgFileName {
+    pub name: SmolStr,
+    /// Full version, including epoch and pkgrel
+    pub version: Version,
+    pub arch: SmolStr,
+    /// Extension, including the leading `.pkg.tar`
+    pub ext: SmolStr,
+}
+
+impl PkgFileName {
+    /// Parse a package file name
+    ///
+    /// Returns `None` if `file_name` isn't a package file name.
+    #[must_use]
+    pub fn parse(file_name: &str) -> Option<Self> {
+        let ext_pos = file_name.rfind(".pkg.tar")?;
+        let (stem, ext) = file_name.split_at(ext_pos);
+        if !PKG_EXTS.contains(&ext) {
+            return None;
+        }
+        let mut parts = stem.rsplitn(4, '-');
+        let arch = parts.next()?;
+        let pkgrel = parts.next()?;
+        let pkgver = parts.next()?;
+        let name = parts.next()?;
+        if [name, pkgver, pkgrel, arch].iter().any(|s| s.is_empty()) {
+            return None;
+        }
+        Some(Self {
+            name: name.into(),
+            version: Version::new(format!("{pkgver}-{pkgrel}")),
+            arch: arch.into(),
+            ext: ext.into(),
+        })
+    }
+}
+
+/// A package file in the cache
+#[derive(Debug, Clone)]
+pub struct CachedPkg {
+    pub path: PathBuf,
+    pub file_name: PkgFileName,
+    pub size: u64,
+    /// Detached signature (`.sig`) belonging to this package, and its size
+    pub sig: Option<(PathBuf, u64)>,
+}
+
+impl CachedPkg {
+    /// Size of the package file together with its signature
+    #[must_use]
+    pub fn total_size(&self) -> u64 {
+        self.size + self.sig.as_ref().map_or(0, |(_, size)| *size)
+    }
+    /// Find the installed package with this name, if any
+    #[must_use]
+    pub fn local_pkg<'pkgs>(&self, local_pkgs: &'pkgs [Pkg]) -> Option<&'pkgs Pkg> {
+        local_pkgs
+            .iter()
+            .find(|pkg| pkg.desc.name == self.file_name.name)
+    }
+    /// Whether this file is the currently installed version of its package
+    #[must_use]
+    pub fn is_installed_version(&self, local_pkgs: &[Pkg]) -> bool {
+        self.local_pkg(local_pkgs).is_some_and(|pkg| {
+            pkg.desc.version == self.file_name.version && pkg.desc.arch == self.file_name.arch
+        })
+    }
+}
+
+/// The contents of a package cache directory
+#[derive(Debug, Default)]
+pub struct Cache {
+    pub pkgs: Vec<CachedPkg>,
+    /// Signatures whose package file is missing, and their sizes
+    pub orphan_sigs: Vec<(PathBuf, u64)>,
+    /// Files that aren't packages or signatures, like partial downloads
+    pub other: Vec<PathBuf>,
+}
+
+/// Scan the package cache at `dir`
+pub fn scan(dir: impl AsRef<Path>) -> std::io::Result<Cache> {
+    let mut cache = Cache::default();
+    let mut sigs = BTreeMap::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        if !meta.is_file() {
+            continue;
+        }
+        let path = entry.path();
+        let file_name = entry.file_name();
+        let Some(file_name) = file_name.to_str() else {
+            cache.other.push(path);
+            continue;
+        };
+        if let Some(pkg_file_name) = file_name.strip_suffix(".sig") {
+            if PkgFileName::parse(pkg_file_name).is_some() {
+                sigs.insert(pkg_file_name.to_owned(), (path, meta.len()));
+            } else {
+                cache.other.push(path);
+            }
+            continue;
+        }
+        match PkgFileName::parse(file_name) {
+            Some(parsed) => cache.pkgs.push(CachedPkg {
+                path,
+                file_name: parsed,
+                size: meta.len(),
+                sig: None,
+            }),
+            None => cache.other.push(path),
+        }
+    }
+    for pkg in &mut cache.pkgs {
+        let file_name = pkg.path.file_name().and_then(|name| name.to_str());
+        pkg.sig = file_name.and_then(|name| sigs.remove(name));
+    }
+    cache.orphan_sigs = sigs.into_values().collect();
+    cache.pkgs.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(cache)
+}
+
+/// The cached versions of a package (of a single architecture)
+#[derive(Debug)]
+pub struct CachedVersions<'cache, 'local> {
+    pub name: &'cache str,
+    pub arch: &'cache str,
+    /// The installed package with this name, if any
+    pub local: Option<&'local Pkg>,
+    /// Cached files, most recent version first
+    pub files: Vec<&'cache CachedPkg>,
+}
+
+/// Which packages a cleanup plan considers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CleanupFilter {
+    #[default]
+    All,
+    /// Only packages that are installed (`paccache -i`)
+    Installed,
+    /// Only packages that are not installed (`paccache -u`)
+    Uninstalled,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CleanupOptions {
+    /// How many of the most recent versions of each package to keep
+    pub keep: usize,
+    pub filter: CleanupFilter,
+}
+
+impl Default for CleanupOptions {
+    /// Same as `paccache`: keep the 3 most recent versions of every package
+    fn default() -> Self {
+        Self {
+            keep: 3,
+            filter: CleanupFilter::All,
+        }
+    }
+}
+
+/// Files a cleanup would remove
+#[derive(Debug, Default)]
+pub struct RemovalPlan<'cache> {
+    pub pkgs: Vec<&'cache CachedPkg>,
+    /// Orphaned signatures of the packages the filter considers
+    pub orphan_sigs: Vec<&'cache Path>,
+    /// Total amount of bytes freed, including signatures
+    pub size: u64,
+}
+
+impl RemovalPlan<'_> {
+    /// All paths that would be removed
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.pkgs
+            .iter()
+            .flat_map(|pkg| {
+                std::iter::once(pkg.path.as_path())
+                    .chain(pkg.sig.as_ref().map(|(path, _)| path.as_path()))
+            })
+            .chain(self.orphan_sigs.iter().copied())
+    }
+}
+
+impl Cache {
+    /// Group the cached files by package and architecture, linked to the installed packages
+    ///
+    /// Groups are sorted by name, then architecture.
+    #[must_use]
+    pub fn versions<'cache, 'local>(
+        &'cache self,
+        local_pkgs: &'local [Pkg],
+    ) -> Vec<CachedVersions<'cache, 'local>> {
+        let mut groups: BTreeMap<(&str, &str), Vec<&CachedPkg>> = BTreeMap::new();
+        for pkg in &self.pkgs {
+            groups
+                .entry((&pkg.file_name.name, &pkg.file_name.arch))
+                .or_default()
+                .push(pkg);
+        }
+        groups
+            .into_iter()
+            .map(|((name, arch), mut files)| {
+                files.sort_by(|a, b| b.file_name.version.cmp(&a.file_name.version));
+                CachedVersions {
+                    name,
+                    arch,
+                    local: files[0].local_pkg(local_pkgs),
+                    files,
+                }
+            })
+            .collect()
+    }
+    /// Plan a cleanup, without removing anything
+    ///
+    /// Orphaned signatures have no package file left to keep, so they are
+    /// removed whenever the filter considers the package they were made for.
+    #[must_use]
+    pub fn plan_cleanup(&self, local_pkgs: &[Pkg], opts: CleanupOptions) -> RemovalPlan<'_> {
+        let wanted = |installed: bool| match opts.filter {
+            CleanupFilter::All => true,
+            CleanupFilter::Installed => installed,
+            CleanupFilter::Uninstalled => !installed,
+        };
+        let mut plan = RemovalPlan::default();
+        for group in self.versions(local_pkgs) {
+            if !wanted(group.local.is_some()) {
+                continue;
+            }
+            for pkg in group.files.into_iter().skip(opts.keep) {
+                plan.size += pkg.total_size();
+                plan.pkgs.push(pkg);
+            }
+        }
+        for (path, size) in &self.orphan_sigs {
+            let installed = path
+                .file_name()
+                .and_then(|name| name.to_str()?.strip_suffix(".sig"))
+                .and_then(PkgFileName::parse)
+                .is_some_and(|file_name| {
+                    local_pkgs.iter().any(|pkg| pkg.desc.name == file_name.name)
+                });
+            if wanted(installed) {
+                plan.size += size;
+                plan.orphan_sigs.push(path);
+            }
+        }
+        plan
+    }
+}
+
+/// Outcome of verifying a cached package against the sync databases
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyStatus {
+    /// Size and checksum match
+    Ok,
+    /// Size matches, but the sync database has no checksum to compare against
+    NoChecksum,
+    /// The file is smaller than the sync database says, likely an interrupted download
+    Truncated {
+        expected: u64,
+        actual: u64,
+    },
+    /// The file is larger than the sync database says
+    SizeMismatch {
+        expected: u64,
+        actual: u64,
+    },
+    ChecksumMismatch {
+        expected: SmolStr,
+        actual: SmolStr,
+    },
+    /// No sync database entry refers to this file
+    NoSyncEntry,
+    /// The file couldn't be read
+    Unreadable {
+        kind: std::io::ErrorKind,
+        message: String,
+    },
+}
+
+#[derive(Debug)]
+pub struct Verification<'cache, 'sync> {
+    pub pkg: &'cache CachedPkg,
+    /// The matching sync database entry, if any
+    pub sync: Option<&'sync Pkg>,
+    pub status: VerifyStatus,
+}
+
+impl Cache {
+    /// Verify every cached package against the sync database entries in `sync_pkgs`
+    ///
+    /// Files are matched to entries by their `%FILENAME%`.
+    /// The checksum is only computed if the size matches.
+    /// A file that can't be read is reported as [`VerifyStatus::Unreadable`].
+    #[must_use]
+    pub fn verify<'cache, 'sync>(
+        &'cache self,
+        sync_pkgs: impl IntoIterator<Item = &'sync Pkg>,
+    ) -> Vec<Verification<'cache, 'sync>> {
+        let mut by_filename = BTreeMap::new();
+        for pkg in sync_pkgs {
+            if let Some(filename) = &pkg.desc.filename {
+                by_filename.entry(filename.as_str()).or_insert(pkg);
+            }
+        }
+        let mut results = Vec::new();
+        for pkg in &self.pkgs {
+            let file_name = pkg.path.file_name().and_then(|name| name.to_str());
+            let sync = file_name.and_then(|name| by_filename.get(name).copied());
+            let status = match sync {
+                Some(sync) => verify_file(pkg, sync).unwrap_or_else(|e| VerifyStatus::Unreadable {
+                    kind: e.kind(),
+                    message: e.to_string(),
+                }),
+                None => VerifyStatus::NoSyncEntry,
+            };
+            results.push(Verification { pkg, sync, status });
+        }
+        results
+    }
+}
+
+fn verify_file(pkg: &CachedPkg, sync: &Pkg) -> std::io::Result<VerifyStatus> {
+    let expected = sync.desc.c_size;
+    if pkg.size < expected {
+        return Ok(VerifyStatus::Truncated {
+            expected,
+            actual: pkg.size,
+        });
+    }
+    if pkg.size > expected {
+        return Ok(VerifyStatus::SizeMismatch {
+            expected,
+            actual: pkg.size,
+        });
+    }
+    let Some(expected) = &sync.desc.sha256sum else {
+        return Ok(VerifyStatus::NoChecksum);
+    };
+    let actual = sha256_hex(std::fs::File::open(&pkg.path)?)?;
+    if actual.eq_ignore_ascii_case(expected) {
+        Ok(VerifyStatus::Ok)
+    } else {
+        Ok(VerifyStatus::ChecksumMismatch {
+            expected: expected.clone(),
+            actual,
+        })
+    }
+}
+
+/// Hex encoded SHA-256 digest of everything read from `reader`
+pub(crate) fn sha256_hex(mut reader: impl Read) -> std::io::Result<SmolStr> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut reader, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
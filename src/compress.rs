@@ -0,0 +1,31 @@
+use {
+    flate2::read::GzDecoder,
+    std::io::{BufRead, BufReader, Read},
+};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+
+/// Wrap `reader` in a decompressor, detecting the compression from the magic bytes
+///
+/// Supports gzip, zstd and xz. Uncompressed data is passed through as is.
+pub(crate) fn decompress<'a>(reader: impl Read + 'a) -> std::io::Result<Box<dyn Read + 'a>> {
+    let mut reader = BufReader::new(reader);
+    let head = reader.fill_buf()?;
+    if head.starts_with(GZIP_MAGIC) {
+        Ok(Box::new(GzDecoder::new(reader)))
+    } else if head.starts_with(ZSTD_MAGIC) {
+        let dec = ruzstd::decoding::StreamingDecoder::new(reader)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        Ok(Box::new(dec))
+    } else if head.starts_with(XZ_MAGIC) {
+        // lzma-rs has no streaming reader, so decompress up front
+        let mut out = Vec::new();
+        lzma_rs::xz_decompress(&mut reader, &mut out)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        Ok(Box::new(std::io::Cursor::new(out)))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
@@ -0,0 +1,290 @@
+//! Parsing of the pacman configuration (`/etc/pacman.conf`)
+
+use {
+    smol_str::SmolStr,
+    std::path::{Path, PathBuf},
+};
+
+/// Default location of the pacman configuration
+pub const DEFAULT_CONFIG_PATH: &str = "/etc/pacman.conf";
+
+/// How deeply `Include` directives may nest, like in pacman
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// The settings of `pacman.conf` that are relevant for reading databases
+///
+/// Unset paths are `None`, so callers can tell them apart from explicit settings.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub root_dir: Option<PathBuf>,
+    pub db_path: Option<PathBuf>,
+    pub cache_dirs: Vec<PathBuf>,
+    pub log_file: Option<PathBuf>,
+    pub gpg_dir: Option<PathBuf>,
+    pub hook_dirs: Vec<PathBuf>,
+    pub architectures: Vec<SmolStr>,
+    pub hold_pkgs: Vec<SmolStr>,
+    pub ignore_pkgs: Vec<SmolStr>,
+    pub ignore_groups: Vec<SmolStr>,
+    pub no_upgrade: Vec<SmolStr>,
+    pub no_extract: Vec<SmolStr>,
+    pub sig_level: Vec<SmolStr>,
+    /// Repositories, in the order they are configured
+    pub repos: Vec<Repo>,
+}
+
+/// A `[repo]` section
+#[derive(Debug, Clone)]
+pub struct Repo {
+    pub name: SmolStr,
+    /// Server URLs, with `$repo` already substituted
+    pub servers: Vec<SmolStr>,
+    pub sig_level: Vec<SmolStr>,
+    pub usage: Vec<SmolStr>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Failed to read {path}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{path}:{line}: invalid line: {content}")]
+    Syntax {
+        path: PathBuf,
+        /// 1-based line number
+        line: usize,
+        content: String,
+    },
+    #[error("{path}: Include nested more than {MAX_INCLUDE_DEPTH} levels deep")]
+    IncludeDepth { path: PathBuf },
+}
+
+/// How configuration files are read
+struct Files<'a> {
+    read_file: &'a mut dyn FnMut(&Path) -> std::io::Result<String>,
+    /// File names in a directory, for expanding `Include` globs
+    list_dir: &'a mut dyn FnMut(&Path) -> std::io::Result<Vec<String>>,
+}
+
+impl Files<'_> {
+    /// The paths an `Include` value refers to
+    ///
+    /// Like glob(3), `*` and `?` are expanded in the file name, in sorted order,
+    /// and a pattern without matches is kept as it is.
+    fn include_paths(&mut self, pattern: &Path) -> Vec<PathBuf> {
+        let Some(name) = pattern
+            .file_name()
+            .and_then(|name| name.to_str())
+            .filter(|name| name.contains(['*', '?']))
+        else {
+            return vec![pattern.to_owned()];
+        };
+        let dir = pattern.parent().unwrap_or(Path::new(""));
+        let listed = if dir.as_os_str().is_empty() {
+            (self.list_dir)(Path::new("."))
+        } else {
+            (self.list_dir)(dir)
+        };
+        let mut matches: Vec<String> = listed
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|file_name| glob_match(name, file_name))
+            .collect();
+        if matches.is_empty() {
+            return vec![pattern.to_owned()];
+        }
+        matches.sort();
+        matches.into_iter().map(|name| dir.join(name)).collect()
+    }
+}
+
+/// Whether `name` matches `pattern`, where `*` matches any number of characters
+/// and `?` a single one
+///
+/// As in shells, a leading `.` must be matched explicitly.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    let (pattern, name): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*`, and where in `name` its match ends
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                star = Some((p, n));
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => {
+                // Let the last `*` match one more character
+                let Some((star_p, star_n)) = star else {
+                    return false;
+                };
+                p = star_p;
+                n = star_n + 1;
+                star = Some((star_p, n));
+            }
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn read_dir_names(dir: &Path) -> std::io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        if let Ok(name) = entry?.file_name().into_string() {
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
+
+impl Config {
+    /// Read the configuration at `path`, following `Include` directives
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        Self::read_with(path, |path| std::fs::read_to_string(path), read_dir_names)
+    }
+    /// Read the configuration at `path`, using `read_file` to read it and any included files
+    /// and `list_dir` to list the file names in a directory, for `Include` globs
+    ///
+    /// Useful for reading the configuration of another root, like a container image.
+    pub fn read_with(
+        path: impl AsRef<Path>,
+        mut read_file: impl FnMut(&Path) -> std::io::Result<String>,
+        mut list_dir: impl FnMut(&Path) -> std::io::Result<Vec<String>>,
+    ) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+        let mut section = None;
+        let mut files = Files {
+            read_file: &mut read_file,
+            list_dir: &mut list_dir,
+        };
+        config.parse_file(path.as_ref(), &mut section, &mut files, 0)?;
+        Ok(config)
+    }
+    /// Parse the contents of a configuration file, with `Include` directives
+    /// read from the filesystem
+    pub fn parse(src: &str) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+        let mut section = None;
+        let mut files = Files {
+            read_file: &mut |path| std::fs::read_to_string(path),
+            list_dir: &mut read_dir_names,
+        };
+        config.parse_src(src, Path::new("<input>"), &mut section, &mut files, 0)?;
+        Ok(config)
+    }
+    /// The names of the configured repositories
+    pub fn repo_names(&self) -> impl Iterator<Item = &str> {
+        self.repos.iter().map(|repo| repo.name.as_str())
+    }
+    /// Parse the file at `path`, which is included `depth` levels deep
+    fn parse_file(
+        &mut self,
+        path: &Path,
+        section: &mut Option<SmolStr>,
+        files: &mut Files,
+        depth: usize,
+    ) -> Result<(), ConfigError> {
+        let src = (files.read_file)(path).map_err(|source| ConfigError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        self.parse_src(&src, path, section, files, depth)
+    }
+    fn parse_src(
+        &mut self,
+        src: &str,
+        path: &Path,
+        section: &mut Option<SmolStr>,
+        files: &mut Files,
+        depth: usize,
+    ) -> Result<(), ConfigError> {
+        for (i, line) in src.lines().enumerate() {
+            let syntax_err = || ConfigError::Syntax {
+                path: path.to_owned(),
+                line: i + 1,
+                content: line.to_owned(),
+            };
+            let line = line.split_once('#').map_or(line, |(line, _)| line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[') {
+                let name = name.strip_suffix(']').ok_or_else(syntax_err)?;
+                if name.is_empty() {
+                    return Err(syntax_err());
+                }
+                if name != "options" {
+                    self.repos.push(Repo {
+                        name: name.into(),
+                        servers: Vec::new(),
+                        sig_level: Vec::new(),
+                        usage: Vec::new(),
+                    });
+                }
+                *section = Some(name.into());
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (line, ""),
+            };
+            let Some(section_name) = section.as_deref() else {
+                return Err(syntax_err());
+            };
+            if key == "Include" {
+                // Also stops files from including themselves
+                if depth >= MAX_INCLUDE_DEPTH {
+                    return Err(ConfigError::IncludeDepth {
+                        path: path.to_owned(),
+                    });
+                }
+                for include in files.include_paths(Path::new(value)) {
+                    self.parse_file(&include, section, files, depth + 1)?;
+                }
+                continue;
+            }
+            let list = || value.split_whitespace().map(SmolStr::from);
+            if section_name == "options" {
+                match key {
+                    "RootDir" => self.root_dir = Some(value.into()),
+                    "DBPath" => self.db_path = Some(value.into()),
+                    "CacheDir" => self
+                        .cache_dirs
+                        .extend(value.split_whitespace().map(Into::into)),
+                    "LogFile" => self.log_file = Some(value.into()),
+                    "GPGDir" => self.gpg_dir = Some(value.into()),
+                    "HookDir" => self
+                        .hook_dirs
+                        .extend(value.split_whitespace().map(Into::into)),
+                    "Architecture" => self.architectures.extend(list()),
+                    "HoldPkg" => self.hold_pkgs.extend(list()),
+                    "IgnorePkg" => self.ignore_pkgs.extend(list()),
+                    "IgnoreGroup" => self.ignore_groups.extend(list()),
+                    "NoUpgrade" => self.no_upgrade.extend(list()),
+                    "NoExtract" => self.no_extract.extend(list()),
+                    "SigLevel" => self.sig_level.extend(list()),
+                    _ => {}
+                }
+            } else if let Some(repo) = self.repos.last_mut() {
+                match key {
+                    "Server" => repo.servers.push(value.replace("$repo", &repo.name).into()),
+                    "SigLevel" => repo.sig_level.extend(list()),
+                    "Usage" => repo.usage.extend(list()),
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,14 @@
-use crate::{Pkg, PkgDesc};
+use {
+    crate::{
+        DepVer, Depend, InstallReason, Pkg, PkgDesc, ReqCmp, SyncDb,
+        version::{Version, rpmvercmp, split_evr},
+    },
+    std::{
+        cmp::Ordering,
+        collections::BTreeMap,
+        fmt::{self, Write as _},
+    },
+};
 
 #[must_use]
 pub fn pkg_matches_dep(pkg: &PkgDesc, dependent_pkg: &PkgDesc) -> bool {
@@ -74,3 +84,570 @@ impl PkgDepsExt for PkgDesc {
         pkg_deps::<true>(self, pkgs)
     }
 }
+
+/// Where a bound sits relative to the releases of its pkgver
+///
+/// A bound without a pkgrel covers every release of the pkgver, so `>=1.5`
+/// starts before `1.5-1` and `>1.5` starts after all `1.5` releases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BoundPos {
+    BeforeReleases,
+    Release,
+    AfterReleases,
+}
+
+#[derive(Debug, Clone)]
+struct Bound {
+    ver: Version,
+    pos: BoundPos,
+    inclusive: bool,
+}
+
+impl Bound {
+    fn lower(ver: &Version, inclusive: bool) -> Self {
+        let pos = match (ver.pkgrel(), inclusive) {
+            (Some(_), _) => BoundPos::Release,
+            (None, true) => BoundPos::BeforeReleases,
+            (None, false) => BoundPos::AfterReleases,
+        };
+        Self {
+            ver: ver.clone(),
+            pos,
+            inclusive,
+        }
+    }
+    fn upper(ver: &Version, inclusive: bool) -> Self {
+        let pos = match (ver.pkgrel(), inclusive) {
+            (Some(_), _) => BoundPos::Release,
+            (None, true) => BoundPos::AfterReleases,
+            (None, false) => BoundPos::BeforeReleases,
+        };
+        Self {
+            ver: ver.clone(),
+            pos,
+            inclusive,
+        }
+    }
+    /// Compare only epoch and pkgver
+    fn cmp_pkgver(&self, ver: &Version) -> Ordering {
+        let (epoch_a, ver_a, _) = split_evr(self.ver.as_str());
+        let (epoch_b, ver_b, _) = split_evr(ver.as_str());
+        rpmvercmp(epoch_a.unwrap_or("0"), epoch_b.unwrap_or("0"))
+            .then_with(|| rpmvercmp(ver_a, ver_b))
+    }
+    fn cmp(&self, other: &Self) -> Ordering {
+        let rank = |pos| match pos {
+            BoundPos::BeforeReleases => 0,
+            BoundPos::Release => 1,
+            BoundPos::AfterReleases => 2,
+        };
+        self.cmp_pkgver(&other.ver)
+            .then_with(|| rank(self.pos).cmp(&rank(other.pos)))
+            .then_with(|| match (self.ver.pkgrel(), other.ver.pkgrel()) {
+                (Some(rel_a), Some(rel_b)) => rpmvercmp(rel_a, rel_b),
+                _ => Ordering::Equal,
+            })
+    }
+    /// How `ver` compares to this bound
+    ///
+    /// Like for dependencies, a version without a pkgrel only compares its pkgver.
+    fn cmp_version(&self, ver: &Version) -> Ordering {
+        if ver.pkgrel().is_none() {
+            return self.cmp_pkgver(ver).reverse();
+        }
+        Self::lower(ver, true).cmp(self)
+    }
+}
+
+/// The versions allowed by a number of version requirements on the same name,
+/// like `foo>=1.2` and `foo<2` combined
+///
+/// Versions compare like they do for dependencies, so a requirement without a
+/// pkgrel is met by every release of that pkgver.
+#[derive(Debug, Clone, Default)]
+pub struct VersionSet {
+    lower: Option<Bound>,
+    upper: Option<Bound>,
+}
+
+impl VersionSet {
+    /// The set of all versions
+    #[must_use]
+    pub fn any() -> Self {
+        Self::default()
+    }
+    /// Narrow the set down to the versions that also meet `req`
+    pub fn insert(&mut self, req: &DepVer) {
+        let (lower, upper) = match req.req_cmp {
+            ReqCmp::Lt => (None, Some(Bound::upper(&req.ver, false))),
+            ReqCmp::LtEq => (None, Some(Bound::upper(&req.ver, true))),
+            ReqCmp::Gt => (Some(Bound::lower(&req.ver, false)), None),
+            ReqCmp::GtEq => (Some(Bound::lower(&req.ver, true)), None),
+            ReqCmp::Eq => (
+                Some(Bound::lower(&req.ver, true)),
+                Some(Bound::upper(&req.ver, true)),
+            ),
+        };
+        self.narrow(lower, upper);
+    }
+    /// The versions in both sets
+    #[must_use]
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut set = self.clone();
+        set.narrow(other.lower.clone(), other.upper.clone());
+        set
+    }
+    fn narrow(&mut self, lower: Option<Bound>, upper: Option<Bound>) {
+        // On equal bounds, the exclusive one is the tighter one
+        if let Some(new) = lower
+            && self.lower.as_ref().is_none_or(|old| {
+                new.cmp(old)
+                    .then_with(|| old.inclusive.cmp(&new.inclusive))
+                    .is_gt()
+            })
+        {
+            self.lower = Some(new);
+        }
+        if let Some(new) = upper
+            && self.upper.as_ref().is_none_or(|old| {
+                new.cmp(old)
+                    .then_with(|| new.inclusive.cmp(&old.inclusive))
+                    .is_lt()
+            })
+        {
+            self.upper = Some(new);
+        }
+    }
+    /// Whether every version is allowed
+    #[must_use]
+    pub fn is_any(&self) -> bool {
+        self.lower.is_none() && self.upper.is_none()
+    }
+    /// Whether any version can meet all the requirements
+    #[must_use]
+    pub fn is_satisfiable(&self) -> bool {
+        match (&self.lower, &self.upper) {
+            (Some(lower), Some(upper)) => match lower.cmp(upper) {
+                Ordering::Less => true,
+                Ordering::Equal => lower.inclusive && upper.inclusive,
+                Ordering::Greater => false,
+            },
+            _ => true,
+        }
+    }
+    /// Whether `ver` meets all the requirements
+    #[must_use]
+    pub fn contains(&self, ver: &Version) -> bool {
+        let above_lower = self.lower.as_ref().is_none_or(|lower| {
+            let ord = lower.cmp_version(ver);
+            ord.is_gt() || (ord.is_eq() && lower.inclusive)
+        });
+        let below_upper = self.upper.as_ref().is_none_or(|upper| {
+            let ord = upper.cmp_version(ver);
+            ord.is_lt() || (ord.is_eq() && upper.inclusive)
+        });
+        // A version without a pkgrel can meet conflicting requirements on pkgrels
+        self.is_satisfiable() && above_lower && below_upper
+    }
+    /// Whether `pkg` meets the requirements on `name`, either directly or through a provision
+    ///
+    /// Like in alpm, a provision without a version only meets unversioned requirements.
+    #[must_use]
+    pub fn matches(&self, name: &str, pkg: &PkgDesc) -> bool {
+        (pkg.name == name && self.contains(&pkg.version))
+            || pkg.provides.iter().any(|prov| {
+                prov.name == name
+                    && match &prov.ver {
+                        Some(ver) => self.contains(&ver.ver),
+                        None => self.is_any(),
+                    }
+            })
+    }
+    /// The requirements describing this set, at most one per bound
+    #[must_use]
+    pub fn to_dep_vers(&self) -> Vec<DepVer> {
+        if let (Some(lower), Some(upper)) = (&self.lower, &self.upper)
+            && lower.inclusive
+            && upper.inclusive
+            && lower.ver.as_str() == upper.ver.as_str()
+        {
+            return vec![DepVer {
+                req_cmp: ReqCmp::Eq,
+                ver: lower.ver.clone(),
+            }];
+        }
+        let lower = self.lower.as_ref().map(|lower| DepVer {
+            req_cmp: if lower.inclusive {
+                ReqCmp::GtEq
+            } else {
+                ReqCmp::Gt
+            },
+            ver: lower.ver.clone(),
+        });
+        let upper = self.upper.as_ref().map(|upper| DepVer {
+            req_cmp: if upper.inclusive {
+                ReqCmp::LtEq
+            } else {
+                ReqCmp::Lt
+            },
+            ver: upper.ver.clone(),
+        });
+        lower.into_iter().chain(upper).collect()
+    }
+}
+
+impl From<&DepVer> for VersionSet {
+    fn from(req: &DepVer) -> Self {
+        let mut set = Self::any();
+        set.insert(req);
+        set
+    }
+}
+
+impl fmt::Display for VersionSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, req) in self.to_dep_vers().iter().enumerate() {
+            if i != 0 {
+                f.write_char(',')?;
+            }
+            write!(f, "{}{}", req.req_cmp, req.ver)?;
+        }
+        Ok(())
+    }
+}
+
+/// The combined version requirements on each dependency name of a package set
+///
+/// Names that are depended on without a version map to [`VersionSet::any`].
+#[must_use]
+pub fn requirements<'pkgs>(
+    pkgs: impl IntoIterator<Item = &'pkgs Pkg>,
+) -> BTreeMap<&'pkgs str, VersionSet> {
+    let mut reqs: BTreeMap<&str, VersionSet> = BTreeMap::new();
+    for pkg in pkgs {
+        for dep in &pkg.desc.depends {
+            let set = reqs.entry(&dep.name).or_default();
+            if let Some(ver) = &dep.ver {
+                set.insert(ver);
+            }
+        }
+    }
+    reqs
+}
+
+/// A problem found by [`check`] or [`check_sync`]
+#[derive(Debug)]
+pub enum Problem<'pkgs> {
+    /// No package of the checked set meets a dependency
+    Unsatisfied {
+        pkg: &'pkgs Pkg,
+        dep: &'pkgs Depend,
+        /// Installed packages with the dependency's name or providing it,
+        /// but in a version that doesn't meet the requirement
+        candidates: Vec<&'pkgs Pkg>,
+    },
+    /// An installed package conflicts with another installed package
+    Conflict {
+        pkg: &'pkgs Pkg,
+        /// The conflict as declared by `pkg`, like `foo<2`
+        conflict: Depend,
+        other: &'pkgs Pkg,
+    },
+    /// A file is owned by more than one package
+    FileConflict {
+        path: &'pkgs str,
+        owners: Vec<&'pkgs Pkg>,
+    },
+}
+
+impl fmt::Display for Problem<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsatisfied { pkg, dep, .. } => {
+                write!(f, "{} requires {dep}", pkg.desc.name)
+            }
+            Self::Conflict {
+                pkg,
+                conflict,
+                other,
+            } => write!(
+                f,
+                "{} and {} are in conflict ({conflict})",
+                pkg.desc.name, other.desc.name
+            ),
+            Self::FileConflict { path, owners } => {
+                write!(f, "/{path} is owned by")?;
+                for (i, owner) in owners.iter().enumerate() {
+                    f.write_str(if i == 0 { " " } else { ", " })?;
+                    f.write_str(&owner.desc.name)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Check an installed package set for unmet dependencies, conflicts and files
+/// owned by more than one package, like `pacman -Dk`
+///
+/// A conflict declared by both packages is only reported once.
+/// Directories are commonly shared, so they are not reported as file conflicts.
+#[must_use]
+pub fn check(pkgs: &[Pkg]) -> Vec<Problem<'_>> {
+    let mut problems = unsatisfied(&pkgs.iter().collect::<Vec<_>>());
+    for pkg in pkgs {
+        for conflict in &pkg.desc.conflicts {
+            let conflict = Depend::parse(conflict);
+            let set = conflict
+                .ver
+                .as_ref()
+                .map_or_else(VersionSet::any, VersionSet::from);
+            // Packages commonly conflict with what they provide themselves
+            for other in pkgs.iter().filter(|other| !std::ptr::eq(*other, pkg)) {
+                let reported = problems.iter().any(|problem| {
+                    matches!(problem, Problem::Conflict { pkg: first, other: second, .. }
+                        if std::ptr::eq(*first, other) && std::ptr::eq(*second, pkg))
+                });
+                if !reported && set.matches(&conflict.name, &other.desc) {
+                    problems.push(Problem::Conflict {
+                        pkg,
+                        conflict: conflict.clone(),
+                        other,
+                    });
+                }
+            }
+        }
+    }
+    let mut owners: BTreeMap<&str, Vec<&Pkg>> = BTreeMap::new();
+    for pkg in pkgs {
+        for file in &pkg.files {
+            if !file.ends_with('/') {
+                owners.entry(file).or_default().push(pkg);
+            }
+        }
+    }
+    problems.extend(
+        owners
+            .into_iter()
+            .filter(|(_, owners)| owners.len() > 1)
+            .map(|(path, owners)| Problem::FileConflict { path, owners }),
+    );
+    problems
+}
+
+/// Check that the dependencies of all sync database packages are available in
+/// the sync databases, which `pacman -Dkk` does in addition to [`check`]
+#[must_use]
+pub fn check_sync(dbs: &[SyncDb]) -> Vec<Problem<'_>> {
+    let pkgs: Vec<&Pkg> = dbs.iter().flat_map(|db| &db.pkgs).collect();
+    unsatisfied(&pkgs)
+}
+
+/// Positions of the packages in `pkgs` with each name, either their own or a provided one
+pub(crate) fn name_index<'pkgs>(
+    pkgs: impl IntoIterator<Item = &'pkgs PkgDesc>,
+) -> BTreeMap<&'pkgs str, Vec<usize>> {
+    let mut index: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (i, pkg) in pkgs.into_iter().enumerate() {
+        let names = std::iter::once(&pkg.name).chain(pkg.provides.iter().map(|prov| &prov.name));
+        for name in names {
+            let positions = index.entry(name.as_str()).or_default();
+            // Packages commonly provide their own name
+            if positions.last() != Some(&i) {
+                positions.push(i);
+            }
+        }
+    }
+    index
+}
+
+/// Dependencies of `pkgs` that no package of `pkgs` meets
+fn unsatisfied<'pkgs>(pkgs: &[&'pkgs Pkg]) -> Vec<Problem<'pkgs>> {
+    let index = name_index(pkgs.iter().map(|pkg| &pkg.desc));
+    let mut problems = Vec::new();
+    for pkg in pkgs {
+        for dep in &pkg.desc.depends {
+            let set = dep
+                .ver
+                .as_ref()
+                .map_or_else(VersionSet::any, VersionSet::from);
+            let candidates: Vec<&Pkg> = index
+                .get(dep.name.as_str())
+                .into_iter()
+                .flatten()
+                .map(|&i| pkgs[i])
+                .collect();
+            if candidates
+                .iter()
+                .any(|other| set.matches(&dep.name, &other.desc))
+            {
+                continue;
+            }
+            problems.push(Problem::Unsatisfied {
+                pkg,
+                dep,
+                candidates,
+            });
+        }
+    }
+    problems
+}
+
+/// A chain of dependents, from a package to an explicitly installed package needing it
+#[derive(Debug, Clone)]
+pub struct Chain<'pkgs> {
+    /// The package asked about first, the explicitly installed package last
+    pub pkgs: Vec<&'pkgs Pkg>,
+    /// For each step, whether it goes through an optional dependency
+    pub optional: Vec<bool>,
+}
+
+impl Chain<'_> {
+    /// The explicitly installed package at the end of the chain
+    #[must_use]
+    pub fn root(&self) -> &Pkg {
+        self.pkgs[self.pkgs.len() - 1]
+    }
+}
+
+/// Formats like `libfoo <- bar <~ baz (explicit)`, with `<~` for optional dependencies
+impl fmt::Display for Chain<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.pkgs[0].desc.name)?;
+        for (pkg, optional) in self.pkgs[1..].iter().zip(&self.optional) {
+            let arrow = if *optional { " <~ " } else { " <- " };
+            write!(f, "{arrow}{}", pkg.desc.name)?;
+        }
+        f.write_str(" (explicit)")
+    }
+}
+
+/// Why a package is installed, as returned by [`why`]
+#[derive(Debug, Clone, Default)]
+pub struct Why<'pkgs> {
+    /// The package is explicitly installed itself, so it has no chains
+    pub explicit: bool,
+    /// Shortest chains of dependencies to each explicitly installed package needing it
+    pub required: Vec<Chain<'pkgs>>,
+    /// Shortest chains to explicitly installed packages that only need it
+    /// through an optional dependency
+    pub optional: Vec<Chain<'pkgs>>,
+}
+
+impl Why<'_> {
+    /// Whether the package is a dependency that no explicitly installed package
+    /// needs, even optionally
+    #[must_use]
+    pub fn is_orphan(&self) -> bool {
+        !self.explicit && self.required.is_empty() && self.optional.is_empty()
+    }
+}
+
+/// Explain why `pkg` is installed, with all the shortest chains of dependents back to
+/// explicitly installed packages
+///
+/// Chains stop at the first explicitly installed package.
+#[must_use]
+pub fn why<'pkgs>(pkg: &PkgDesc, pkgs: &'pkgs [Pkg]) -> Why<'pkgs> {
+    let Some(start) = pkgs.iter().find(|other| other.desc.name == pkg.name) else {
+        return Why::default();
+    };
+    if start.desc.install_reason == InstallReason::Explicit {
+        return Why {
+            explicit: true,
+            ..Why::default()
+        };
+    }
+    let required = shortest_chains(start, pkgs, false);
+    let mut optional = shortest_chains(start, pkgs, true);
+    optional.retain(|chain| {
+        !required
+            .iter()
+            .any(|req| std::ptr::eq(req.root(), chain.root()))
+    });
+    Why {
+        explicit: false,
+        required,
+        optional,
+    }
+}
+
+/// All shortest chains from `start` to each reachable explicitly installed package
+fn shortest_chains<'pkgs>(
+    start: &'pkgs Pkg,
+    pkgs: &'pkgs [Pkg],
+    optional: bool,
+) -> Vec<Chain<'pkgs>> {
+    // The packages one step closer to `start`, by name, with whether the step is optional
+    let mut parents: BTreeMap<&str, Vec<(&Pkg, bool)>> = BTreeMap::new();
+    let mut depth: BTreeMap<&str, usize> = BTreeMap::from([(start.desc.name.as_str(), 0)]);
+    let mut layer = vec![start];
+    let mut roots = Vec::new();
+    while !layer.is_empty() {
+        let mut next = Vec::new();
+        for pkg in layer {
+            let next_depth = depth[pkg.desc.name.as_str()] + 1;
+            let dependents = pkg.required_by(pkgs.iter()).map(|other| (other, false));
+            let optional_dependents = optional
+                .then(|| pkg.optional_for(pkgs.iter()).map(|other| (other, true)))
+                .into_iter()
+                .flatten();
+            for (dependent, is_optional) in dependents.chain(optional_dependents) {
+                let name = dependent.desc.name.as_str();
+                match depth.get(name) {
+                    None => {
+                        depth.insert(name, next_depth);
+                        if dependent.desc.install_reason == InstallReason::Explicit {
+                            roots.push(dependent);
+                        } else {
+                            next.push(dependent);
+                        }
+                    }
+                    Some(&found) if found == next_depth => {}
+                    Some(_) => continue,
+                }
+                let parents = parents.entry(name).or_default();
+                // Prefer the required step when a package is both required and optional
+                if !parents.iter().any(|(parent, _)| std::ptr::eq(*parent, pkg)) {
+                    parents.push((pkg, is_optional));
+                }
+            }
+        }
+        layer = next;
+    }
+    let mut chains: Vec<Chain> = roots
+        .into_iter()
+        .flat_map(|root| chains_to(root, &parents))
+        .collect();
+    chains.sort_by_cached_key(|chain| {
+        chain
+            .pkgs
+            .iter()
+            .rev()
+            .map(|pkg| pkg.desc.name.clone())
+            .collect::<Vec<_>>()
+    });
+    chains
+}
+
+/// The chains from the start of a search to `pkg`, following `parents`
+fn chains_to<'pkgs>(
+    pkg: &'pkgs Pkg,
+    parents: &BTreeMap<&str, Vec<(&'pkgs Pkg, bool)>>,
+) -> Vec<Chain<'pkgs>> {
+    let Some(pkg_parents) = parents.get(pkg.desc.name.as_str()) else {
+        return vec![Chain {
+            pkgs: vec![pkg],
+            optional: Vec::new(),
+        }];
+    };
+    let mut chains = Vec::new();
+    for &(parent, is_optional) in pkg_parents {
+        for mut chain in chains_to(parent, parents) {
+            chain.pkgs.push(pkg);
+            chain.optional.push(is_optional);
+            chains.push(chain);
+        }
+    }
+    chains
+}
@@ -0,0 +1,132 @@
+//! Differences between two package sets, like two snapshots of a database
+
+use {
+    crate::Pkg,
+    smol_str::SmolStr,
+    std::{
+        cmp::Ordering,
+        collections::{BTreeMap, BTreeSet},
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Upgraded,
+    Downgraded,
+    /// Same version, but some tracked fields changed
+    Modified,
+}
+
+/// Entries added to and removed from a list field
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListChange {
+    pub added: Vec<SmolStr>,
+    pub removed: Vec<SmolStr>,
+}
+
+impl ListChange {
+    fn new<T: ToString>(old: &[T], new: &[T]) -> Option<Self> {
+        let old: BTreeSet<String> = old.iter().map(ToString::to_string).collect();
+        let new: BTreeSet<String> = new.iter().map(ToString::to_string).collect();
+        let change = Self {
+            added: new.difference(&old).map(Into::into).collect(),
+            removed: old.difference(&new).map(Into::into).collect(),
+        };
+        (!change.added.is_empty() || !change.removed.is_empty()).then_some(change)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldChange {
+    Depends(ListChange),
+    Provides(ListChange),
+    Licenses(ListChange),
+    Packager {
+        old: Option<SmolStr>,
+        new: Option<SmolStr>,
+    },
+}
+
+#[derive(Debug)]
+pub struct PkgDiff<'old, 'new> {
+    pub name: SmolStr,
+    pub kind: ChangeKind,
+    pub old: Option<&'old Pkg>,
+    pub new: Option<&'new Pkg>,
+    /// Field level changes. Empty for added and removed packages.
+    pub fields: Vec<FieldChange>,
+}
+
+fn field_changes(old: &Pkg, new: &Pkg) -> Vec<FieldChange> {
+    let (old, new) = (&old.desc, &new.desc);
+    let mut changes = Vec::new();
+    if let Some(change) = ListChange::new(&old.depends, &new.depends) {
+        changes.push(FieldChange::Depends(change));
+    }
+    if let Some(change) = ListChange::new(&old.provides, &new.provides) {
+        changes.push(FieldChange::Provides(change));
+    }
+    if let Some(change) = ListChange::new(&old.licenses, &new.licenses) {
+        changes.push(FieldChange::Licenses(change));
+    }
+    if old.packager != new.packager {
+        changes.push(FieldChange::Packager {
+            old: old.packager.clone(),
+            new: new.packager.clone(),
+        });
+    }
+    changes
+}
+
+/// Compute the differences between two package sets
+///
+/// Packages are matched by name. If a set contains a name more than once
+/// (e.g. several repositories), the first occurrence is used.
+/// Unchanged packages are omitted, and the result is sorted by name.
+#[must_use]
+pub fn diff<'old, 'new>(old: &'old [Pkg], new: &'new [Pkg]) -> Vec<PkgDiff<'old, 'new>> {
+    let mut pairs: BTreeMap<&str, (Option<&Pkg>, Option<&Pkg>)> = BTreeMap::new();
+    for pkg in old {
+        pairs
+            .entry(&pkg.desc.name)
+            .or_default()
+            .0
+            .get_or_insert(pkg);
+    }
+    for pkg in new {
+        pairs
+            .entry(&pkg.desc.name)
+            .or_default()
+            .1
+            .get_or_insert(pkg);
+    }
+    pairs
+        .into_iter()
+        .filter_map(|(name, (old, new))| {
+            let (kind, fields) = match (old, new) {
+                (Some(_), None) => (ChangeKind::Removed, Vec::new()),
+                (None, Some(_)) => (ChangeKind::Added, Vec::new()),
+                (Some(old_pkg), Some(new_pkg)) => {
+                    let fields = field_changes(old_pkg, new_pkg);
+                    let kind = match old_pkg.desc.version.cmp(&new_pkg.desc.version) {
+                        Ordering::Less => ChangeKind::Upgraded,
+                        Ordering::Greater => ChangeKind::Downgraded,
+                        Ordering::Equal if fields.is_empty() => return None,
+                        Ordering::Equal => ChangeKind::Modified,
+                    };
+                    (kind, fields)
+                }
+                (None, None) => return None,
+            };
+            Some(PkgDiff {
+                name: name.into(),
+                kind,
+                old,
+                new,
+                fields,
+            })
+        })
+        .collect()
+}
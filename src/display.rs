@@ -0,0 +1,375 @@
+//! Formatting of package information, matching pacman's `-Qi` and `-Si` output
+
+use {
+    crate::{InstallReason, Pkg, Validation, dep::PkgDepsExt},
+    jiff::tz::TimeZone,
+    std::{
+        fmt::{self, Write as _},
+        path::Path,
+    },
+};
+
+/// Convert a size in bytes to a value and unit label, like pacman's `humanize_size`
+///
+/// With `target_unit` set (e.g. `'M'`), that unit is used, otherwise the largest unit
+/// that keeps the value at most 2048.
+#[must_use]
+#[expect(clippy::cast_precision_loss)]
+pub fn humanize_size(bytes: i64, target_unit: Option<char>) -> (f64, &'static str) {
+    const LABELS: [&str; 9] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB", "ZiB", "YiB"];
+    let mut val = bytes as f64;
+    let mut index = 0;
+    while index < LABELS.len() - 1 {
+        let done = match target_unit {
+            Some(unit) => LABELS[index].starts_with(unit),
+            None => (-2048.0..=2048.0).contains(&val),
+        };
+        if done {
+            break;
+        }
+        val /= 1024.0;
+        index += 1;
+    }
+    // Don't display negative zeroes
+    if val < 0.0 && val > -0.005 {
+        val = 0.0;
+    }
+    (val, LABELS[index])
+}
+
+/// How dates are formatted
+///
+/// pacman uses the `%c` format of the current locale.
+/// The C/POSIX locale and the common `en_US` style are supported,
+/// other locales fall back to the `en_US` style.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DateFormat {
+    /// `Sat 09 Dec 2023 05:37:35 PM CET`
+    #[default]
+    EnUs,
+    /// `Sat Dec  9 17:37:35 2023`
+    Posix,
+    /// A custom `strftime` format
+    Strftime(String),
+}
+
+impl DateFormat {
+    /// Pick the format for the locale in `LC_ALL`, `LC_TIME` or `LANG`
+    #[must_use]
+    pub fn from_env() -> Self {
+        let locale = ["LC_ALL", "LC_TIME", "LANG"]
+            .into_iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty());
+        match locale.as_deref() {
+            None | Some("C" | "POSIX") => Self::Posix,
+            Some(locale) if locale.starts_with("C.") => Self::Posix,
+            Some(_) => Self::EnUs,
+        }
+    }
+    fn strftime(&self) -> &str {
+        match self {
+            Self::EnUs => "%a %d %b %Y %I:%M:%S %p %Z",
+            Self::Posix => "%a %b %e %H:%M:%S %Y",
+            Self::Strftime(format) => format,
+        }
+    }
+}
+
+/// Format a unix timestamp, or `None` if it is unset or out of range
+#[must_use]
+pub fn format_date(secs: u64, tz: &TimeZone, format: &DateFormat) -> Option<String> {
+    if secs == 0 {
+        return None;
+    }
+    let ts = jiff::Timestamp::from_second(i64::try_from(secs).ok()?).ok()?;
+    Some(
+        ts.to_zoned(tz.clone())
+            .strftime(format.strftime())
+            .to_string(),
+    )
+}
+
+/// Package information, formatted like `pacman -Qi` or `pacman -Si`
+///
+/// The output ends with an empty line, like pacman's.
+pub struct PkgInfo<'a> {
+    pkg: &'a Pkg,
+    /// Repository of a sync package
+    repo: Option<&'a str>,
+    /// Installed packages, for required by, optional for and `[installed]` markers
+    local_pkgs: &'a [Pkg],
+    backup_root: Option<&'a Path>,
+    cols: usize,
+    tz: TimeZone,
+    date_format: DateFormat,
+}
+
+impl<'a> PkgInfo<'a> {
+    /// Information about an installed package (`-Qi`)
+    #[must_use]
+    pub fn local(pkg: &'a Pkg, local_pkgs: &'a [Pkg]) -> Self {
+        Self::new(pkg, None, local_pkgs)
+    }
+    /// Information about a package in the sync database `repo` (`-Si`)
+    #[must_use]
+    pub fn sync(pkg: &'a Pkg, repo: &'a str) -> Self {
+        Self::new(pkg, Some(repo), &[])
+    }
+    fn new(pkg: &'a Pkg, repo: Option<&'a str>, local_pkgs: &'a [Pkg]) -> Self {
+        Self {
+            pkg,
+            repo,
+            local_pkgs,
+            backup_root: None,
+            cols: 0,
+            tz: TimeZone::system(),
+            date_format: DateFormat::default(),
+        }
+    }
+    /// Wrap lines at `cols` columns, like pacman on a terminal. 0 disables wrapping.
+    #[must_use]
+    pub fn cols(mut self, cols: usize) -> Self {
+        self.cols = cols;
+        self
+    }
+    #[must_use]
+    pub fn time_zone(mut self, tz: TimeZone) -> Self {
+        self.tz = tz;
+        self
+    }
+    #[must_use]
+    pub fn date_format(mut self, format: DateFormat) -> Self {
+        self.date_format = format;
+        self
+    }
+    /// Installed packages, to mark installed optional dependencies of a sync package (`-Si`)
+    #[must_use]
+    pub fn local_pkgs(mut self, local_pkgs: &'a [Pkg]) -> Self {
+        self.local_pkgs = local_pkgs;
+        self
+    }
+    /// Also list backup files with their state under `root` (`-Qii`)
+    #[must_use]
+    pub fn backup_files(mut self, root: &'a Path) -> Self {
+        self.backup_root = Some(root);
+        self
+    }
+    fn date(&self, secs: u64) -> Option<String> {
+        format_date(secs, &self.tz, &self.date_format)
+    }
+    fn optdeps(&self) -> Vec<String> {
+        self.pkg
+            .desc
+            .opt_depends
+            .iter()
+            .map(|opt| {
+                let installed = self.local_pkgs.iter().any(|pkg| {
+                    pkg.desc.name == opt.dep.name
+                        || pkg
+                            .desc
+                            .provides
+                            .iter()
+                            .any(|prov| prov.name == opt.dep.name)
+                });
+                if installed {
+                    format!("{opt} [installed]")
+                } else {
+                    opt.to_string()
+                }
+            })
+            .collect()
+    }
+    fn validations(&self) -> Vec<&'static str> {
+        // pacman lists them in a fixed order
+        [
+            (Validation::Md5, "MD5 Sum"),
+            (Validation::Sha256, "SHA-256 Sum"),
+            (Validation::Pgp, "Signature"),
+        ]
+        .into_iter()
+        .filter(|(v, _)| self.pkg.desc.validations.contains(v))
+        .map(|(_, label)| label)
+        .collect()
+    }
+    fn backups(&self, root: &Path) -> Vec<String> {
+        self.pkg
+            .backup
+            .iter()
+            .filter(|backup| !backup.md5.is_empty())
+            .map(|backup| {
+                let status = match backup.status(root) {
+                    crate::BackupStatus::Unmodified => "unmodified",
+                    crate::BackupStatus::Modified => "modified",
+                    crate::BackupStatus::Missing => "missing",
+                    crate::BackupStatus::Unreadable => "unreadable",
+                };
+                format!("{} [{status}]", root.join(&*backup.path).display())
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for PkgInfo<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let desc = &self.pkg.desc;
+        let cols = self.cols;
+        let local = self.repo.is_none();
+        if let Some(repo) = self.repo {
+            string_display(f, "Repository      :", Some(repo), cols)?;
+        }
+        string_display(f, "Name            :", Some(&desc.name), cols)?;
+        string_display(f, "Version         :", Some(desc.version.as_str()), cols)?;
+        string_display(f, "Description     :", desc.desc.as_deref(), cols)?;
+        string_display(f, "Architecture    :", Some(&desc.arch), cols)?;
+        string_display(f, "URL             :", desc.url.as_deref(), cols)?;
+        list_display(f, "Licenses        :", &desc.licenses, cols)?;
+        list_display(f, "Groups          :", &desc.groups, cols)?;
+        list_display(f, "Provides        :", &desc.provides, cols)?;
+        list_display(f, "Depends On      :", &desc.depends, cols)?;
+        list_display_linebreak(f, "Optional Deps   :", &self.optdeps(), cols)?;
+        if local {
+            let required_by = sorted_names(desc.required_by(self.local_pkgs.iter()));
+            list_display(f, "Required By     :", &required_by, cols)?;
+            let optional_for = sorted_names(desc.optional_for(self.local_pkgs.iter()));
+            list_display(f, "Optional For    :", &optional_for, cols)?;
+        }
+        list_display(f, "Conflicts With  :", &desc.conflicts, cols)?;
+        list_display(f, "Replaces        :", &desc.replaces, cols)?;
+        let mut size_display = |title: &str, bytes: u64| {
+            let (size, label) = humanize_size(i64::try_from(bytes).unwrap_or(i64::MAX), None);
+            writeln!(f, "{title} {size:.2} {label}")
+        };
+        if !local {
+            size_display("Download Size   :", desc.c_size)?;
+        }
+        size_display("Installed Size  :", desc.size)?;
+        string_display(f, "Packager        :", desc.packager.as_deref(), cols)?;
+        let build_date = self.date(desc.build_date);
+        string_display(f, "Build Date      :", build_date.as_deref(), cols)?;
+        if local {
+            let install_date = self.date(desc.install_date);
+            string_display(f, "Install Date    :", install_date.as_deref(), cols)?;
+            let reason = match desc.install_reason {
+                InstallReason::Explicit => "Explicitly installed",
+                InstallReason::Dep => "Installed as a dependency for another package",
+            };
+            string_display(f, "Install Reason  :", Some(reason), cols)?;
+            let install_script = if desc.install_script { "Yes" } else { "No" };
+            string_display(f, "Install Script  :", Some(install_script), cols)?;
+        }
+        list_display(f, "Validated By    :", &self.validations(), cols)?;
+        if local && let Some(root) = self.backup_root {
+            list_display_linebreak(f, "Backup Files    :", &self.backups(root), cols)?;
+        }
+        writeln!(f)
+    }
+}
+
+fn sorted_names<'a>(pkgs: impl Iterator<Item = &'a Pkg>) -> Vec<&'a str> {
+    let mut names: Vec<_> = pkgs.map(|pkg| pkg.desc.name.as_str()).collect();
+    names.sort_unstable();
+    names
+}
+
+/// Display width of a string, counting each character as one column
+fn width(s: &str) -> usize {
+    s.chars().count()
+}
+
+/// A single value, word wrapped (pacman's `string_display`)
+fn string_display(
+    f: &mut fmt::Formatter<'_>,
+    title: &str,
+    value: Option<&str>,
+    cols: usize,
+) -> fmt::Result {
+    write!(f, "{title} ")?;
+    match value {
+        Some(value) if !value.is_empty() => indent_print(f, value, width(title) + 1, cols)?,
+        _ => f.write_str("None")?,
+    }
+    writeln!(f)
+}
+
+/// Items separated by two spaces, wrapped to new lines (pacman's `list_display`)
+fn list_display<T: fmt::Display>(
+    f: &mut fmt::Formatter<'_>,
+    title: &str,
+    items: &[T],
+    max_cols: usize,
+) -> fmt::Result {
+    write!(f, "{title} ")?;
+    let indent = width(title) + 1;
+    let Some((first, rest)) = items.split_first() else {
+        return writeln!(f, "None");
+    };
+    let first = first.to_string();
+    f.write_str(&first)?;
+    let mut cols = indent + width(&first);
+    for item in rest {
+        let item = item.to_string();
+        let item_width = width(&item);
+        if max_cols > indent && cols + item_width + 2 >= max_cols {
+            write!(f, "\n{:indent$}", "")?;
+            cols = indent;
+        } else if cols != indent {
+            f.write_str("  ")?;
+            cols += 2;
+        }
+        f.write_str(&item)?;
+        cols += item_width;
+    }
+    writeln!(f)
+}
+
+/// One item per line (pacman's `list_display_linebreak`)
+fn list_display_linebreak<T: fmt::Display>(
+    f: &mut fmt::Formatter<'_>,
+    title: &str,
+    items: &[T],
+    cols: usize,
+) -> fmt::Result {
+    write!(f, "{title} ")?;
+    let indent = width(title) + 1;
+    if items.is_empty() {
+        return writeln!(f, "None");
+    }
+    for (i, item) in items.iter().enumerate() {
+        if i != 0 {
+            write!(f, "{:indent$}", "")?;
+        }
+        indent_print(f, &item.to_string(), indent, cols)?;
+        writeln!(f)?;
+    }
+    Ok(())
+}
+
+/// Print `s` word wrapped at `cols`, indenting continuation lines (pacman's `indentprint`)
+fn indent_print(f: &mut fmt::Formatter<'_>, s: &str, indent: usize, cols: usize) -> fmt::Result {
+    if cols == 0 || indent > cols {
+        return f.write_str(s);
+    }
+    let mut cidx = indent;
+    let mut chars = s.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != ' ' {
+            f.write_char(c)?;
+            cidx += 1;
+            continue;
+        }
+        // Runs of spaces collapse into one
+        if chars.peek().is_none_or(|&(_, next)| next == ' ') {
+            continue;
+        }
+        let word = s[i + 1..].split(' ').next().unwrap_or_default();
+        if width(word) + 1 > cols.saturating_sub(cidx) {
+            write!(f, "\n{:indent$}", "")?;
+            cidx = indent;
+        } else {
+            f.write_char(' ')?;
+            cidx += 1;
+        }
+    }
+    Ok(())
+}
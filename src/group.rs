@@ -0,0 +1,84 @@
+use {
+    crate::Pkg,
+    std::collections::{BTreeMap, BTreeSet},
+};
+
+/// Collect every group found in `pkgs`, mapped to its members
+///
+/// Both the groups and their members are sorted by name.
+/// A package name is only listed once per group, the first one wins,
+/// so `pkgs` should be in repository order.
+pub fn groups<'pkgs>(
+    pkgs: impl Iterator<Item = &'pkgs Pkg>,
+) -> BTreeMap<&'pkgs str, Vec<&'pkgs Pkg>> {
+    let mut groups: BTreeMap<&str, Vec<&Pkg>> = BTreeMap::new();
+    let mut seen = BTreeSet::new();
+    for pkg in pkgs {
+        for group in &pkg.desc.groups {
+            if seen.insert((group.as_str(), pkg.desc.name.as_str())) {
+                groups.entry(group.as_str()).or_default().push(pkg);
+            }
+        }
+    }
+    for members in groups.values_mut() {
+        members.sort_by(|a, b| a.desc.name.cmp(&b.desc.name));
+    }
+    groups
+}
+
+/// Returns the members of group `name` in `pkgs`
+pub fn group_members<'pkgs>(
+    name: &str,
+    pkgs: impl Iterator<Item = &'pkgs Pkg>,
+) -> impl Iterator<Item = &'pkgs Pkg> {
+    pkgs.filter(move |pkg| pkg.desc.groups.iter().any(|group| group == name))
+}
+
+/// Returns the members of group `name` in `sync_pkgs` that are not installed
+///
+/// A member counts as installed if `local_pkgs` has a package of the same name.
+pub fn missing_group_members<'pkgs, 'local>(
+    name: &str,
+    sync_pkgs: impl Iterator<Item = &'pkgs Pkg>,
+    local_pkgs: impl Iterator<Item = &'local Pkg>,
+) -> impl Iterator<Item = &'pkgs Pkg> {
+    let installed: BTreeSet<&str> = local_pkgs.map(|pkg| pkg.desc.name.as_str()).collect();
+    group_members(name, sync_pkgs).filter(move |pkg| !installed.contains(pkg.desc.name.as_str()))
+}
+
+/// A target that could not be resolved by [`expand_targets`]
+#[derive(Debug, thiserror::Error)]
+#[error("target not found: {0}")]
+pub struct TargetNotFound(pub String);
+
+/// Expand a list of targets into packages, like pacman does for `-S`
+///
+/// A target naming a package resolves to that package, otherwise it's treated as a
+/// group and resolves to all of its members.
+/// The first package with a given name wins, so `pkgs` should be in repository order.
+/// Packages appearing through several targets are only returned once.
+pub fn expand_targets<'pkgs, S: AsRef<str>>(
+    targets: &[S],
+    pkgs: &'pkgs [Pkg],
+) -> Result<Vec<&'pkgs Pkg>, TargetNotFound> {
+    let mut expanded: Vec<&Pkg> = Vec::new();
+    let mut seen = BTreeSet::new();
+    for target in targets {
+        let target = target.as_ref();
+        let resolved: Vec<&Pkg> = if let Some(pkg) = pkgs.iter().find(|pkg| pkg.desc.name == target)
+        {
+            vec![pkg]
+        } else {
+            group_members(target, pkgs.iter()).collect()
+        };
+        if resolved.is_empty() {
+            return Err(TargetNotFound(target.to_owned()));
+        }
+        for pkg in resolved {
+            if seen.insert(pkg.desc.name.as_str()) {
+                expanded.push(pkg);
+            }
+        }
+    }
+    Ok(expanded)
+}
@@ -0,0 +1,480 @@
+//! Reading the pacman databases of container images, without running them
+//!
+//! Supports `docker save` tarballs, OCI image archives and OCI image layout directories.
+//! Layers are applied in order, including whiteouts, but only the files pacman
+//! cares about are kept, along with the targets of symlinks to them.
+
+use {
+    crate::{
+        DbReadError, Pkg, PkgDesc, SyncDb, check_db_version, compress,
+        config::{Config, ConfigError},
+        json::Json,
+        merge_depends, parse_files, read_syncdb_bytes,
+    },
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        fs::File,
+        io::{Read, Seek as _, SeekFrom},
+        path::{Component, Path, PathBuf},
+    },
+    tar::{Archive, EntryType},
+};
+
+/// Only files under these paths are kept while applying layers
+const KEPT_PREFIXES: [&str; 2] = ["etc/", "var/lib/pacman/"];
+
+const DEFAULT_DB_PATH: &str = "var/lib/pacman";
+
+/// Symlinks followed before giving up, like Linux' `MAXSYMLINKS`
+const MAX_SYMLINK_HOPS: usize = 40;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImageError {
+    #[error("Io error")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid JSON in {file} at byte {offset}")]
+    Json { file: String, offset: usize },
+    #[error("Invalid image: {0}")]
+    Invalid(String),
+    #[error("Failed to read pacman.conf of the image")]
+    Config(#[from] ConfigError),
+    #[error("Failed to read database")]
+    Db(#[from] DbReadError),
+}
+
+/// The pacman related files of a container image, with all layers applied
+#[derive(Debug, Default)]
+pub struct ContainerImage {
+    /// Regular files by path, relative to the image root
+    files: BTreeMap<String, Vec<u8>>,
+    /// Targets of all symlinks by path, relative to the image root
+    symlinks: BTreeMap<String, String>,
+}
+
+impl ContainerImage {
+    /// Open a `docker save` tarball, an OCI image archive or an OCI image layout directory
+    ///
+    /// For multi-platform images, the first manifest for a real platform is used.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ImageError> {
+        let path = path.as_ref();
+        let mut source = if path.is_dir() {
+            Source::Dir(path.to_owned())
+        } else {
+            Source::tarball(File::open(path)?)?
+        };
+        let layers = source.layers()?;
+        // Symlinks may point outside of the kept paths, and so may `DBPath`.
+        // Their targets are only known once all layers are applied,
+        // so the layers are applied again until nothing is missing.
+        let mut extra: BTreeSet<String> = BTreeSet::new();
+        loop {
+            let keep = |path: &str| kept(path) || extra.iter().any(|dir| in_tree(path, dir));
+            let mut image = Self::default();
+            for layer in &layers {
+                image.apply_layer(source.open(layer)?, &keep)?;
+            }
+            let missing: Vec<String> = image
+                .needed_paths()
+                .into_iter()
+                .filter(|path| !keep(path) && !keep(&format!("{path}/")))
+                .collect();
+            if missing.is_empty() {
+                return Ok(image);
+            }
+            extra.extend(missing);
+        }
+    }
+    /// The contents of a file under `/etc` or `/var/lib/pacman` in the image,
+    /// or behind a symlink there
+    #[must_use]
+    pub fn file(&self, path: &str) -> Option<&[u8]> {
+        self.files.get(&self.resolve(path)).map(Vec::as_slice)
+    }
+    /// `path` relative to the image root, with all symlinks in it followed
+    fn resolve(&self, path: &str) -> String {
+        let mut path = normalize(Path::new(path));
+        'hops: for _ in 0..MAX_SYMLINK_HOPS {
+            let mut end = 0;
+            while end < path.len() {
+                end = path[end + 1..]
+                    .find('/')
+                    .map_or(path.len(), |i| end + 1 + i);
+                if let Some(target) = self.symlinks.get(&path[..end]) {
+                    path = normalize(Path::new(&format!("{target}{}", &path[end..])));
+                    continue 'hops;
+                }
+            }
+            break;
+        }
+        path
+    }
+    /// Names of the kept files and symlinks directly in the directory `path`
+    fn list_dir(&self, path: &str) -> Vec<String> {
+        let prefix = format!("{}/", self.resolve(path));
+        let names = self.files.keys().chain(self.symlinks.keys());
+        let mut names: Vec<String> = names
+            .filter_map(|path| path.strip_prefix(&prefix))
+            .filter(|name| !name.contains('/'))
+            .map(str::to_owned)
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+    /// The database path from the image's `pacman.conf`, relative to the image root
+    fn db_path(&self) -> Result<String, ConfigError> {
+        let db_path = self.config()?.and_then(|config| config.db_path);
+        Ok(match db_path {
+            Some(db_path) => self.resolve(&db_path.to_string_lossy()),
+            None => self.resolve(DEFAULT_DB_PATH),
+        })
+    }
+    /// Paths outside of `/etc` and `/var/lib/pacman` that pacman would read:
+    /// the targets of symlinks there and the database path
+    fn needed_paths(&self) -> BTreeSet<String> {
+        let mut paths: BTreeSet<String> = self
+            .symlinks
+            .keys()
+            .filter(|path| kept(path) || kept(&format!("{path}/")))
+            .map(|path| self.resolve(path))
+            .collect();
+        // An unreadable config is reported when it's used
+        if let Ok(db_path) = self.db_path() {
+            paths.insert(db_path);
+        }
+        paths
+    }
+    /// The image's `/etc/pacman.conf`, if it has one
+    pub fn config(&self) -> Result<Option<Config>, ConfigError> {
+        if self.file("etc/pacman.conf").is_none() {
+            return Ok(None);
+        }
+        Config::read_with(
+            "/etc/pacman.conf",
+            |path| {
+                let data = path
+                    .to_str()
+                    .and_then(|path| self.file(path))
+                    .ok_or(std::io::ErrorKind::NotFound)?;
+                String::from_utf8(data.to_vec())
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+            },
+            |dir| {
+                let dir = dir.to_str().ok_or(std::io::ErrorKind::NotFound)?;
+                Ok(self.list_dir(dir))
+            },
+        )
+        .map(Some)
+    }
+    /// The local database under the `DBPath` of the image's `pacman.conf`,
+    /// like [`crate::read_local_db`] would return inside the image
+    pub fn local_db(&self) -> Result<Vec<Pkg>, ImageError> {
+        let local_db = format!("{}/local/", self.db_path()?);
+        let version = self.text(&format!("{local_db}ALPM_DB_VERSION"))?;
+        check_db_version(version)?;
+        let mut dirs: Vec<&str> = self
+            .files
+            .range(local_db.clone()..)
+            .map_while(|(path, _)| path.strip_prefix(&local_db))
+            .filter_map(|rest| Some(rest.split_once('/')?.0))
+            .collect();
+        dirs.dedup();
+        dirs.into_iter()
+            .map(|dir| {
+                let entry = format!("{local_db}{dir}");
+                let desc = self.text(&format!("{entry}/desc"))?;
+                let depends = self.text(&format!("{entry}/depends")).ok();
+                let install_script = self.files.contains_key(&format!("{entry}/install"));
+                let (files, backup) = parse_files(&self.text(&format!("{entry}/files"))?);
+                let desc = PkgDesc::parse(&merge_depends(desc, depends.as_deref()), install_script)
+                    .ok_or(DbReadError::MissingFields(entry))?;
+                Ok(Pkg {
+                    desc,
+                    files,
+                    backup,
+                })
+            })
+            .collect()
+    }
+    /// The sync databases of the repositories in the image's `pacman.conf`
+    ///
+    /// Repositories that were never synced in the image are skipped,
+    /// and there are none without a `pacman.conf`.
+    pub fn sync_dbs(&self) -> Result<Vec<SyncDb>, ImageError> {
+        let Some(config) = self.config()? else {
+            return Ok(Vec::new());
+        };
+        let db_path = self.db_path()?;
+        let mut dbs = Vec::new();
+        for name in config.repo_names() {
+            if let Some(data) = self.file(&format!("{db_path}/sync/{name}.db")) {
+                dbs.push(SyncDb {
+                    name: name.into(),
+                    pkgs: read_syncdb_bytes(data)?,
+                });
+            }
+        }
+        Ok(dbs)
+    }
+    fn text(&self, path: &str) -> std::io::Result<String> {
+        let data = self.file(path).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("/{path} not found in image"),
+            )
+        })?;
+        String::from_utf8(data.to_vec())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Where the manifests and layers of an image are read from
+enum Source {
+    /// OCI image layout directory
+    Dir(PathBuf),
+    /// `docker save` tarball or OCI image archive
+    Tarball {
+        file: File,
+        /// Offset and size of each regular file in the tarball
+        members: BTreeMap<String, (u64, u64)>,
+    },
+}
+
+impl Source {
+    fn tarball(file: File) -> Result<Self, ImageError> {
+        let mut members = BTreeMap::new();
+        let mut ar = Archive::new(&file);
+        for en in ar.entries()? {
+            let en = en?;
+            if en.header().entry_type().is_file() {
+                members.insert(normalize(&en.path()?), (en.raw_file_position(), en.size()));
+            }
+        }
+        Ok(Self::Tarball { file, members })
+    }
+    fn open(&mut self, path: &str) -> Result<Box<dyn Read + '_>, ImageError> {
+        match self {
+            Self::Dir(dir) => Ok(Box::new(File::open(dir.join(path))?)),
+            Self::Tarball { file, members } => {
+                let &(offset, size) = members
+                    .get(path)
+                    .ok_or_else(|| ImageError::Invalid(format!("missing {path}")))?;
+                file.seek(SeekFrom::Start(offset))?;
+                Ok(Box::new(Read::take(&*file, size)))
+            }
+        }
+    }
+    fn exists(&self, path: &str) -> bool {
+        match self {
+            Self::Dir(dir) => dir.join(path).exists(),
+            Self::Tarball { members, .. } => members.contains_key(path),
+        }
+    }
+    fn json(&mut self, path: &str) -> Result<Json, ImageError> {
+        let mut src = String::new();
+        self.open(path)?.read_to_string(&mut src)?;
+        Json::parse(&src).map_err(|e| ImageError::Json {
+            file: path.to_owned(),
+            offset: e.0,
+        })
+    }
+    /// Paths of the layers, from the bottom up
+    fn layers(&mut self) -> Result<Vec<String>, ImageError> {
+        // `docker save` writes `manifest.json`, OCI images only have `index.json`
+        if self.exists("manifest.json") {
+            let manifest = self.json("manifest.json")?;
+            let image = manifest
+                .as_array()
+                .first()
+                .ok_or_else(|| ImageError::Invalid("empty manifest.json".into()))?;
+            return image
+                .get("Layers")
+                .map(Json::as_array)
+                .unwrap_or_default()
+                .iter()
+                .map(layer_path)
+                .collect();
+        }
+        let mut manifest = self.json("index.json")?;
+        // Image indexes can be nested, follow them down to an image manifest
+        while manifest.get("manifests").is_some() {
+            let descriptor = pick_manifest(&manifest)?;
+            manifest = self.json(&blob_path(descriptor)?)?;
+        }
+        manifest
+            .get("layers")
+            .map(Json::as_array)
+            .unwrap_or_default()
+            .iter()
+            .map(blob_path)
+            .collect()
+    }
+}
+
+/// The first manifest of an image index that is for a real platform,
+/// skipping attestations and the like
+fn pick_manifest(index: &Json) -> Result<&Json, ImageError> {
+    let manifests = index
+        .get("manifests")
+        .map(Json::as_array)
+        .unwrap_or_default();
+    manifests
+        .iter()
+        .find(|m| {
+            m.get("platform")
+                .and_then(|p| p.get("architecture"))
+                .and_then(Json::as_str)
+                != Some("unknown")
+        })
+        .ok_or_else(|| ImageError::Invalid("image index has no manifests".into()))
+}
+
+/// Path of the blob a descriptor refers to (`blobs/<algorithm>/<hex>`)
+fn blob_path(descriptor: &Json) -> Result<String, ImageError> {
+    let digest = descriptor.get("digest").and_then(Json::as_str);
+    let Some((algorithm, hex)) = digest.and_then(|digest| digest.split_once(':')) else {
+        return Err(ImageError::Invalid("descriptor without digest".into()));
+    };
+    if [algorithm, hex]
+        .iter()
+        .any(|part| part.is_empty() || part.contains(['/', '.']))
+    {
+        return Err(ImageError::Invalid(format!("invalid digest {digest:?}")));
+    }
+    Ok(format!("blobs/{algorithm}/{hex}"))
+}
+
+/// Path of a layer listed in `manifest.json`, which must stay inside the tarball
+fn layer_path(layer: &Json) -> Result<String, ImageError> {
+    let Some(path) = layer.as_str() else {
+        return Err(ImageError::Invalid("invalid layer in manifest.json".into()));
+    };
+    if path.starts_with('/')
+        || Path::new(path)
+            .components()
+            .any(|c| c == Component::ParentDir)
+    {
+        return Err(ImageError::Invalid(format!("invalid layer path {path:?}")));
+    }
+    Ok(normalize(Path::new(path)))
+}
+
+/// A path inside a layer as `a/b/c`, without `.`, leading slashes or `..`
+fn normalize(path: &Path) -> String {
+    let mut out = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => out.push(part.to_string_lossy()),
+            Component::ParentDir => {
+                out.pop();
+            }
+            _ => {}
+        }
+    }
+    out.join("/")
+}
+
+fn kept(path: &str) -> bool {
+    KEPT_PREFIXES.iter().any(|prefix| path.starts_with(prefix))
+}
+
+/// Whether `path` is `dir` or under it
+fn in_tree(path: &str, dir: &str) -> bool {
+    path.strip_prefix(dir)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Remove `path` and everything under it
+fn remove_tree<T>(map: &mut BTreeMap<String, T>, path: &str) {
+    map.retain(|entry, _| !in_tree(entry, path));
+}
+
+impl ContainerImage {
+    /// Apply a (possibly compressed) layer tarball, keeping the regular files
+    /// `keep` accepts and all symlinks
+    fn apply_layer(
+        &mut self,
+        layer: impl Read,
+        keep: &dyn Fn(&str) -> bool,
+    ) -> Result<(), ImageError> {
+        let mut ar = Archive::new(compress::decompress(layer)?);
+        // Whiteouts only hide files of lower layers, so they are applied before the
+        // files of this layer are added
+        let mut removed = Vec::new();
+        let mut opaque_dirs = Vec::new();
+        let mut added = BTreeMap::new();
+        let mut symlinks = BTreeMap::new();
+        let mut links = Vec::new();
+        for en in ar.entries()? {
+            let mut en = en?;
+            let path = normalize(&en.path()?);
+            let (dir, name) = path.rsplit_once('/').unwrap_or(("", &path));
+            if name == ".wh..wh..opq" {
+                opaque_dirs.push(dir.to_owned());
+                continue;
+            }
+            if let Some(hidden) = name.strip_prefix(".wh.") {
+                removed.push(if dir.is_empty() {
+                    hidden.to_owned()
+                } else {
+                    format!("{dir}/{hidden}")
+                });
+                continue;
+            }
+            match en.header().entry_type() {
+                EntryType::Symlink => {
+                    if let Some(target) = en.link_name()? {
+                        // Relative targets are relative to the symlink's directory
+                        let target = if target.is_absolute() {
+                            normalize(&target)
+                        } else {
+                            normalize(&Path::new(dir).join(target))
+                        };
+                        symlinks.insert(path, target);
+                    }
+                }
+                EntryType::Link if keep(&path) => {
+                    if let Some(target) = en.link_name()? {
+                        links.push((path, normalize(&target)));
+                    }
+                }
+                ty if ty.is_file() && keep(&path) => {
+                    let mut data = Vec::new();
+                    en.read_to_end(&mut data)?;
+                    added.insert(path, data);
+                }
+                _ => {}
+            }
+        }
+        for dir in opaque_dirs {
+            if dir.is_empty() {
+                self.files.clear();
+                self.symlinks.clear();
+            } else {
+                self.files
+                    .retain(|file, _| !in_tree(file, &dir) || file == &dir);
+                self.symlinks
+                    .retain(|link, _| !in_tree(link, &dir) || link == &dir);
+            }
+        }
+        for path in removed {
+            remove_tree(&mut self.files, &path);
+            remove_tree(&mut self.symlinks, &path);
+        }
+        // A file replaces a symlink and the other way around
+        for path in added.keys() {
+            self.symlinks.remove(path);
+        }
+        for path in symlinks.keys() {
+            remove_tree(&mut self.files, path);
+        }
+        self.files.extend(added);
+        self.symlinks.extend(symlinks);
+        for (path, target) in links {
+            if let Some(data) = self.files.get(&target).cloned() {
+                self.files.insert(path, data);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,326 @@
+//! Minimal JSON reading and writing, for image manifests, advisories, SBOMs
+//! and the `--json` output of the command line tool
+
+use std::fmt::{self, Write as _};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Num(f64),
+    Str(String),
+    Array(Vec<Json>),
+    /// Members in document order
+    Object(Vec<(String, Json)>),
+}
+
+/// Arrays and objects nested deeper than this are rejected,
+/// so that hostile input can't overflow the stack
+const MAX_DEPTH: usize = 128;
+
+/// Byte offset where parsing failed
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid JSON at byte {0}")]
+pub struct JsonError(pub usize);
+
+impl Json {
+    pub fn parse(src: &str) -> Result<Self, JsonError> {
+        let mut parser = Parser {
+            src: src.as_bytes(),
+            pos: 0,
+            depth: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_ws();
+        if parser.pos != parser.src.len() {
+            return Err(JsonError(parser.pos));
+        }
+        Ok(value)
+    }
+    /// Member `key` of an object
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&Self> {
+        match self {
+            Self::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+    #[must_use]
+    pub fn as_array(&self) -> &[Self] {
+        match self {
+            Self::Array(items) => items,
+            _ => &[],
+        }
+    }
+    /// Build an object from `(key, value)` pairs
+    #[must_use]
+    pub fn object<'a>(members: impl IntoIterator<Item = (&'a str, Self)>) -> Self {
+        Self::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| (key.to_owned(), value))
+                .collect(),
+        )
+    }
+    #[must_use]
+    pub fn str(s: impl Into<String>) -> Self {
+        Self::Str(s.into())
+    }
+    /// A string, or `null` for `None`
+    #[must_use]
+    pub fn opt_str(s: Option<&str>) -> Self {
+        s.map_or(Self::Null, Self::str)
+    }
+    /// An array of strings
+    pub fn strs<T: ToString>(items: impl IntoIterator<Item = T>) -> Self {
+        Self::Array(
+            items
+                .into_iter()
+                .map(|item| Self::Str(item.to_string()))
+                .collect(),
+        )
+    }
+    /// A number from an integer, like a size or a date
+    #[must_use]
+    #[expect(
+        clippy::cast_precision_loss,
+        reason = "sizes and dates are far below 2^53"
+    )]
+    pub fn uint(n: u64) -> Self {
+        Self::Num(n as f64)
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => f.write_str("null"),
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::Num(n) => write!(f, "{n}"),
+            Self::Str(s) => write_str(f, s),
+            Self::Array(items) => {
+                f.write_char('[')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_char(']')
+            }
+            Self::Object(members) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i != 0 {
+                        f.write_char(',')?;
+                    }
+                    write_str(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_str(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", u32::from(c))?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+struct Parser<'a> {
+    src: &'a [u8],
+    pos: usize,
+    /// Arrays and objects currently open
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn err<T>(&self) -> Result<T, JsonError> {
+        Err(JsonError(self.pos))
+    }
+    fn skip_ws(&mut self) {
+        while self
+            .src
+            .get(self.pos)
+            .is_some_and(|b| matches!(b, b' ' | b'\t' | b'\n' | b'\r'))
+        {
+            self.pos += 1;
+        }
+    }
+    fn eat(&mut self, lit: &str) -> bool {
+        let found = self.src[self.pos..].starts_with(lit.as_bytes());
+        if found {
+            self.pos += lit.len();
+        }
+        found
+    }
+    fn value(&mut self) -> Result<Json, JsonError> {
+        self.skip_ws();
+        match self.src.get(self.pos) {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => Ok(Json::Str(self.string()?)),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ if self.eat("null") => Ok(Json::Null),
+            _ if self.eat("true") => Ok(Json::Bool(true)),
+            _ if self.eat("false") => Ok(Json::Bool(false)),
+            _ => self.err(),
+        }
+    }
+    /// Parse comma separated items until `close`, with `item` parsing each one
+    fn list(
+        &mut self,
+        close: u8,
+        mut item: impl FnMut(&mut Self) -> Result<(), JsonError>,
+    ) -> Result<(), JsonError> {
+        if self.depth == MAX_DEPTH {
+            return self.err();
+        }
+        // Skip the opening bracket
+        self.pos += 1;
+        self.skip_ws();
+        if self.src.get(self.pos) == Some(&close) {
+            self.pos += 1;
+            return Ok(());
+        }
+        self.depth += 1;
+        loop {
+            item(self)?;
+            self.skip_ws();
+            match self.src.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(&b) if b == close => {
+                    self.pos += 1;
+                    self.depth -= 1;
+                    return Ok(());
+                }
+                _ => return self.err(),
+            }
+        }
+    }
+    fn object(&mut self) -> Result<Json, JsonError> {
+        let mut members = Vec::new();
+        self.list(b'}', |p| {
+            p.skip_ws();
+            if p.src.get(p.pos) != Some(&b'"') {
+                return p.err();
+            }
+            let key = p.string()?;
+            p.skip_ws();
+            if !p.eat(":") {
+                return p.err();
+            }
+            members.push((key, p.value()?));
+            Ok(())
+        })?;
+        Ok(Json::Object(members))
+    }
+    fn array(&mut self) -> Result<Json, JsonError> {
+        let mut items = Vec::new();
+        self.list(b']', |p| {
+            items.push(p.value()?);
+            Ok(())
+        })?;
+        Ok(Json::Array(items))
+    }
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.pos;
+        while self
+            .src
+            .get(self.pos)
+            .is_some_and(|b| matches!(b, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+        {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.src[start..self.pos])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(Json::Num)
+            .ok_or(JsonError(start))
+    }
+    fn hex4(&mut self) -> Result<u32, JsonError> {
+        // `from_str_radix` alone would also accept a sign
+        let hex = self
+            .src
+            .get(self.pos..self.pos + 4)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .ok_or(JsonError(self.pos))?;
+        self.pos += 4;
+        Ok(hex)
+    }
+    fn string(&mut self) -> Result<String, JsonError> {
+        // Skip the opening quote
+        self.pos += 1;
+        let mut out = Vec::new();
+        loop {
+            let Some(&b) = self.src.get(self.pos) else {
+                return self.err();
+            };
+            self.pos += 1;
+            match b {
+                b'"' => break,
+                b'\\' => {
+                    let Some(&esc) = self.src.get(self.pos) else {
+                        return self.err();
+                    };
+                    self.pos += 1;
+                    let c = match esc {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.unicode_escape()?,
+                        _ => return self.err(),
+                    };
+                    out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                b => out.push(b),
+            }
+        }
+        String::from_utf8(out).or_else(|_| self.err())
+    }
+    /// The character of a `\u` escape, which may be a surrogate pair
+    ///
+    /// Unpaired surrogates can't be represented in a `String`, so they are errors.
+    fn unicode_escape(&mut self) -> Result<char, JsonError> {
+        let high = self.hex4()?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            if !self.eat("\\u") {
+                return self.err();
+            }
+            let low = self.hex4()?;
+            if !(0xDC00..=0xDFFF).contains(&low) {
+                return Err(JsonError(self.pos - 4));
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or(JsonError(self.pos))
+    }
+}
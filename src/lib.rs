@@ -2,22 +2,124 @@
 #![warn(clippy::pedantic)]
 #![expect(clippy::missing_errors_doc)]
 
+pub mod advisory;
+pub mod cache;
+mod compress;
+pub mod config;
 pub mod dep;
+pub mod diff;
+pub mod display;
+pub mod group;
+pub mod image;
+pub mod json;
+pub mod log;
+pub mod mtree;
+#[cfg(feature = "tokio")]
+pub mod nonblocking;
+pub mod package;
+#[cfg(feature = "pgp")]
+pub mod pgp;
 mod pkgdesc;
-pub use pkgdesc::{Depend, InstallReason, OptDepend, PkgDesc, ReqCmp, Validation};
+pub mod repo;
+pub mod sbom;
+pub mod srcinfo;
+pub mod transaction;
+pub mod version;
+#[cfg(feature = "watch")]
+pub mod watch;
+pub mod write;
+pub use pkgdesc::{DepVer, Depend, InstallReason, OptDepend, PkgDesc, ReqCmp, Validation};
 
 use {
-    flate2::read::GzDecoder,
-    std::{io::Read, path::Path},
+    smol_str::SmolStr,
+    std::{collections::BTreeMap, io::Read, path::Path},
     tar::Archive,
 };
 
+#[derive(Debug)]
 pub struct Pkg {
     pub desc: PkgDesc,
     pub files: Vec<Box<str>>,
+    /// Files that are preserved as `.pacnew`/`.pacsave` (local database only)
+    pub backup: Vec<Backup>,
 }
 
-const SUPPORTED_DB_VERSION: &str = "9";
+/// A file marked for backup by its package
+#[derive(Debug, Clone)]
+pub struct Backup {
+    /// Path relative to the root, without leading slash
+    pub path: Box<str>,
+    /// MD5 checksum of the file as installed
+    pub md5: SmolStr,
+}
+
+/// State of a backup file on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupStatus {
+    /// Matches the checksum recorded at install time
+    Unmodified,
+    Modified,
+    Missing,
+    /// Exists, but couldn't be read
+    Unreadable,
+}
+
+impl Backup {
+    /// Compare the file under `root` against the checksum recorded at install time
+    #[must_use]
+    pub fn status(&self, root: &Path) -> BackupStatus {
+        let file = match std::fs::File::open(root.join(&*self.path)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return BackupStatus::Missing,
+            Err(_) => return BackupStatus::Unreadable,
+        };
+        match md5_hex(file) {
+            Ok(md5) if md5 == self.md5 => BackupStatus::Unmodified,
+            Ok(_) => BackupStatus::Modified,
+            Err(_) => BackupStatus::Unreadable,
+        }
+    }
+}
+
+pub(crate) fn md5_hex(mut reader: impl Read) -> std::io::Result<SmolStr> {
+    use md5::Digest as _;
+    let mut hasher = md5::Md5::new();
+    std::io::copy(&mut reader, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+/// Parse the `files` file of a local database entry
+pub(crate) fn parse_files(src: &str) -> (Vec<Box<str>>, Vec<Backup>) {
+    let mut files = Vec::new();
+    let mut backup = Vec::new();
+    let mut section = None;
+    for line in src.lines() {
+        if line.is_empty() {
+            section = None;
+            continue;
+        }
+        match section {
+            Some("FILES") => files.push(line.into()),
+            Some("BACKUP") => {
+                if let Some((path, md5)) = line.split_once('\t') {
+                    backup.push(Backup {
+                        path: path.into(),
+                        md5: md5.into(),
+                    });
+                }
+            }
+            Some(_) => {}
+            None => section = line.strip_prefix('%').and_then(|l| l.strip_suffix('%')),
+        }
+    }
+    (files, backup)
+}
+
+pub(crate) const SUPPORTED_DB_VERSION: &str = "9";
 
 #[derive(Debug, thiserror::Error)]
 pub enum DbReadError {
@@ -28,58 +130,257 @@ pub enum DbReadError {
         supported: &'static str,
         got: String,
     },
+    #[error("Database entry {0} is missing mandatory fields")]
+    MissingFields(String),
 }
 
 pub fn read_local_db() -> Result<Vec<Pkg>, DbReadError> {
-    let mut pkgs = vec![];
-    let local_db_root = Path::new("/var/lib/pacman/local/");
-    let db_ver = std::fs::read_to_string(local_db_root.join("ALPM_DB_VERSION"))?;
+    read_local_db_at("/var/lib/pacman")
+}
+
+/// Read the local database under the database path `db_path` (e.g. `/var/lib/pacman`),
+/// which can be anywhere, like a copy of another host's database
+///
+/// Only the current database version is accepted,
+/// see [`read_local_db_with_format`] for older databases.
+pub fn read_local_db_at(db_path: impl AsRef<Path>) -> Result<Vec<Pkg>, DbReadError> {
+    let local_db_root = &db_path.as_ref().join("local");
+    check_db_version(std::fs::read_to_string(
+        local_db_root.join("ALPM_DB_VERSION"),
+    )?)?;
+    Ok(read_local_entries(local_db_root)?.0)
+}
+
+/// Check the contents of an `ALPM_DB_VERSION` file against the supported version
+pub(crate) fn check_db_version(db_ver: String) -> Result<(), DbReadError> {
     if db_ver.trim() != SUPPORTED_DB_VERSION {
         return Err(DbReadError::DbVerMismatch {
             supported: SUPPORTED_DB_VERSION,
             got: db_ver,
         });
     }
+    Ok(())
+}
+
+/// The layout of a database, as detected while reading it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DbFormat {
+    /// Value of `ALPM_DB_VERSION` (local databases only)
+    ///
+    /// `None` if there is no such file, like in local databases of pacman before 4.2.
+    pub version: Option<u32>,
+    /// Dependency fields are in separate `depends` files, like in local databases of
+    /// pacman 3 and sync databases made by older versions of `repo-add`
+    pub split_depends: bool,
+}
+
+impl DbFormat {
+    /// Whether this is an older layout than the one pacman currently writes
+    ///
+    /// Sync databases have no version, so a missing version doesn't count.
+    #[must_use]
+    pub fn is_legacy(&self) -> bool {
+        self.split_depends
+            || self
+                .version
+                .is_some_and(|v| v.to_string() != SUPPORTED_DB_VERSION)
+    }
+}
+
+/// Read the local database under `db_path`, also accepting older layouts
+///
+/// Databases without `ALPM_DB_VERSION` and older versions are read,
+/// only versions newer than the supported one are rejected.
+pub fn read_local_db_with_format(
+    db_path: impl AsRef<Path>,
+) -> Result<(Vec<Pkg>, DbFormat), DbReadError> {
+    let local_db_root = &db_path.as_ref().join("local");
+    let version = match std::fs::read_to_string(local_db_root.join("ALPM_DB_VERSION")) {
+        Ok(db_ver) => {
+            let supported: u32 = SUPPORTED_DB_VERSION.parse().unwrap_or_default();
+            match db_ver.trim().parse() {
+                Ok(version) if version <= supported => Some(version),
+                _ => {
+                    return Err(DbReadError::DbVerMismatch {
+                        supported: SUPPORTED_DB_VERSION,
+                        got: db_ver,
+                    });
+                }
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    let (pkgs, split_depends) = read_local_entries(local_db_root)?;
+    Ok((
+        pkgs,
+        DbFormat {
+            version,
+            split_depends,
+        },
+    ))
+}
+
+/// Read all entry directories of a local database, and whether any had a `depends` file
+fn read_local_entries(local_db_root: &Path) -> Result<(Vec<Pkg>, bool), DbReadError> {
+    let mut pkgs = vec![];
+    let mut split_depends = false;
     for entry in std::fs::read_dir(local_db_root)? {
         let entry = entry?;
-        if !entry.file_type().is_ok_and(|ft| ft.is_dir()) {
+        // Package names can't start with a dot, so hidden directories aren't entries,
+        // even though other tools may create some here, e.g. for staging writes
+        if !entry.file_type().is_ok_and(|ft| ft.is_dir())
+            || entry.file_name().as_encoded_bytes().starts_with(b".")
+        {
             continue;
         }
-        let install_script = entry.path().join("install").exists();
-        let desc = PkgDesc::parse(
-            &std::fs::read_to_string(entry.path().join("desc"))?,
-            install_script,
-        );
-        pkgs.push(Pkg {
+        let (pkg, split) = read_local_entry_with_format(&entry.path())?;
+        split_depends |= split;
+        pkgs.push(pkg);
+    }
+    Ok((pkgs, split_depends))
+}
+
+/// Read a single entry directory of the local database
+#[cfg(feature = "watch")]
+pub(crate) fn read_local_entry(dir: &Path) -> Result<Pkg, DbReadError> {
+    Ok(read_local_entry_with_format(dir)?.0)
+}
+
+/// Read a single entry directory, and whether it had a separate `depends` file
+fn read_local_entry_with_format(dir: &Path) -> Result<(Pkg, bool), DbReadError> {
+    let install_script = dir.join("install").exists();
+    let desc = std::fs::read_to_string(dir.join("desc"))?;
+    let depends = match std::fs::read_to_string(dir.join("depends")) {
+        Ok(depends) => Some(depends),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    let split_depends = depends.is_some();
+    let desc = PkgDesc::parse(&merge_depends(desc, depends.as_deref()), install_script)
+        .ok_or_else(|| DbReadError::MissingFields(dir.display().to_string()))?;
+    let (files, backup) = parse_files(&std::fs::read_to_string(dir.join("files"))?);
+    Ok((
+        Pkg {
             desc,
-            files: std::fs::read_to_string(entry.path().join("files"))?
-                .lines()
-                .skip(1)
-                .take_while(|l| !l.is_empty())
-                .map(From::from)
-                .collect(),
-        });
+            files,
+            backup,
+        },
+        split_depends,
+    ))
+}
+
+/// Append the contents of a legacy `depends` file to a `desc` file
+pub(crate) fn merge_depends(mut desc: String, depends: Option<&str>) -> String {
+    if let Some(depends) = depends {
+        // Make sure the last section of `desc` is terminated
+        desc.push_str("\n\n");
+        desc.push_str(depends);
     }
-    Ok(pkgs)
+    desc
 }
 
 pub fn read_syncdb(name: &str) -> Result<Vec<Pkg>, DbReadError> {
-    let mut pkgs = vec![];
-    let dec = GzDecoder::new(std::fs::File::open(format!(
-        "/var/lib/pacman/sync/{name}.db"
-    ))?);
-    let mut ar = Archive::new(dec);
+    read_syncdb_at("/var/lib/pacman", name)
+}
+
+/// Read the sync database `name` under the database path `db_path`
+pub fn read_syncdb_at(db_path: impl AsRef<Path>, name: &str) -> Result<Vec<Pkg>, DbReadError> {
+    read_syncdb_file(db_path.as_ref().join(format!("sync/{name}.db")))
+}
+
+/// Read a sync database file at any path, like an archived copy of a `.db` file
+pub fn read_syncdb_file(path: impl AsRef<Path>) -> Result<Vec<Pkg>, DbReadError> {
+    read_db_archive(std::fs::File::open(path)?)
+}
 
+/// Read a sync database from any reader, like an HTTP response body
+///
+/// The data may be compressed like a `.db` file.
+pub fn read_syncdb_from(reader: impl Read) -> Result<Vec<Pkg>, DbReadError> {
+    read_db_archive(reader)
+}
+
+/// Read a sync database from bytes in memory
+pub fn read_syncdb_bytes(bytes: &[u8]) -> Result<Vec<Pkg>, DbReadError> {
+    read_syncdb_from(bytes)
+}
+
+/// A sync database, with the name of its repository
+#[derive(Debug)]
+pub struct SyncDb {
+    pub name: SmolStr,
+    pub pkgs: Vec<Pkg>,
+}
+
+/// Read a sync database file, and report its detected layout
+pub fn read_syncdb_file_with_format(
+    path: impl AsRef<Path>,
+) -> Result<(Vec<Pkg>, DbFormat), DbReadError> {
+    let (pkgs, split_depends) = read_db_archive_with_format(std::fs::File::open(path)?)?;
+    Ok((
+        pkgs,
+        DbFormat {
+            version: None,
+            split_depends,
+        },
+    ))
+}
+
+/// Read the entries of a (possibly compressed) database archive
+///
+/// Handles both `.db` archives and `.files` archives, which also carry file lists.
+pub(crate) fn read_db_archive(reader: impl Read) -> Result<Vec<Pkg>, DbReadError> {
+    Ok(read_db_archive_with_format(reader)?.0)
+}
+
+/// Files of a database archive entry
+#[derive(Default)]
+struct ArchiveEntry {
+    desc: Option<String>,
+    depends: Option<String>,
+    files: Option<String>,
+}
+
+/// Read the entries of a database archive, and whether any had a `depends` file
+fn read_db_archive_with_format(reader: impl Read) -> Result<(Vec<Pkg>, bool), DbReadError> {
+    let mut ar = Archive::new(compress::decompress(reader)?);
+    let mut entries: BTreeMap<String, ArchiveEntry> = BTreeMap::new();
     for en in ar.entries()? {
         let mut en = en?;
+        let path = en.path()?;
+        let (Some(dir), Some(file_name)) = (path.parent(), path.file_name()) else {
+            continue;
+        };
+        let dir = dir.to_string_lossy().into_owned();
+        let file_name = file_name.to_string_lossy().into_owned();
+        if !matches!(&*file_name, "desc" | "depends" | "files") {
+            continue;
+        }
         let mut s = String::new();
-        if en.path()?.file_name() == Some("desc".as_ref()) {
-            en.read_to_string(&mut s)?;
-            pkgs.push(Pkg {
-                desc: PkgDesc::parse(&s, false),
-                files: Vec::new(),
-            });
+        en.read_to_string(&mut s)?;
+        let entry = entries.entry(dir).or_default();
+        match &*file_name {
+            "desc" => entry.desc = Some(s),
+            "depends" => entry.depends = Some(s),
+            _ => entry.files = Some(s),
         }
     }
-    Ok(pkgs)
+    let split_depends = entries.values().any(|entry| entry.depends.is_some());
+    let pkgs = entries
+        .into_iter()
+        .filter_map(|(dir, entry)| {
+            let (files, backup) = entry.files.as_deref().map(parse_files).unwrap_or_default();
+            let desc = merge_depends(entry.desc?, entry.depends.as_deref());
+            Some(match PkgDesc::parse(&desc, false) {
+                Some(desc) => Ok(Pkg {
+                    desc,
+                    files,
+                    backup,
+                }),
+                None => Err(DbReadError::MissingFields(dir)),
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    Ok((pkgs, split_depends))
 }
@@ -0,0 +1,314 @@
+//! Parsing of the pacman log (`/var/log/pacman.log`)
+
+use {
+    jiff::{Timestamp, civil::DateTime, tz::TimeZone},
+    smol_str::SmolStr,
+    std::path::Path,
+};
+
+/// Default location of the pacman log
+pub const DEFAULT_LOG_PATH: &str = "/var/log/pacman.log";
+
+/// A single line of the pacman log
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub time: LogTime,
+    pub source: LogSource,
+    pub event: LogEvent,
+}
+
+/// The timestamp of a log entry
+///
+/// Logs written by pacman 5.1 and later record the UTC offset,
+/// older ones only contain the local time with minute precision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogTime {
+    Timestamp(Timestamp),
+    Local(DateTime),
+}
+
+impl LogTime {
+    /// Converts to an absolute timestamp, interpreting local times in `tz`
+    pub fn to_timestamp(self, tz: &TimeZone) -> Result<Timestamp, jiff::Error> {
+        match self {
+            Self::Timestamp(ts) => Ok(ts),
+            Self::Local(dt) => Ok(dt.to_zoned(tz.clone())?.timestamp()),
+        }
+    }
+    fn parse(src: &str) -> Option<Self> {
+        if let Ok(ts) = Timestamp::strptime("%Y-%m-%dT%H:%M:%S%z", src) {
+            return Some(Self::Timestamp(ts));
+        }
+        DateTime::strptime("%Y-%m-%d %H:%M", src)
+            .ok()
+            .map(Self::Local)
+    }
+}
+
+/// Which component wrote a log entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogSource {
+    /// The pacman frontend
+    Pacman,
+    /// The alpm library
+    Alpm,
+    /// Output of an install scriptlet or hook
+    AlpmScriptlet,
+    /// Some other frontend, e.g. an AUR helper
+    Other(SmolStr),
+    /// Entry written before pacman started tagging the source (pre 4.1)
+    Untagged,
+}
+
+#[derive(Debug, Clone)]
+pub enum LogEvent {
+    /// The command line pacman was run with (`Running 'pacman -Syu'`)
+    Command(SmolStr),
+    TransactionStarted,
+    TransactionCompleted,
+    TransactionFailed,
+    TransactionInterrupted,
+    Package(PkgChange),
+    /// `warning: ...`
+    Warning(SmolStr),
+    /// `error: ...`
+    Error(SmolStr),
+    /// Anything else, like scriptlet output
+    Message(SmolStr),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PkgAction {
+    Install,
+    Upgrade,
+    Downgrade,
+    Reinstall,
+    Remove,
+}
+
+/// A change to an installed package
+#[derive(Debug, Clone)]
+pub struct PkgChange {
+    pub action: PkgAction,
+    pub name: SmolStr,
+    /// The version before the change. `None` for installs.
+    pub old_version: Option<SmolStr>,
+    /// The version after the change. `None` for removals.
+    pub new_version: Option<SmolStr>,
+}
+
+impl PkgChange {
+    fn parse(msg: &str) -> Option<Self> {
+        let (verb, rest) = msg.split_once(' ')?;
+        let action = match verb {
+            "installed" => PkgAction::Install,
+            "upgraded" => PkgAction::Upgrade,
+            "downgraded" => PkgAction::Downgrade,
+            "reinstalled" => PkgAction::Reinstall,
+            "removed" => PkgAction::Remove,
+            _ => return None,
+        };
+        let (name, versions) = rest.split_once(" (")?;
+        let versions = versions.strip_suffix(')')?;
+        let (old_version, new_version) = match action {
+            PkgAction::Install => (None, Some(versions.into())),
+            PkgAction::Remove => (Some(versions.into()), None),
+            PkgAction::Reinstall => (Some(versions.into()), Some(versions.into())),
+            PkgAction::Upgrade | PkgAction::Downgrade => {
+                let (old, new) = versions.split_once(" -> ")?;
+                (Some(old.into()), Some(new.into()))
+            }
+        };
+        Some(Self {
+            action,
+            name: name.into(),
+            old_version,
+            new_version,
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Malformed log line {line}: {content:?}")]
+pub struct LogParseError {
+    /// 1-based line number
+    pub line: usize,
+    pub content: String,
+}
+
+impl LogEntry {
+    fn parse(line: &str) -> Option<Self> {
+        let (time, rest) = line.strip_prefix('[')?.split_once("] ")?;
+        let time = LogTime::parse(time)?;
+        let (source, msg) = match rest.strip_prefix('[').and_then(|r| r.split_once("] ")) {
+            Some((source, msg)) if !source.contains(' ') => {
+                let source = match source {
+                    "PACMAN" => LogSource::Pacman,
+                    "ALPM" => LogSource::Alpm,
+                    "ALPM-SCRIPTLET" => LogSource::AlpmScriptlet,
+                    other => LogSource::Other(other.into()),
+                };
+                (source, msg)
+            }
+            _ => (LogSource::Untagged, rest),
+        };
+        let event = if let Some(cmd) = msg.strip_prefix("Running '") {
+            LogEvent::Command(cmd.strip_suffix('\'').unwrap_or(cmd).into())
+        } else if let Some(warning) = msg.strip_prefix("warning: ") {
+            LogEvent::Warning(warning.into())
+        } else if let Some(error) = msg.strip_prefix("error: ") {
+            LogEvent::Error(error.into())
+        } else if source == LogSource::AlpmScriptlet {
+            LogEvent::Message(msg.into())
+        } else {
+            match msg {
+                "transaction started" => LogEvent::TransactionStarted,
+                "transaction completed" => LogEvent::TransactionCompleted,
+                "transaction failed" => LogEvent::TransactionFailed,
+                "transaction interrupted" => LogEvent::TransactionInterrupted,
+                _ => PkgChange::parse(msg)
+                    .map_or_else(|| LogEvent::Message(msg.into()), LogEvent::Package),
+            }
+        };
+        Some(Self {
+            time,
+            source,
+            event,
+        })
+    }
+}
+
+/// Parse the contents of a pacman log
+///
+/// Empty lines are skipped. Lines that aren't log entries are yielded as errors,
+/// so the caller can decide whether to bail out or ignore them
+/// (a crash can leave a truncated line behind, for example).
+pub fn parse(src: &str) -> impl Iterator<Item = Result<LogEntry, LogParseError>> {
+    src.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| {
+            LogEntry::parse(line).ok_or_else(|| LogParseError {
+                line: i + 1,
+                content: line.to_owned(),
+            })
+        })
+}
+
+/// Read and parse the log at `path`, skipping malformed lines
+pub fn read_log(path: impl AsRef<Path>) -> std::io::Result<Vec<LogEntry>> {
+    let bytes = std::fs::read(path)?;
+    Ok(parse(&String::from_utf8_lossy(&bytes))
+        .filter_map(Result::ok)
+        .collect())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionOutcome {
+    Completed,
+    Failed,
+    Interrupted,
+    /// No end marker was logged, e.g. because pacman was killed,
+    /// or the log predates transaction markers
+    Unknown,
+}
+
+/// A group of package changes performed together
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    /// The pacman command line that started the transaction, if logged
+    pub command: Option<SmolStr>,
+    pub started: LogTime,
+    pub ended: Option<LogTime>,
+    pub outcome: TransactionOutcome,
+    pub changes: Vec<PkgChange>,
+    pub warnings: Vec<SmolStr>,
+    /// Output of install scriptlets and hooks
+    pub scriptlet_output: Vec<SmolStr>,
+}
+
+impl Transaction {
+    fn new(command: Option<SmolStr>, started: LogTime) -> Self {
+        Self {
+            command,
+            started,
+            ended: None,
+            outcome: TransactionOutcome::Unknown,
+            changes: Vec::new(),
+            warnings: Vec::new(),
+            scriptlet_output: Vec::new(),
+        }
+    }
+}
+
+/// Group log entries into transactions
+///
+/// Transactions are delimited by the `transaction started` and
+/// `transaction completed/failed/interrupted` markers.
+/// Package changes logged outside of markers (old logs) are grouped by the
+/// preceding command instead.
+/// Transactions without any package changes are omitted.
+#[must_use]
+pub fn transactions(entries: &[LogEntry]) -> Vec<Transaction> {
+    let mut transactions = Vec::new();
+    let mut command: Option<SmolStr> = None;
+    let mut current: Option<Transaction> = None;
+    // Whether `current` was opened with an explicit start marker
+    let mut explicit = false;
+    let finish = |tr: Option<Transaction>, transactions: &mut Vec<Transaction>| {
+        if let Some(tr) = tr
+            && !tr.changes.is_empty()
+        {
+            transactions.push(tr);
+        }
+    };
+    for entry in entries {
+        match &entry.event {
+            LogEvent::Command(cmd) => {
+                if !explicit {
+                    finish(current.take(), &mut transactions);
+                }
+                command = Some(cmd.clone());
+            }
+            LogEvent::TransactionStarted => {
+                finish(current.take(), &mut transactions);
+                current = Some(Transaction::new(command.clone(), entry.time));
+                explicit = true;
+            }
+            LogEvent::TransactionCompleted
+            | LogEvent::TransactionFailed
+            | LogEvent::TransactionInterrupted => {
+                if let Some(tr) = &mut current {
+                    tr.ended = Some(entry.time);
+                    tr.outcome = match entry.event {
+                        LogEvent::TransactionCompleted => TransactionOutcome::Completed,
+                        LogEvent::TransactionFailed => TransactionOutcome::Failed,
+                        _ => TransactionOutcome::Interrupted,
+                    };
+                }
+                finish(current.take(), &mut transactions);
+                command = None;
+                explicit = false;
+            }
+            LogEvent::Package(change) => {
+                current
+                    .get_or_insert_with(|| Transaction::new(command.clone(), entry.time))
+                    .changes
+                    .push(change.clone());
+            }
+            LogEvent::Warning(msg) => {
+                if let Some(tr) = &mut current {
+                    tr.warnings.push(msg.clone());
+                }
+            }
+            LogEvent::Message(msg) if entry.source == LogSource::AlpmScriptlet => {
+                if let Some(tr) = &mut current {
+                    tr.scriptlet_output.push(msg.clone());
+                }
+            }
+            LogEvent::Error(_) | LogEvent::Message(_) => {}
+        }
+    }
+    finish(current.take(), &mut transactions);
+    transactions
+}
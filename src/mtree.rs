@@ -0,0 +1,244 @@
+//! Parsing of package `mtree` files, which record the metadata and checksums of every file
+
+use {
+    crate::{PkgDesc, compress},
+    smol_str::SmolStr,
+    std::{collections::BTreeMap, fmt, io::Read, os::unix::fs::MetadataExt as _, path::Path},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Dir,
+    Link,
+    /// Devices, fifos and the like
+    Other,
+}
+
+/// A file recorded in an `mtree` file
+#[derive(Debug, Clone)]
+pub struct MtreeEntry {
+    /// Path relative to the root, without leading `./`
+    pub path: Box<str>,
+    pub kind: EntryKind,
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub size: Option<u64>,
+    /// Modification time in seconds
+    pub time: Option<u64>,
+    /// Hex encoded MD5 checksum
+    pub md5: Option<SmolStr>,
+    /// Hex encoded SHA-256 checksum
+    pub sha256: Option<SmolStr>,
+    /// Target of a symlink
+    pub link: Option<Box<str>>,
+}
+
+impl MtreeEntry {
+    /// Whether this is one of the package metadata files, like `.PKGINFO`
+    #[must_use]
+    pub fn is_metadata(&self) -> bool {
+        !self.path.contains('/') && self.path.starts_with('.')
+    }
+    /// Compare this entry with the file at its path under `root`, like `pacman -Qkk`
+    ///
+    /// The type, permissions, size, symlink target and checksum (SHA-256, or MD5
+    /// for old packages) are compared, as far as the entry records them.
+    /// The checksum is only computed if everything else matches.
+    pub fn compare(&self, root: impl AsRef<Path>) -> std::io::Result<Vec<Mismatch>> {
+        let path = root.as_ref().join(&*self.path);
+        let meta = path.symlink_metadata()?;
+        let file_type = meta.file_type();
+        let kind = if file_type.is_file() {
+            EntryKind::File
+        } else if file_type.is_dir() {
+            EntryKind::Dir
+        } else if file_type.is_symlink() {
+            EntryKind::Link
+        } else {
+            EntryKind::Other
+        };
+        if kind != self.kind {
+            return Ok(vec![Mismatch::Type]);
+        }
+        let mut mismatches = Vec::new();
+        let mode = meta.mode() & 0o7777;
+        if let Some(expected) = self.mode
+            && kind != EntryKind::Link
+            && expected != mode
+        {
+            mismatches.push(Mismatch::Mode {
+                expected,
+                actual: mode,
+            });
+        }
+        if let Some(link) = &self.link
+            && kind == EntryKind::Link
+            && std::fs::read_link(&path)?.as_os_str() != &**link
+        {
+            mismatches.push(Mismatch::Link);
+        }
+        if kind != EntryKind::File {
+            return Ok(mismatches);
+        }
+        if let Some(expected) = self.size
+            && expected != meta.len()
+        {
+            mismatches.push(Mismatch::Size {
+                expected,
+                actual: meta.len(),
+            });
+        }
+        if mismatches.is_empty() {
+            let checksum_ok = match (&self.sha256, &self.md5) {
+                (Some(sha256), _) => crate::cache::sha256_hex(std::fs::File::open(&path)?)?
+                    .eq_ignore_ascii_case(sha256),
+                (None, Some(md5)) => {
+                    crate::md5_hex(std::fs::File::open(&path)?)?.eq_ignore_ascii_case(md5)
+                }
+                (None, None) => true,
+            };
+            if !checksum_ok {
+                mismatches.push(Mismatch::Checksum);
+            }
+        }
+        Ok(mismatches)
+    }
+}
+
+/// A difference between an [`MtreeEntry`] and the file on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mismatch {
+    /// A file is a directory, a symlink is a file, and the like
+    Type,
+    Mode {
+        expected: u32,
+        actual: u32,
+    },
+    Size {
+        expected: u64,
+        actual: u64,
+    },
+    /// A symlink points somewhere else
+    Link,
+    Checksum,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Type => "File type mismatch",
+            Self::Mode { .. } => "Permissions mismatch",
+            Self::Size { .. } => "Size mismatch",
+            Self::Link => "Symlink path mismatch",
+            Self::Checksum => "Checksum mismatch",
+        })
+    }
+}
+
+/// Parse the (uncompressed) contents of an `mtree` file
+#[must_use]
+pub fn parse(src: &str) -> Vec<MtreeEntry> {
+    let mut defaults: BTreeMap<&str, &str> = BTreeMap::new();
+    let mut entries = Vec::new();
+    for line in src.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut words = line.split_ascii_whitespace();
+        let Some(first) = words.next() else {
+            continue;
+        };
+        match first {
+            "/set" => {
+                defaults.extend(words.filter_map(|word| word.split_once('=')));
+                continue;
+            }
+            "/unset" => {
+                for key in words {
+                    if key == "all" {
+                        defaults.clear();
+                    } else {
+                        defaults.remove(key);
+                    }
+                }
+                continue;
+            }
+            _ => {}
+        }
+        let mut keywords = defaults.clone();
+        keywords.extend(words.filter_map(|word| word.split_once('=')));
+        let path = unescape(first);
+        let path = path.strip_prefix("./").unwrap_or(&path);
+        entries.push(MtreeEntry {
+            path: path.into(),
+            kind: match keywords.get("type").copied() {
+                None | Some("file") => EntryKind::File,
+                Some("dir") => EntryKind::Dir,
+                Some("link") => EntryKind::Link,
+                Some(_) => EntryKind::Other,
+            },
+            mode: keywords
+                .get("mode")
+                .and_then(|mode| u32::from_str_radix(mode, 8).ok()),
+            uid: keywords.get("uid").and_then(|v| v.parse().ok()),
+            gid: keywords.get("gid").and_then(|v| v.parse().ok()),
+            size: keywords.get("size").and_then(|v| v.parse().ok()),
+            time: keywords
+                .get("time")
+                .and_then(|time| time.split('.').next()?.parse().ok()),
+            md5: keywords.get("md5digest").map(|&v| v.into()),
+            sha256: keywords.get("sha256digest").map(|&v| v.into()),
+            link: keywords.get("link").map(|link| unescape(link).into()),
+        });
+    }
+    entries
+}
+
+/// Read a (possibly compressed) `mtree` file
+pub fn read(reader: impl Read) -> std::io::Result<Vec<MtreeEntry>> {
+    let mut src = String::new();
+    compress::decompress(reader)?.read_to_string(&mut src)?;
+    Ok(parse(&src))
+}
+
+/// Read the `mtree` file of an installed package, under the database path `db_path`
+///
+/// Returns `None` if the package has no `mtree` file, like packages installed
+/// by very old versions of pacman.
+pub fn read_local(
+    db_path: impl AsRef<Path>,
+    pkg: &PkgDesc,
+) -> std::io::Result<Option<Vec<MtreeEntry>>> {
+    let path = db_path
+        .as_ref()
+        .join(format!("local/{}-{}/mtree", pkg.name, pkg.version));
+    match std::fs::File::open(path) {
+        Ok(file) => read(file).map(Some),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Decode the `\ooo` octal escapes used for special characters
+fn unescape(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\'
+            && let Some(octal) = bytes.get(i + 1..i + 4)
+            && let Ok(octal) = std::str::from_utf8(octal)
+            && let Ok(byte) = u8::from_str_radix(octal, 8)
+        {
+            out.push(byte);
+            i += 4;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
@@ -0,0 +1,51 @@
+//! Async versions of the database readers
+//!
+//! Reading and decompressing happens on tokio's blocking thread pool,
+//! so the executor is never blocked.
+
+use {
+    crate::{DbReadError, Pkg},
+    std::path::PathBuf,
+};
+
+/// Run `f` on the blocking thread pool, propagating panics
+///
+/// A cancelled task, like when the runtime shuts down, is reported as an io error.
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, DbReadError> + Send + 'static,
+) -> Result<T, DbReadError> {
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => Err(std::io::Error::other(e).into()),
+    }
+}
+
+/// Async version of [`crate::read_local_db`]
+pub async fn read_local_db() -> Result<Vec<Pkg>, DbReadError> {
+    blocking(crate::read_local_db).await
+}
+
+/// Async version of [`crate::read_local_db_at`]
+pub async fn read_local_db_at(db_path: impl Into<PathBuf>) -> Result<Vec<Pkg>, DbReadError> {
+    let db_path = db_path.into();
+    blocking(move || crate::read_local_db_at(db_path)).await
+}
+
+/// Async version of [`crate::read_syncdb`]
+pub async fn read_syncdb(name: &str) -> Result<Vec<Pkg>, DbReadError> {
+    let name = name.to_owned();
+    blocking(move || crate::read_syncdb(&name)).await
+}
+
+/// Async version of [`crate::read_syncdb_file`]
+pub async fn read_syncdb_file(path: impl Into<PathBuf>) -> Result<Vec<Pkg>, DbReadError> {
+    let path = path.into();
+    blocking(move || crate::read_syncdb_file(path)).await
+}
+
+/// Async version of [`crate::read_syncdb_bytes`], taking ownership of the bytes
+pub async fn read_syncdb_bytes(bytes: impl Into<Vec<u8>>) -> Result<Vec<Pkg>, DbReadError> {
+    let bytes = bytes.into();
+    blocking(move || crate::read_syncdb_bytes(&bytes)).await
+}
@@ -0,0 +1,96 @@
+//! Reading package archives (`.pkg.tar.*`)
+
+use {
+    crate::{Backup, Pkg, PkgDesc, compress},
+    smol_str::SmolStr,
+    std::{io::Read, path::Path},
+    tar::Archive,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum PkgReadError {
+    #[error("Io error")]
+    Io(#[from] std::io::Error),
+    #[error("Package has no .PKGINFO")]
+    MissingPkgInfo,
+    #[error("Package has an invalid .PKGINFO")]
+    InvalidPkgInfo,
+}
+
+/// The metadata of a package archive
+#[derive(Debug)]
+pub struct PkgArchive {
+    /// Package description, with the file list and backup files.
+    /// Backup checksums are empty, as they are only known once installed.
+    pub pkg: Pkg,
+    /// Contents of `.MTREE` (gzip compressed)
+    pub mtree: Option<Vec<u8>>,
+    /// Contents of `.INSTALL`
+    pub install: Option<Vec<u8>>,
+}
+
+/// Read the metadata of a package archive
+///
+/// Supports gzip, zstd, xz compressed and uncompressed packages.
+pub fn read_package(path: impl AsRef<Path>) -> Result<PkgArchive, PkgReadError> {
+    read_package_from(std::fs::File::open(path)?)
+}
+
+/// Read the metadata of a package archive from `reader`
+pub fn read_package_from(reader: impl Read) -> Result<PkgArchive, PkgReadError> {
+    let mut ar = Archive::new(compress::decompress(reader)?);
+    let mut pkginfo = None;
+    let mut mtree = None;
+    let mut install = None;
+    let mut files = Vec::new();
+    for en in ar.entries()? {
+        let mut en = en?;
+        let path = en.path()?.to_string_lossy().into_owned();
+        match path.as_str() {
+            ".PKGINFO" => {
+                let mut s = String::new();
+                en.read_to_string(&mut s)?;
+                pkginfo = Some(s);
+            }
+            ".MTREE" => {
+                let mut buf = Vec::new();
+                en.read_to_end(&mut buf)?;
+                mtree = Some(buf);
+            }
+            ".INSTALL" => {
+                let mut buf = Vec::new();
+                en.read_to_end(&mut buf)?;
+                install = Some(buf);
+            }
+            _ if path.starts_with('.') => {}
+            _ => {
+                let mut path = path;
+                if en.header().entry_type().is_dir() && !path.ends_with('/') {
+                    path.push('/');
+                }
+                files.push(path.into_boxed_str());
+            }
+        }
+    }
+    let pkginfo = pkginfo.ok_or(PkgReadError::MissingPkgInfo)?;
+    let mut desc = PkgDesc::parse_pkginfo(&pkginfo).ok_or(PkgReadError::InvalidPkgInfo)?;
+    desc.install_script = install.is_some();
+    files.sort();
+    let backup = pkginfo
+        .lines()
+        .filter_map(|line| line.strip_prefix("backup = "))
+        .map(|path| Backup {
+            path: path.into(),
+            md5: SmolStr::default(),
+        })
+        .collect();
+    Ok(PkgArchive {
+        pkg: Pkg {
+            desc,
+            files,
+            backup,
+        },
+        mtree,
+        install,
+    })
+}
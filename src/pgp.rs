@@ -0,0 +1,1100 @@
+//! `OpenPGP` signature verification of databases and packages against a local keyring
+//!
+//! Only the subset of `OpenPGP` used by pacman is supported: v4 keys and signatures
+//! made with RSA or Ed25519 keys.
+//! Key validity follows the classic `GnuPG` trust model with a single level of introducers,
+//! which is how the pacman keyring is set up: keys listed in a `*-trusted` file act as
+//! introducers, and other keys become valid through their certifications.
+
+use {
+    crate::PkgDesc,
+    base64::Engine as _,
+    ed25519_dalek::Verifier as _,
+    rsa::{BigUint, Pkcs1v15Sign, RsaPublicKey},
+    sha1::Sha1,
+    sha2::{Digest, Sha224, Sha256, Sha384, Sha512},
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        fmt,
+        io::Read,
+        path::Path,
+        time::{SystemTime, UNIX_EPOCH},
+    },
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum PgpError {
+    #[error("Io error")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid base64")]
+    Base64(#[from] base64::DecodeError),
+    #[error("Malformed OpenPGP data: {0}")]
+    Malformed(&'static str),
+    #[error("No signature found")]
+    NoSignature,
+}
+
+/// A v4 key fingerprint
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fingerprint(pub [u8; 20]);
+
+impl Fingerprint {
+    #[must_use]
+    pub fn key_id(&self) -> KeyId {
+        let mut id = [0; 8];
+        id.copy_from_slice(&self.0[12..]);
+        KeyId(id)
+    }
+    fn parse_hex(src: &str) -> Option<Self> {
+        let mut fpr = [0; 20];
+        if src.len() != 40 {
+            return None;
+        }
+        for (byte, chunk) in fpr.iter_mut().zip(src.as_bytes().chunks(2)) {
+            *byte = u8::from_str_radix(std::str::from_utf8(chunk).ok()?, 16).ok()?;
+        }
+        Some(Self(fpr))
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02X}"))
+    }
+}
+
+impl fmt::Debug for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Fingerprint({self})")
+    }
+}
+
+/// The low 64 bits of a fingerprint
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct KeyId(pub [u8; 8]);
+
+impl fmt::Display for KeyId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02X}"))
+    }
+}
+
+impl fmt::Debug for KeyId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "KeyId({self})")
+    }
+}
+
+/// How much a key is trusted to be genuine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Trust {
+    /// Revoked, or explicitly distrusted
+    Never,
+    Unknown,
+    Marginal,
+    Full,
+    Ultimate,
+}
+
+/// Outcome of checking a single signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigStatus {
+    Valid,
+    /// The signature doesn't match the data
+    Bad,
+    /// The signing key isn't in the keyring
+    UnknownKey,
+    /// The signature is good, but the signing key has expired
+    KeyExpired,
+    /// The signature is good, but the signing key has been revoked
+    KeyRevoked,
+    /// The signature is good, but has expired
+    SigExpired,
+    /// The signature is good, but the signing key isn't allowed to sign,
+    /// by its key flags or a missing back-signature
+    UnusableKey,
+    /// The signature uses an algorithm that isn't supported
+    Unsupported,
+}
+
+#[derive(Debug, Clone)]
+pub struct SigCheck {
+    /// Key ID of the signing key (may be a subkey)
+    pub key_id: KeyId,
+    /// Fingerprint of the signing key's primary key, if the key is known
+    pub primary_fingerprint: Option<Fingerprint>,
+    /// First user ID of the signing key, if the key is known
+    pub user_id: Option<String>,
+    /// Signature creation time, in seconds since the epoch
+    pub created: u32,
+    pub status: SigStatus,
+    /// Validity of the signing key, whether it is genuine
+    pub validity: Trust,
+    /// Owner trust of the signing key, how far its certifications are trusted
+    pub owner_trust: Trust,
+}
+
+impl SigCheck {
+    /// Whether the signature is good and made by a fully valid key, like pacman requires
+    #[must_use]
+    pub fn is_trusted(&self) -> bool {
+        self.status == SigStatus::Valid && self.validity >= Trust::Full
+    }
+}
+
+/// A set of public keys along with their trust
+#[derive(Default)]
+pub struct Keyring {
+    keys: Vec<Key>,
+    owner_trust: BTreeMap<Fingerprint, Trust>,
+    revoked: BTreeSet<Fingerprint>,
+    /// Computed validity of each primary key, by index into `keys`
+    validity: Vec<Trust>,
+}
+
+impl Keyring {
+    /// Load a keyring directory, like an exported pacman keyring
+    ///
+    /// Recognized files:
+    /// - `*.gpg`, `*.pgp` and `*.asc`: public keys, binary or ASCII armored
+    /// - `*-trusted`: `FINGERPRINT:LEVEL:` lines in `gpg --export-ownertrust` format
+    /// - `*-revoked`: one fingerprint per line
+    pub fn load_dir(path: impl AsRef<Path>) -> Result<Self, PgpError> {
+        let mut keyring = Self::default();
+        let mut paths = std::fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.sort();
+        for path in paths {
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let ext = path.extension().and_then(|ext| ext.to_str());
+            if matches!(ext, Some("gpg" | "pgp" | "asc")) {
+                keyring.add_keys(&std::fs::read(&path)?)?;
+            } else if file_name.ends_with("-trusted") {
+                keyring.add_owner_trust(&std::fs::read_to_string(&path)?);
+            } else if file_name.ends_with("-revoked") {
+                keyring.add_revoked(&std::fs::read_to_string(&path)?);
+            }
+        }
+        Ok(keyring)
+    }
+    /// Add public keys, binary or ASCII armored
+    pub fn add_keys(&mut self, data: &[u8]) -> Result<(), PgpError> {
+        let data = dearmor(data)?;
+        let mut packets = Packets::new(&data).peekable();
+        while let Some(packet) = packets.next() {
+            let (tag, body) = packet?;
+            if tag == TAG_PUBLIC_KEY {
+                let mut key = Key::new(KeyMaterial::parse(body)?);
+                while let Some(Ok((tag, body))) = packets
+                    .next_if(|packet| matches!(packet, Ok((tag, _)) if *tag != TAG_PUBLIC_KEY))
+                {
+                    key.add_packet(tag, body);
+                }
+                key.check_self_sigs();
+                self.keys.push(key);
+            }
+        }
+        self.compute_validity();
+        Ok(())
+    }
+    /// Add owner trust, in `gpg --export-ownertrust` format
+    pub fn add_owner_trust(&mut self, src: &str) {
+        for line in src.lines().filter(|line| !line.starts_with('#')) {
+            let mut fields = line.split(':');
+            let (Some(fpr), Some(level)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            let Some(fpr) = Fingerprint::parse_hex(fpr) else {
+                continue;
+            };
+            let trust = match level {
+                "3" => Trust::Never,
+                "4" => Trust::Marginal,
+                "5" => Trust::Full,
+                "6" => Trust::Ultimate,
+                _ => Trust::Unknown,
+            };
+            self.owner_trust.insert(fpr, trust);
+        }
+        self.compute_validity();
+    }
+    /// Mark keys as revoked, given one fingerprint per line
+    pub fn add_revoked(&mut self, src: &str) {
+        self.revoked.extend(
+            src.lines()
+                .filter_map(|line| Fingerprint::parse_hex(line.trim())),
+        );
+        self.compute_validity();
+    }
+    /// Fingerprints of all primary keys in the keyring
+    pub fn fingerprints(&self) -> impl Iterator<Item = Fingerprint> {
+        self.keys.iter().map(|key| key.primary.fingerprint)
+    }
+    /// Verify a detached signature (binary or ASCII armored) over `data`
+    ///
+    /// Returns a result for every signature packet found.
+    pub fn verify(&self, mut data: impl Read, sig: &[u8]) -> Result<Vec<SigCheck>, PgpError> {
+        let sig = dearmor(sig)?;
+        let mut sigs = Vec::new();
+        for packet in Packets::new(&sig) {
+            let (tag, body) = packet?;
+            if tag == TAG_SIGNATURE {
+                sigs.push(Signature::parse(body, false)?);
+            }
+        }
+        if sigs.is_empty() {
+            return Err(PgpError::NoSignature);
+        }
+        let mut hashers: Vec<Option<Hasher>> =
+            sigs.iter().map(|sig| Hasher::new(sig.hash_alg)).collect();
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let n = data.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            for hasher in hashers.iter_mut().flatten() {
+                hasher.update(&buf[..n]);
+            }
+        }
+        Ok(sigs
+            .iter()
+            .zip(hashers)
+            .map(|(sig, hasher)| self.check(sig, hasher))
+            .collect())
+    }
+    /// Verify `path` against its detached signature at `{path}.sig`
+    ///
+    /// Works for both sync databases (`core.db`) and package files.
+    pub fn verify_file(&self, path: impl AsRef<Path>) -> Result<Vec<SigCheck>, PgpError> {
+        let path = path.as_ref();
+        let mut sig_path = path.as_os_str().to_owned();
+        sig_path.push(".sig");
+        let sig = std::fs::read(sig_path)?;
+        self.verify(std::fs::File::open(path)?, &sig)
+    }
+    /// Verify a package file against the base64 `%PGPSIG%` of its database entry
+    pub fn verify_pgpsig(
+        &self,
+        pkg_file: impl Read,
+        desc: &PkgDesc,
+    ) -> Result<Vec<SigCheck>, PgpError> {
+        let Some(pgpsig) = &desc.pgpsig else {
+            return Err(PgpError::NoSignature);
+        };
+        let sig = base64::engine::general_purpose::STANDARD.decode(pgpsig.as_bytes())?;
+        self.verify(pkg_file, &sig)
+    }
+    fn check(&self, sig: &Signature, hasher: Option<Hasher>) -> SigCheck {
+        let issuer = sig.issuer();
+        let found = self.find_signing_key(sig);
+        let mut check = SigCheck {
+            key_id: issuer.unwrap_or(KeyId([0; 8])),
+            primary_fingerprint: None,
+            user_id: None,
+            created: sig.created,
+            status: SigStatus::UnknownKey,
+            validity: Trust::Unknown,
+            owner_trust: Trust::Unknown,
+        };
+        let Some((key_idx, material, usable)) = found else {
+            return check;
+        };
+        let key = &self.keys[key_idx];
+        check.key_id = material.fingerprint.key_id();
+        check.primary_fingerprint = Some(key.primary.fingerprint);
+        check.user_id = key
+            .uids
+            .first()
+            .map(|uid| String::from_utf8_lossy(&uid.id).into_owned());
+        check.validity = self.validity[key_idx];
+        check.owner_trust = self.owner_trust(key);
+        let Some(hasher) = hasher else {
+            check.status = SigStatus::Unsupported;
+            return check;
+        };
+        let now = now();
+        // A subkey expires with its primary key
+        let expired = |material: &KeyMaterial| material.expires.is_some_and(|exp| exp <= now);
+        check.status = match (sig.sig_type, material.verify(sig, hasher)) {
+            (SIG_BINARY, Some(true)) if self.is_revoked(key) => SigStatus::KeyRevoked,
+            (SIG_BINARY, Some(true)) if expired(material) || expired(&key.primary) => {
+                SigStatus::KeyExpired
+            }
+            (SIG_BINARY, Some(true)) if sig.expires().is_some_and(|exp| exp <= now) => {
+                SigStatus::SigExpired
+            }
+            (SIG_BINARY, Some(true)) if !usable => SigStatus::UnusableKey,
+            (SIG_BINARY, Some(true)) => SigStatus::Valid,
+            (SIG_BINARY, Some(false)) => SigStatus::Bad,
+            _ => SigStatus::Unsupported,
+        };
+        check
+    }
+    /// Find the (sub)key that made `sig`, along with the index of its primary key
+    /// and whether it may sign
+    fn find_signing_key(&self, sig: &Signature) -> Option<(usize, &KeyMaterial, bool)> {
+        self.keys.iter().enumerate().find_map(|(idx, key)| {
+            key.signing_keys()
+                .find(|(material, _)| sig.is_issued_by(material))
+                .map(|(material, usable)| (idx, material, usable))
+        })
+    }
+    fn is_revoked(&self, key: &Key) -> bool {
+        key.revoked || self.revoked.contains(&key.primary.fingerprint)
+    }
+    fn owner_trust(&self, key: &Key) -> Trust {
+        if self.is_revoked(key) {
+            return Trust::Never;
+        }
+        self.owner_trust
+            .get(&key.primary.fingerprint)
+            .copied()
+            .unwrap_or(Trust::Unknown)
+    }
+    fn compute_validity(&mut self) {
+        let owner_trust = |key: &Key| self.owner_trust(key);
+        // Keys with owner trust are the introducers, and valid by themselves
+        let introducers: Vec<(&Key, Trust)> = self
+            .keys
+            .iter()
+            .map(|key| (key, owner_trust(key)))
+            .filter(|(_, trust)| *trust >= Trust::Marginal)
+            .collect();
+        let validity = self
+            .keys
+            .iter()
+            .map(|key| {
+                match owner_trust(key) {
+                    Trust::Unknown => {}
+                    // Introducers are signed by the local master key,
+                    // which makes them fully valid
+                    Trust::Marginal => return Trust::Full,
+                    trust => return trust,
+                }
+                let (mut marginal, mut full) = (0, 0);
+                for (introducer, trust) in &introducers {
+                    if key.is_certified_by(&introducer.primary) {
+                        if *trust >= Trust::Full {
+                            full += 1;
+                        } else {
+                            marginal += 1;
+                        }
+                    }
+                }
+                match (full, marginal) {
+                    (1.., _) | (_, 3..) => Trust::Full,
+                    (_, 1..) => Trust::Marginal,
+                    _ => Trust::Unknown,
+                }
+            })
+            .collect();
+        self.validity = validity;
+    }
+}
+
+const TAG_SIGNATURE: u8 = 2;
+const TAG_PUBLIC_KEY: u8 = 6;
+const TAG_USER_ID: u8 = 13;
+const TAG_PUBLIC_SUBKEY: u8 = 14;
+const TAG_USER_ATTRIBUTE: u8 = 17;
+
+const SIG_BINARY: u8 = 0x00;
+const SIG_SUBKEY_BINDING: u8 = 0x18;
+const SIG_PRIMARY_BINDING: u8 = 0x19;
+const SIG_DIRECT_KEY: u8 = 0x1F;
+const SIG_KEY_REVOCATION: u8 = 0x20;
+const SIG_SUBKEY_REVOCATION: u8 = 0x28;
+const SIG_CERT_REVOCATION: u8 = 0x30;
+
+const KEY_FLAG_CERTIFY: u8 = 0x01;
+const KEY_FLAG_SIGN: u8 = 0x02;
+
+fn now() -> u32 {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |dur| dur.as_secs());
+    u32::try_from(secs).unwrap_or(u32::MAX)
+}
+
+/// Decode ASCII armor if present, otherwise return the data as is
+fn dearmor(data: &[u8]) -> Result<Vec<u8>, PgpError> {
+    if !data.starts_with(b"-----BEGIN PGP") {
+        return Ok(data.to_vec());
+    }
+    let text = std::str::from_utf8(data).map_err(|_| PgpError::Malformed("armor is not utf-8"))?;
+    let mut out = Vec::new();
+    let mut lines = text.lines().map(str::trim_end);
+    while let Some(line) = lines.next() {
+        if !line.starts_with("-----BEGIN PGP") {
+            continue;
+        }
+        // Armor headers end at the first empty line
+        for line in lines.by_ref() {
+            if line.is_empty() {
+                break;
+            }
+        }
+        let mut b64 = String::new();
+        for line in lines.by_ref() {
+            if line.starts_with("-----END PGP") || (line.starts_with('=') && line.len() == 5) {
+                break;
+            }
+            b64.push_str(line);
+        }
+        out.extend(base64::engine::general_purpose::STANDARD.decode(b64)?);
+    }
+    Ok(out)
+}
+
+/// Iterator over the `(tag, body)` packets of binary `OpenPGP` data
+struct Packets<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Packets<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+    fn next_packet(&mut self) -> Result<(u8, &'a [u8]), PgpError> {
+        let malformed = || PgpError::Malformed("truncated packet");
+        let (&first, rest) = self.data.split_first().ok_or_else(malformed)?;
+        if first & 0x80 == 0 {
+            return Err(PgpError::Malformed("invalid packet header"));
+        }
+        let (tag, len, rest) = if first & 0x40 == 0 {
+            // Old format
+            let tag = (first >> 2) & 0xF;
+            let (len, rest) = match first & 0x3 {
+                0 => (
+                    usize::from(*rest.first().ok_or_else(malformed)?),
+                    &rest[1..],
+                ),
+                1 => (usize::from(be_u16(rest).ok_or_else(malformed)?), &rest[2..]),
+                2 => (be_u32(rest).ok_or_else(malformed)? as usize, &rest[4..]),
+                _ => (rest.len(), rest),
+            };
+            (tag, len, rest)
+        } else {
+            let tag = first & 0x3F;
+            let (&len0, rest) = rest.split_first().ok_or_else(malformed)?;
+            let (len, rest) = match len0 {
+                0..192 => (usize::from(len0), rest),
+                192..224 => {
+                    let (&len1, rest) = rest.split_first().ok_or_else(malformed)?;
+                    (
+                        ((usize::from(len0) - 192) << 8) + usize::from(len1) + 192,
+                        rest,
+                    )
+                }
+                255 => (be_u32(rest).ok_or_else(malformed)? as usize, &rest[4..]),
+                _ => {
+                    return Err(PgpError::Malformed(
+                        "partial body lengths are not supported",
+                    ));
+                }
+            };
+            (tag, len, rest)
+        };
+        if rest.len() < len {
+            return Err(malformed());
+        }
+        let (body, rest) = rest.split_at(len);
+        self.data = rest;
+        Ok((tag, body))
+    }
+}
+
+impl<'a> Iterator for Packets<'a> {
+    type Item = Result<(u8, &'a [u8]), PgpError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let packet = self.next_packet();
+        if packet.is_err() {
+            self.data = &[];
+        }
+        Some(packet)
+    }
+}
+
+fn be_u16(data: &[u8]) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(..2)?.try_into().ok()?))
+}
+
+fn be_u32(data: &[u8]) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(..4)?.try_into().ok()?))
+}
+
+/// Reads a multiprecision integer, returning its big-endian bytes
+fn read_mpi<'a>(data: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let bits = usize::from(be_u16(data)?);
+    let len = bits.div_ceil(8);
+    let mpi = data.get(2..2 + len)?;
+    *data = &data[2 + len..];
+    Some(mpi)
+}
+
+/// Left-pad big-endian bytes to `N` bytes
+fn pad<const N: usize>(bytes: &[u8]) -> Option<[u8; N]> {
+    let mut out = [0; N];
+    out.get_mut(N.checked_sub(bytes.len())?..)?
+        .copy_from_slice(bytes);
+    Some(out)
+}
+
+enum PublicKey {
+    Rsa(RsaPublicKey),
+    Ed25519(ed25519_dalek::VerifyingKey),
+    Unsupported,
+}
+
+const ED25519_OID: &[u8] = &[0x2B, 0x06, 0x01, 0x04, 0x01, 0xDA, 0x47, 0x0F, 0x01];
+
+/// A primary key or subkey
+struct KeyMaterial {
+    body: Vec<u8>,
+    fingerprint: Fingerprint,
+    created: u32,
+    key: PublicKey,
+    /// Expiration time, from the self-signature or binding signature
+    expires: Option<u32>,
+    /// Key flags, from the self-signature or binding signature
+    flags: Option<u8>,
+}
+
+impl KeyMaterial {
+    fn parse(body: &[u8]) -> Result<Self, PgpError> {
+        let malformed = || PgpError::Malformed("invalid public key packet");
+        if body.first() != Some(&4) {
+            return Err(PgpError::Malformed("only v4 keys are supported"));
+        }
+        let created = be_u32(&body[1..]).ok_or_else(malformed)?;
+        let alg = *body.get(5).ok_or_else(malformed)?;
+        let mut rest = &body[6..];
+        let key = match alg {
+            // RSA (Encrypt or Sign), RSA Sign-Only
+            1 | 3 => {
+                let n = read_mpi(&mut rest).ok_or_else(malformed)?;
+                let e = read_mpi(&mut rest).ok_or_else(malformed)?;
+                RsaPublicKey::new_with_max_size(
+                    BigUint::from_bytes_be(n),
+                    BigUint::from_bytes_be(e),
+                    16384,
+                )
+                .map_or(PublicKey::Unsupported, PublicKey::Rsa)
+            }
+            // EdDSA (legacy)
+            22 => {
+                let oid_len = usize::from(*rest.first().ok_or_else(malformed)?);
+                let oid = rest.get(1..=oid_len).ok_or_else(malformed)?;
+                rest = &rest[1 + oid_len..];
+                let point = read_mpi(&mut rest).ok_or_else(malformed)?;
+                match (oid, point) {
+                    (ED25519_OID, [0x40, point @ ..]) => ed25519_key(point),
+                    _ => PublicKey::Unsupported,
+                }
+            }
+            // Ed25519
+            27 => ed25519_key(rest.get(..32).ok_or_else(malformed)?),
+            _ => PublicKey::Unsupported,
+        };
+        let mut hasher = Sha1::new();
+        hasher.update(key_header(body));
+        hasher.update(body);
+        Ok(Self {
+            body: body.to_vec(),
+            fingerprint: Fingerprint(hasher.finalize().into()),
+            created,
+            key,
+            expires: None,
+            flags: None,
+        })
+    }
+    /// Whether the key flags allow certifying other keys, which is assumed without flags
+    fn can_certify(&self) -> bool {
+        self.flags.is_none_or(|flags| flags & KEY_FLAG_CERTIFY != 0)
+    }
+    /// Whether the key flags allow signing data, which is assumed without flags
+    fn can_sign(&self) -> bool {
+        self.flags.is_none_or(|flags| flags & KEY_FLAG_SIGN != 0)
+    }
+    /// Checks whether `sig` is a valid signature by this key, given the hashed signed data
+    ///
+    /// Returns `None` if the algorithm is not supported.
+    fn verify(&self, sig: &Signature, mut hasher: Hasher) -> Option<bool> {
+        hasher.update(&sig.hashed);
+        hasher.update(&[4, 0xFF]);
+        hasher.update(&u32::try_from(sig.hashed.len()).ok()?.to_be_bytes());
+        let digest = hasher.finalize();
+        if digest.get(..2) != Some(&sig.left16[..]) {
+            return Some(false);
+        }
+        match (&self.key, sig.pk_alg) {
+            (PublicKey::Rsa(key), 1 | 3) => {
+                let [sig_mpi] = &sig.material[..] else {
+                    return Some(false);
+                };
+                let scheme = rsa_scheme(sig.hash_alg)?;
+                let mut padded = vec![0; key_size(key).saturating_sub(sig_mpi.len())];
+                padded.extend_from_slice(sig_mpi);
+                Some(key.verify(scheme, &digest, &padded).is_ok())
+            }
+            (PublicKey::Ed25519(key), 22 | 27) => {
+                let bytes: [u8; 64] = match &sig.material[..] {
+                    [r, s] => {
+                        let (Some(r), Some(s)) = (pad::<32>(r), pad::<32>(s)) else {
+                            return Some(false);
+                        };
+                        let mut bytes = [0; 64];
+                        bytes[..32].copy_from_slice(&r);
+                        bytes[32..].copy_from_slice(&s);
+                        bytes
+                    }
+                    [raw] => match pad::<64>(raw) {
+                        Some(bytes) => bytes,
+                        None => return Some(false),
+                    },
+                    _ => return Some(false),
+                };
+                let sig = ed25519_dalek::Signature::from_bytes(&bytes);
+                Some(key.verify(&digest, &sig).is_ok())
+            }
+            _ => None,
+        }
+    }
+}
+
+fn key_size(key: &RsaPublicKey) -> usize {
+    use rsa::traits::PublicKeyParts as _;
+    key.size()
+}
+
+fn ed25519_key(point: &[u8]) -> PublicKey {
+    point
+        .try_into()
+        .ok()
+        .and_then(|point| ed25519_dalek::VerifyingKey::from_bytes(point).ok())
+        .map_or(PublicKey::Unsupported, PublicKey::Ed25519)
+}
+
+fn rsa_scheme(hash_alg: u8) -> Option<Pkcs1v15Sign> {
+    Some(match hash_alg {
+        2 => Pkcs1v15Sign::new::<Sha1>(),
+        8 => Pkcs1v15Sign::new::<Sha256>(),
+        9 => Pkcs1v15Sign::new::<Sha384>(),
+        10 => Pkcs1v15Sign::new::<Sha512>(),
+        11 => Pkcs1v15Sign::new::<Sha224>(),
+        _ => return None,
+    })
+}
+
+/// The header key packet bodies are prefixed with when hashed,
+/// for fingerprints and key signatures
+fn key_header(body: &[u8]) -> [u8; 3] {
+    let [hi, lo] = u16::try_from(body.len()).unwrap_or(u16::MAX).to_be_bytes();
+    [0x99, hi, lo]
+}
+
+enum Hasher {
+    Sha1(Sha1),
+    Sha224(Sha224),
+    Sha256(Sha256),
+    Sha384(Sha384),
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    fn new(hash_alg: u8) -> Option<Self> {
+        Some(match hash_alg {
+            2 => Self::Sha1(Sha1::new()),
+            8 => Self::Sha256(Sha256::new()),
+            9 => Self::Sha384(Sha384::new()),
+            10 => Self::Sha512(Sha512::new()),
+            11 => Self::Sha224(Sha224::new()),
+            _ => return None,
+        })
+    }
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha1(h) => h.update(data),
+            Self::Sha224(h) => h.update(data),
+            Self::Sha256(h) => h.update(data),
+            Self::Sha384(h) => h.update(data),
+            Self::Sha512(h) => h.update(data),
+        }
+    }
+    fn update_key(&mut self, key: &KeyMaterial) {
+        self.update(&key_header(&key.body));
+        self.update(&key.body);
+    }
+    fn finalize(self) -> Vec<u8> {
+        match self {
+            Self::Sha1(h) => h.finalize().to_vec(),
+            Self::Sha224(h) => h.finalize().to_vec(),
+            Self::Sha256(h) => h.finalize().to_vec(),
+            Self::Sha384(h) => h.finalize().to_vec(),
+            Self::Sha512(h) => h.finalize().to_vec(),
+        }
+    }
+}
+
+struct Signature {
+    sig_type: u8,
+    pk_alg: u8,
+    hash_alg: u8,
+    /// The hashed part of the signature packet, from the version up to the
+    /// end of the hashed subpackets
+    hashed: Vec<u8>,
+    created: u32,
+    /// Signature expiration, in seconds after creation
+    expiration: Option<u32>,
+    /// Key expiration, in seconds after key creation
+    key_expiration: Option<u32>,
+    issuer: Option<KeyId>,
+    issuer_fpr: Option<Fingerprint>,
+    key_flags: Option<u8>,
+    /// An embedded signature, like the back-signature of a signing subkey
+    embedded: Option<Box<Signature>>,
+    left16: [u8; 2],
+    /// MPIs, or raw signature bytes
+    material: Vec<Vec<u8>>,
+}
+
+impl Signature {
+    /// Parse a signature packet body, or an embedded signature if `nested` is set
+    fn parse(body: &[u8], nested: bool) -> Result<Self, PgpError> {
+        let malformed = || PgpError::Malformed("invalid signature packet");
+        if body.first() != Some(&4) {
+            return Err(PgpError::Malformed("only v4 signatures are supported"));
+        }
+        let [_, sig_type, pk_alg, hash_alg, ..] = *body else {
+            return Err(malformed());
+        };
+        let hashed_len = usize::from(be_u16(&body[4..]).ok_or_else(malformed)?);
+        let hashed_end = 6 + hashed_len;
+        let hashed_subpackets = body.get(6..hashed_end).ok_or_else(malformed)?;
+        let unhashed_len = usize::from(be_u16(&body[hashed_end..]).ok_or_else(malformed)?);
+        let unhashed_end = hashed_end + 2 + unhashed_len;
+        let unhashed_subpackets = body
+            .get(hashed_end + 2..unhashed_end)
+            .ok_or_else(malformed)?;
+        let left16 = body
+            .get(unhashed_end..unhashed_end + 2)
+            .ok_or_else(malformed)?
+            .try_into()
+            .map_err(|_| malformed())?;
+        let mut rest = &body[unhashed_end + 2..];
+        let material = match pk_alg {
+            1 | 3 => vec![read_mpi(&mut rest).ok_or_else(malformed)?.to_vec()],
+            22 => vec![
+                read_mpi(&mut rest).ok_or_else(malformed)?.to_vec(),
+                read_mpi(&mut rest).ok_or_else(malformed)?.to_vec(),
+            ],
+            27 => vec![rest.get(..64).ok_or_else(malformed)?.to_vec()],
+            _ => Vec::new(),
+        };
+        let mut sig = Self {
+            sig_type,
+            pk_alg,
+            hash_alg,
+            hashed: body[..hashed_end].to_vec(),
+            created: 0,
+            expiration: None,
+            key_expiration: None,
+            issuer: None,
+            issuer_fpr: None,
+            key_flags: None,
+            embedded: None,
+            left16,
+            material,
+        };
+        sig.read_subpackets(hashed_subpackets, true, nested)?;
+        sig.read_subpackets(unhashed_subpackets, false, nested)?;
+        Ok(sig)
+    }
+    fn read_subpackets(
+        &mut self,
+        mut data: &[u8],
+        hashed: bool,
+        nested: bool,
+    ) -> Result<(), PgpError> {
+        let malformed = || PgpError::Malformed("invalid signature subpacket");
+        while let Some((&len0, rest)) = data.split_first() {
+            let (len, rest) = match len0 {
+                0..192 => (usize::from(len0), rest),
+                192..255 => {
+                    let (&len1, rest) = rest.split_first().ok_or_else(malformed)?;
+                    (
+                        ((usize::from(len0) - 192) << 8) + usize::from(len1) + 192,
+                        rest,
+                    )
+                }
+                255 => (be_u32(rest).ok_or_else(malformed)? as usize, &rest[4..]),
+            };
+            let subpacket = rest.get(..len).ok_or_else(malformed)?;
+            data = &rest[len..];
+            let Some((&kind, content)) = subpacket.split_first() else {
+                continue;
+            };
+            match (kind & 0x7F, hashed) {
+                (2, true) => self.created = be_u32(content).ok_or_else(malformed)?,
+                (3, true) => self.expiration = be_u32(content).filter(|&exp| exp != 0),
+                (9, true) => self.key_expiration = be_u32(content).filter(|&exp| exp != 0),
+                (16, _) => self.issuer = content.try_into().ok().map(KeyId),
+                (27, true) => self.key_flags = content.first().copied(),
+                // Back-signatures, the only embedded signatures, never embed another one
+                (32, _) if nested => {
+                    return Err(PgpError::Malformed("nested embedded signature"));
+                }
+                (32, _) => self.embedded = Signature::parse(content, true).ok().map(Box::new),
+                (33, _) => {
+                    self.issuer_fpr = content
+                        .get(1..)
+                        .and_then(|fpr| fpr.try_into().ok())
+                        .map(Fingerprint);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+    fn issuer(&self) -> Option<KeyId> {
+        self.issuer_fpr.map(|fpr| fpr.key_id()).or(self.issuer)
+    }
+    fn is_issued_by(&self, key: &KeyMaterial) -> bool {
+        match (self.issuer_fpr, self.issuer) {
+            (Some(fpr), _) => fpr == key.fingerprint,
+            (None, Some(id)) => id == key.fingerprint.key_id(),
+            (None, None) => false,
+        }
+    }
+    fn expires(&self) -> Option<u32> {
+        self.expiration.map(|exp| self.created.saturating_add(exp))
+    }
+}
+
+struct UserId {
+    id: Vec<u8>,
+    sigs: Vec<Signature>,
+}
+
+struct Subkey {
+    material: KeyMaterial,
+    sigs: Vec<Signature>,
+    /// Has a valid binding signature from the primary key
+    bound: bool,
+    /// Has a valid back-signature, which signing subkeys need
+    back_signed: bool,
+    revoked: bool,
+}
+
+/// A primary key with its user IDs and subkeys
+struct Key {
+    primary: KeyMaterial,
+    direct_sigs: Vec<Signature>,
+    uids: Vec<UserId>,
+    subkeys: Vec<Subkey>,
+    revoked: bool,
+    /// Where the signatures following the last packet belong
+    sig_target: SigTarget,
+}
+
+enum SigTarget {
+    Primary,
+    UserId,
+    Subkey,
+    /// Signatures on unsupported packets, like user attributes
+    Ignored,
+}
+
+impl Key {
+    fn new(primary: KeyMaterial) -> Self {
+        Self {
+            primary,
+            direct_sigs: Vec::new(),
+            uids: Vec::new(),
+            subkeys: Vec::new(),
+            revoked: false,
+            sig_target: SigTarget::Primary,
+        }
+    }
+    fn add_packet(&mut self, tag: u8, body: &[u8]) {
+        match tag {
+            TAG_SIGNATURE => {
+                // Skip signatures we can't parse, like v3 certifications
+                let Ok(sig) = Signature::parse(body, false) else {
+                    return;
+                };
+                let sigs = match self.sig_target {
+                    SigTarget::Primary => Some(&mut self.direct_sigs),
+                    SigTarget::UserId => self.uids.last_mut().map(|uid| &mut uid.sigs),
+                    SigTarget::Subkey => self.subkeys.last_mut().map(|subkey| &mut subkey.sigs),
+                    SigTarget::Ignored => None,
+                };
+                if let Some(sigs) = sigs {
+                    sigs.push(sig);
+                }
+            }
+            TAG_USER_ID => {
+                self.uids.push(UserId {
+                    id: body.to_vec(),
+                    sigs: Vec::new(),
+                });
+                self.sig_target = SigTarget::UserId;
+            }
+            TAG_PUBLIC_SUBKEY => match KeyMaterial::parse(body) {
+                Ok(material) => {
+                    self.subkeys.push(Subkey {
+                        material,
+                        sigs: Vec::new(),
+                        bound: false,
+                        back_signed: false,
+                        revoked: false,
+                    });
+                    self.sig_target = SigTarget::Subkey;
+                }
+                Err(_) => self.sig_target = SigTarget::Ignored,
+            },
+            TAG_USER_ATTRIBUTE => self.sig_target = SigTarget::Ignored,
+            _ => {}
+        }
+    }
+    /// Verify self-signatures, to find out about revocations, expiration and subkey bindings
+    fn check_self_sigs(&mut self) {
+        let primary = &self.primary;
+        let self_sig_ok = |sig: &Signature, prefix: &dyn Fn(&mut Hasher)| {
+            sig.is_issued_by(primary)
+                && Hasher::new(sig.hash_alg).is_some_and(|mut hasher| {
+                    prefix(&mut hasher);
+                    primary.verify(sig, hasher) == Some(true)
+                })
+        };
+        let hash_primary = |hasher: &mut Hasher| hasher.update_key(primary);
+        let mut newest_self_sig: Option<&Signature> = None;
+        for sig in &self.direct_sigs {
+            if !self_sig_ok(sig, &hash_primary) {
+                continue;
+            }
+            match sig.sig_type {
+                SIG_KEY_REVOCATION => self.revoked = true,
+                SIG_DIRECT_KEY
+                    if newest_self_sig.is_none_or(|newest| sig.created > newest.created) =>
+                {
+                    newest_self_sig = Some(sig);
+                }
+                _ => {}
+            }
+        }
+        for uid in &self.uids {
+            let hash_uid = |hasher: &mut Hasher| {
+                hasher.update_key(primary);
+                hash_uid(hasher, &uid.id);
+            };
+            for sig in &uid.sigs {
+                if (0x10..=0x13).contains(&sig.sig_type)
+                    && newest_self_sig.is_none_or(|newest| sig.created > newest.created)
+                    && self_sig_ok(sig, &hash_uid)
+                {
+                    newest_self_sig = Some(sig);
+                }
+            }
+        }
+        let primary_expires = newest_self_sig
+            .and_then(|sig| sig.key_expiration)
+            .map(|exp| primary.created.saturating_add(exp));
+        let primary_flags = newest_self_sig.and_then(|sig| sig.key_flags);
+        for subkey in &mut self.subkeys {
+            let hash_binding = |hasher: &mut Hasher| {
+                hasher.update_key(primary);
+                hasher.update_key(&subkey.material);
+            };
+            let mut newest_binding: Option<&Signature> = None;
+            for sig in &subkey.sigs {
+                if !self_sig_ok(sig, &hash_binding) {
+                    continue;
+                }
+                match sig.sig_type {
+                    SIG_SUBKEY_REVOCATION => subkey.revoked = true,
+                    SIG_SUBKEY_BINDING
+                        if newest_binding.is_none_or(|newest| sig.created > newest.created) =>
+                    {
+                        newest_binding = Some(sig);
+                    }
+                    _ => {}
+                }
+            }
+            // The subkey signs the primary key back, so that nobody can claim
+            // someone else's signing subkey as their own
+            let back_signed = newest_binding
+                .and_then(|sig| sig.embedded.as_deref())
+                .is_some_and(|back_sig| {
+                    back_sig.sig_type == SIG_PRIMARY_BINDING
+                        && Hasher::new(back_sig.hash_alg).is_some_and(|mut hasher| {
+                            hash_binding(&mut hasher);
+                            subkey.material.verify(back_sig, hasher) == Some(true)
+                        })
+                });
+            subkey.bound = newest_binding.is_some();
+            subkey.back_signed = back_signed;
+            subkey.material.expires = newest_binding
+                .and_then(|sig| sig.key_expiration)
+                .map(|exp| subkey.material.created.saturating_add(exp));
+            subkey.material.flags = newest_binding.and_then(|sig| sig.key_flags);
+        }
+        self.primary.expires = primary_expires;
+        self.primary.flags = primary_flags;
+    }
+    /// The primary key and all bound subkeys, with whether they may sign
+    fn signing_keys(&self) -> impl Iterator<Item = (&KeyMaterial, bool)> {
+        std::iter::once((&self.primary, self.primary.can_sign())).chain(
+            self.subkeys
+                .iter()
+                .filter(|subkey| subkey.bound && !subkey.revoked)
+                .map(|subkey| {
+                    let usable = subkey.material.can_sign() && subkey.back_signed;
+                    (&subkey.material, usable)
+                }),
+        )
+    }
+    /// Whether any user ID of this key carries a valid certification by `certifier`
+    ///
+    /// A certification doesn't count if the certifier revoked it later.
+    fn is_certified_by(&self, certifier: &KeyMaterial) -> bool {
+        if certifier.fingerprint == self.primary.fingerprint || !certifier.can_certify() {
+            return false;
+        }
+        self.uids.iter().any(|uid| {
+            let verified = |sig: &Signature| {
+                sig.is_issued_by(certifier)
+                    && Hasher::new(sig.hash_alg).is_some_and(|mut hasher| {
+                        hasher.update_key(&self.primary);
+                        hash_uid(&mut hasher, &uid.id);
+                        certifier.verify(sig, hasher) == Some(true)
+                    })
+            };
+            let revoked_at = uid
+                .sigs
+                .iter()
+                .filter(|sig| sig.sig_type == SIG_CERT_REVOCATION && verified(sig))
+                .map(|sig| sig.created)
+                .max();
+            uid.sigs.iter().any(|sig| {
+                (0x10..=0x13).contains(&sig.sig_type)
+                    && revoked_at.is_none_or(|revoked_at| sig.created > revoked_at)
+                    && verified(sig)
+            })
+        })
+    }
+}
+
+fn hash_uid(hasher: &mut Hasher, uid: &[u8]) {
+    hasher.update(&[0xB4]);
+    hasher.update(&u32::try_from(uid.len()).unwrap_or(u32::MAX).to_be_bytes());
+    hasher.update(uid);
+}
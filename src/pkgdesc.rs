@@ -1,15 +1,25 @@
-use smol_str::SmolStr;
+use {
+    crate::version::{Version, vercmp},
+    smol_str::SmolStr,
+    std::fmt::{self, Write as _},
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PkgDesc {
     pub name: SmolStr,
-    pub version: SmolStr,
+    pub version: Version,
+    /// Name of the split package base, if different from the name
+    pub base: Option<SmolStr>,
     pub desc: Option<SmolStr>,
     pub arch: SmolStr,
     pub url: Option<SmolStr>,
     pub licenses: Vec<SmolStr>,
     pub depends: Vec<Depend>,
     pub opt_depends: Vec<OptDepend>,
+    /// Build time dependencies (sync databases only)
+    pub make_depends: Vec<Depend>,
+    /// Test dependencies (sync databases only)
+    pub check_depends: Vec<Depend>,
     pub provides: Vec<Depend>,
     pub conflicts: Vec<SmolStr>,
     pub replaces: Vec<SmolStr>,
@@ -23,33 +33,40 @@ pub struct PkgDesc {
     pub install_reason: InstallReason,
     pub install_script: bool,
     pub validations: Vec<Validation>,
+    /// File name of the package archive (sync databases only)
+    pub filename: Option<SmolStr>,
+    /// Hex encoded SHA-256 checksum of the package archive (sync databases only)
+    pub sha256sum: Option<SmolStr>,
+    /// Base64 encoded `OpenPGP` signature of the package archive (sync databases only)
+    pub pgpsig: Option<SmolStr>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Depend {
     pub name: SmolStr,
     pub ver: Option<DepVer>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DepVer {
     pub req_cmp: ReqCmp,
-    pub ver: SmolStr,
+    pub ver: Version,
 }
 
 impl DepVer {
     fn satisfies(&self, other: &Self) -> bool {
+        let ord = vercmp(self.ver.as_str(), other.ver.as_str());
         match other.req_cmp {
-            ReqCmp::Lt => self.ver < other.ver,
-            ReqCmp::Gt => self.ver > other.ver,
-            ReqCmp::Eq => self.ver == other.ver,
-            ReqCmp::LtEq => self.ver <= other.ver,
-            ReqCmp::GtEq => self.ver >= other.ver,
+            ReqCmp::Lt => ord.is_lt(),
+            ReqCmp::Gt => ord.is_gt(),
+            ReqCmp::Eq => ord.is_eq(),
+            ReqCmp::LtEq => ord.is_le(),
+            ReqCmp::GtEq => ord.is_ge(),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ReqCmp {
     Lt,
     LtEq,
@@ -59,7 +76,7 @@ pub enum ReqCmp {
 }
 
 impl Depend {
-    fn parse(src: &str) -> Self {
+    pub(crate) fn parse(src: &str) -> Self {
         match src.find(['=', '>', '<']) {
             Some(pos) => {
                 let name = &src[..pos];
@@ -85,7 +102,7 @@ impl Depend {
                     name: name.into(),
                     ver: Some(DepVer {
                         req_cmp,
-                        ver: ver.into(),
+                        ver: Version::new(ver),
                     }),
                 }
             }
@@ -97,14 +114,12 @@ impl Depend {
     }
     /// Checks if this dependency satisfies a requirement dependency
     ///
-    /// # Panics
-    ///
-    /// If the requirement has a version, but this dependnecy doesn't
+    /// Like in alpm, a provision without a version only meets unversioned requirements.
     #[must_use]
     pub fn satisfies(&self, req: &Self) -> bool {
         if self.name == req.name {
             match &req.ver {
-                Some(ver) => self.ver.as_ref().unwrap().satisfies(ver),
+                Some(ver) => self.ver.as_ref().is_some_and(|own| own.satisfies(ver)),
                 None => true,
             }
         } else {
@@ -113,20 +128,52 @@ impl Depend {
     }
 }
 
-#[derive(Debug)]
+impl fmt::Display for ReqCmp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Lt => "<",
+            Self::LtEq => "<=",
+            Self::Gt => ">",
+            Self::GtEq => ">=",
+            Self::Eq => "=",
+        })
+    }
+}
+
+impl fmt::Display for Depend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)?;
+        if let Some(ver) = &self.ver {
+            write!(f, "{}{}", ver.req_cmp, ver.ver)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for OptDepend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.dep)?;
+        if let Some(reason) = &self.reason {
+            write!(f, ": {reason}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InstallReason {
     Explicit,
     Dep,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Validation {
     Pgp,
     Sha256,
     Md5,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct OptDepend {
     pub dep: Depend,
     pub reason: Option<SmolStr>,
@@ -135,108 +182,239 @@ pub struct OptDepend {
 impl PkgDesc {
     /// Parse a package description file
     ///
-    /// # Panics
-    ///
-    /// Panics on missing mandatory keys, like `name` and `version`.
+    /// Returns `None` if a mandatory key, like `name` or `version`, is missing.
     #[must_use]
-    pub fn parse(src: &str, install_script: bool) -> Self {
+    pub fn parse(src: &str, install_script: bool) -> Option<Self> {
+        let mut desc = Self::empty();
+        desc.install_script = install_script;
         let mut section = None;
-        let [
-            mut name,
-            mut version,
-            mut arch,
-            mut desc,
-            mut url,
-            mut packager,
-        ] = [const { None }; 6];
-        let mut depends = Vec::new();
-        let mut opt_depends = Vec::new();
-        let mut licenses = Vec::new();
-        let mut provides = Vec::new();
-        let mut conflicts = Vec::new();
-        let mut replaces = Vec::new();
-        let mut groups = Vec::new();
-        let mut size = 0;
-        let mut c_size = 0;
-        let mut build_date = 0;
-        let mut install_date = 0;
-        let mut install_reason = InstallReason::Explicit;
-        let mut validations = Vec::new();
         for line in src.lines() {
             if line.is_empty() {
                 section = None;
                 continue;
             }
             match section {
-                Some(section) => match section {
-                    "NAME" => name = Some(line.into()),
-                    "VERSION" => version = Some(line.into()),
-                    "ARCH" => arch = Some(line.into()),
-                    "DESC" => desc = Some(line.into()),
-                    "URL" => url = Some(line.into()),
-                    "DEPENDS" => depends.push(Depend::parse(line)),
-                    "OPTDEPENDS" => {
-                        let (dep, reason) = match line.split_once(": ") {
-                            Some((pkg, reason)) => (pkg, Some(reason.into())),
-                            None => (line, None),
-                        };
-                        opt_depends.push(OptDepend {
-                            dep: Depend::parse(dep),
-                            reason,
-                        });
-                    }
-                    "LICENSE" => licenses.push(line.into()),
-                    "PROVIDES" => provides.push(Depend::parse(line)),
-                    "CONFLICTS" => conflicts.push(line.into()),
-                    "REPLACES" => replaces.push(line.into()),
-                    "GROUPS" => groups.push(line.into()),
-                    "SIZE" | "ISIZE" => size = line.parse().unwrap(),
-                    "CSIZE" => c_size = line.parse().unwrap(),
-                    "PACKAGER" => packager = Some(line.into()),
-                    "BUILDDATE" => build_date = line.parse().unwrap(),
-                    "INSTALLDATE" => install_date = line.parse().unwrap(),
-                    "REASON" => {
-                        install_reason = match line {
-                            "0" => InstallReason::Explicit,
-                            _ => InstallReason::Dep,
-                        }
-                    }
-                    #[expect(clippy::single_match)]
-                    "VALIDATION" => match line {
-                        "pgp" => validations.push(Validation::Pgp),
-                        _ => {}
-                    },
-                    "SHA256SUM" => validations.push(Validation::Sha256),
-                    "MD5SUM" => validations.push(Validation::Md5),
-                    "PGPSIG" => validations.push(Validation::Pgp),
-                    _ => {}
-                },
+                Some(section) => desc.set_field(section, line),
                 None => {
                     section = Some(&line[1..line.len() - 1]);
                 }
             }
         }
+        desc.has_mandatory_fields().then_some(desc)
+    }
+    /// Parse the `.PKGINFO` file of a package archive
+    ///
+    /// Returns `None` if a mandatory key is missing.
+    #[must_use]
+    pub fn parse_pkginfo(src: &str) -> Option<Self> {
+        let mut desc = Self::empty();
+        for line in src.lines() {
+            if line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let section = match key {
+                "pkgname" => "NAME",
+                "pkgbase" => "BASE",
+                "pkgver" => "VERSION",
+                "pkgdesc" => "DESC",
+                "url" => "URL",
+                "builddate" => "BUILDDATE",
+                "packager" => "PACKAGER",
+                "size" => "SIZE",
+                "arch" => "ARCH",
+                "license" => "LICENSE",
+                "replaces" => "REPLACES",
+                "group" => "GROUPS",
+                "conflict" => "CONFLICTS",
+                "provides" => "PROVIDES",
+                "depend" => "DEPENDS",
+                "optdepend" => "OPTDEPENDS",
+                "makedepend" => "MAKEDEPENDS",
+                "checkdepend" => "CHECKDEPENDS",
+                _ => continue,
+            };
+            if !value.is_empty() {
+                desc.set_field(section, value);
+            }
+        }
+        desc.has_mandatory_fields().then_some(desc)
+    }
+    pub(crate) fn empty() -> Self {
         Self {
-            name: name.unwrap(),
-            version: version.unwrap(),
-            arch: arch.unwrap(),
-            desc,
-            licenses,
-            url,
-            depends,
-            opt_depends,
-            provides,
-            conflicts,
-            replaces,
-            size,
-            c_size,
-            packager,
-            groups,
-            build_date,
-            install_date,
-            install_reason,
-            install_script,
-            validations,
+            name: SmolStr::default(),
+            version: Version::new(""),
+            base: None,
+            desc: None,
+            arch: SmolStr::default(),
+            url: None,
+            licenses: Vec::new(),
+            depends: Vec::new(),
+            opt_depends: Vec::new(),
+            make_depends: Vec::new(),
+            check_depends: Vec::new(),
+            provides: Vec::new(),
+            conflicts: Vec::new(),
+            replaces: Vec::new(),
+            size: 0,
+            c_size: 0,
+            packager: None,
+            groups: Vec::new(),
+            build_date: 0,
+            install_date: 0,
+            install_reason: InstallReason::Explicit,
+            install_script: false,
+            validations: Vec::new(),
+            filename: None,
+            sha256sum: None,
+            pgpsig: None,
         }
     }
+    fn has_mandatory_fields(&self) -> bool {
+        !self.name.is_empty() && !self.version.as_str().is_empty() && !self.arch.is_empty()
+    }
+    /// Set the field for a `%SECTION%` from a line of its value
+    pub(crate) fn set_field(&mut self, section: &str, line: &str) {
+        match section {
+            "NAME" => self.name = line.into(),
+            "VERSION" => self.version = Version::new(line),
+            "BASE" => self.base = Some(line.into()),
+            "ARCH" => self.arch = line.into(),
+            "DESC" => self.desc = Some(line.into()),
+            "URL" => self.url = Some(line.into()),
+            "DEPENDS" => self.depends.push(Depend::parse(line)),
+            "OPTDEPENDS" => {
+                let (dep, reason) = match line.split_once(": ") {
+                    Some((pkg, reason)) => (pkg, Some(reason.into())),
+                    None => (line, None),
+                };
+                self.opt_depends.push(OptDepend {
+                    dep: Depend::parse(dep),
+                    reason,
+                });
+            }
+            "MAKEDEPENDS" => self.make_depends.push(Depend::parse(line)),
+            "CHECKDEPENDS" => self.check_depends.push(Depend::parse(line)),
+            "LICENSE" => self.licenses.push(line.into()),
+            "PROVIDES" => self.provides.push(Depend::parse(line)),
+            "CONFLICTS" => self.conflicts.push(line.into()),
+            "REPLACES" => self.replaces.push(line.into()),
+            "GROUPS" => self.groups.push(line.into()),
+            "SIZE" | "ISIZE" => self.size = line.parse().unwrap_or_default(),
+            "CSIZE" => self.c_size = line.parse().unwrap_or_default(),
+            "PACKAGER" => self.packager = Some(line.into()),
+            "BUILDDATE" => self.build_date = line.parse().unwrap_or_default(),
+            "INSTALLDATE" => self.install_date = line.parse().unwrap_or_default(),
+            "REASON" => {
+                self.install_reason = match line {
+                    "0" => InstallReason::Explicit,
+                    _ => InstallReason::Dep,
+                }
+            }
+            "VALIDATION" => match line {
+                "pgp" => self.validations.push(Validation::Pgp),
+                "sha256" => self.validations.push(Validation::Sha256),
+                "md5" => self.validations.push(Validation::Md5),
+                _ => {}
+            },
+            "FILENAME" => self.filename = Some(line.into()),
+            "SHA256SUM" => {
+                self.sha256sum = Some(line.into());
+                self.validations.push(Validation::Sha256);
+            }
+            "MD5SUM" => self.validations.push(Validation::Md5),
+            "PGPSIG" => {
+                self.pgpsig = Some(line.into());
+                self.validations.push(Validation::Pgp);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Writes a `%SECTION%` with one value per line, skipping it if there are no values
+fn write_section<T: fmt::Display>(
+    out: &mut String,
+    name: &str,
+    values: impl IntoIterator<Item = T>,
+) {
+    let mut values = values.into_iter().peekable();
+    if values.peek().is_none() {
+        return;
+    }
+    let _ = writeln!(out, "%{name}%");
+    for value in values {
+        let _ = writeln!(out, "{value}");
+    }
+    out.push('\n');
+}
+
+impl PkgDesc {
+    /// Serialize to the `desc` format of local database entries
+    #[must_use]
+    pub fn to_local_desc(&self) -> String {
+        let mut out = String::new();
+        write_section(&mut out, "NAME", [&self.name]);
+        write_section(&mut out, "VERSION", [&self.version]);
+        write_section(&mut out, "BASE", &self.base);
+        write_section(&mut out, "DESC", &self.desc);
+        write_section(&mut out, "URL", &self.url);
+        write_section(&mut out, "ARCH", [&self.arch]);
+        write_section(&mut out, "BUILDDATE", [self.build_date]);
+        write_section(&mut out, "INSTALLDATE", [self.install_date]);
+        write_section(&mut out, "PACKAGER", &self.packager);
+        write_section(&mut out, "SIZE", Some(self.size).filter(|&size| size != 0));
+        if let InstallReason::Dep = self.install_reason {
+            write_section(&mut out, "REASON", [1]);
+        }
+        write_section(&mut out, "GROUPS", &self.groups);
+        write_section(&mut out, "LICENSE", &self.licenses);
+        let validations = self.validations.iter().map(|validation| match validation {
+            Validation::Pgp => "pgp",
+            Validation::Sha256 => "sha256",
+            Validation::Md5 => "md5",
+        });
+        if self.validations.is_empty() {
+            write_section(&mut out, "VALIDATION", ["none"]);
+        } else {
+            write_section(&mut out, "VALIDATION", validations);
+        }
+        write_section(&mut out, "REPLACES", &self.replaces);
+        write_section(&mut out, "DEPENDS", &self.depends);
+        write_section(&mut out, "OPTDEPENDS", &self.opt_depends);
+        write_section(&mut out, "CONFLICTS", &self.conflicts);
+        write_section(&mut out, "PROVIDES", &self.provides);
+        out
+    }
+    /// Serialize to the `desc` format of sync database entries, like `repo-add` does
+    #[must_use]
+    pub fn to_sync_desc(&self) -> String {
+        let mut out = String::new();
+        write_section(&mut out, "FILENAME", &self.filename);
+        write_section(&mut out, "NAME", [&self.name]);
+        write_section(&mut out, "BASE", &self.base);
+        write_section(&mut out, "VERSION", [&self.version]);
+        write_section(&mut out, "DESC", &self.desc);
+        write_section(&mut out, "GROUPS", &self.groups);
+        write_section(&mut out, "CSIZE", [self.c_size]);
+        write_section(&mut out, "ISIZE", [self.size]);
+        write_section(&mut out, "SHA256SUM", &self.sha256sum);
+        write_section(&mut out, "PGPSIG", &self.pgpsig);
+        write_section(&mut out, "URL", &self.url);
+        write_section(&mut out, "LICENSE", &self.licenses);
+        write_section(&mut out, "ARCH", [&self.arch]);
+        write_section(&mut out, "BUILDDATE", [self.build_date]);
+        write_section(&mut out, "PACKAGER", &self.packager);
+        write_section(&mut out, "REPLACES", &self.replaces);
+        write_section(&mut out, "CONFLICTS", &self.conflicts);
+        write_section(&mut out, "PROVIDES", &self.provides);
+        write_section(&mut out, "DEPENDS", &self.depends);
+        write_section(&mut out, "OPTDEPENDS", &self.opt_depends);
+        write_section(&mut out, "MAKEDEPENDS", &self.make_depends);
+        write_section(&mut out, "CHECKDEPENDS", &self.check_depends);
+        out
+    }
 }
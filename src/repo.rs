@@ -0,0 +1,209 @@
+//! Building repository databases, like `repo-add` and `repo-remove`
+
+use {
+    crate::{
+        DbReadError, Pkg,
+        cache::sha256_hex,
+        package::{PkgReadError, read_package},
+        read_db_archive,
+        version::Version,
+    },
+    base64::Engine as _,
+    flate2::{Compression, write::GzEncoder},
+    smol_str::SmolStr,
+    std::{
+        collections::BTreeMap,
+        io::Write as _,
+        path::{Path, PathBuf},
+    },
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RepoError {
+    #[error("Io error")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to read package {path}")]
+    Package {
+        path: PathBuf,
+        #[source]
+        source: PkgReadError,
+    },
+    #[error("Failed to read the existing database")]
+    Db(#[from] DbReadError),
+    #[error("Package path has no file name: {0}")]
+    NoFileName(PathBuf),
+}
+
+/// What happened when adding a package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddOutcome {
+    Added,
+    /// An entry for another version of the package was replaced
+    Replaced {
+        old_version: Version,
+    },
+    /// The repository already has the same or a newer version
+    Skipped {
+        existing_version: Version,
+    },
+}
+
+/// A repository database being built
+///
+/// Changes are kept in memory until [`RepoBuilder::write`] is called.
+pub struct RepoBuilder {
+    dir: PathBuf,
+    name: String,
+    /// Entries by package name
+    entries: BTreeMap<SmolStr, Pkg>,
+    include_signatures: bool,
+}
+
+impl RepoBuilder {
+    /// Open the repository `name` in `dir`
+    ///
+    /// Existing entries are loaded from `{name}.files.tar.gz` (or `{name}.db.tar.gz`
+    /// if there is no files database), so packages can be added incrementally.
+    pub fn open(dir: impl AsRef<Path>, name: &str) -> Result<Self, RepoError> {
+        let dir = dir.as_ref().to_owned();
+        let mut entries = BTreeMap::new();
+        for db in [format!("{name}.files.tar.gz"), format!("{name}.db.tar.gz")] {
+            let path = dir.join(db);
+            if path.exists() {
+                for pkg in read_db_archive(std::fs::File::open(path)?)? {
+                    entries.insert(pkg.desc.name.clone(), pkg);
+                }
+                break;
+            }
+        }
+        Ok(Self {
+            dir,
+            name: name.to_owned(),
+            entries,
+            include_signatures: false,
+        })
+    }
+    /// Embed detached signatures (`{package}.sig`) as `%PGPSIG%`, like `repo-add --include-sigs`
+    pub fn include_signatures(&mut self, include: bool) -> &mut Self {
+        self.include_signatures = include;
+        self
+    }
+    /// The current entries of the repository
+    pub fn entries(&self) -> impl Iterator<Item = &Pkg> {
+        self.entries.values()
+    }
+    /// Add the package file at `path`, replacing any other version of it
+    pub fn add(&mut self, path: impl AsRef<Path>) -> Result<AddOutcome, RepoError> {
+        self.add_impl(path.as_ref(), false)
+    }
+    /// Add the package file at `path`, unless the repository has the same or a newer version
+    /// (`repo-add --new`)
+    pub fn add_if_newer(&mut self, path: impl AsRef<Path>) -> Result<AddOutcome, RepoError> {
+        self.add_impl(path.as_ref(), true)
+    }
+    fn add_impl(&mut self, path: &Path, only_newer: bool) -> Result<AddOutcome, RepoError> {
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| RepoError::NoFileName(path.to_owned()))?;
+        let mut pkg = read_package(path)
+            .map_err(|source| RepoError::Package {
+                path: path.to_owned(),
+                source,
+            })?
+            .pkg;
+        let existing = self.entries.get(&pkg.desc.name);
+        if only_newer
+            && let Some(existing) = existing
+            && existing.desc.version >= pkg.desc.version
+        {
+            return Ok(AddOutcome::Skipped {
+                existing_version: existing.desc.version.clone(),
+            });
+        }
+        let desc = &mut pkg.desc;
+        desc.filename = Some(file_name.into());
+        desc.c_size = std::fs::metadata(path)?.len();
+        desc.sha256sum = Some(sha256_hex(std::fs::File::open(path)?)?);
+        desc.pgpsig = None;
+        if self.include_signatures {
+            let mut sig_path = path.as_os_str().to_owned();
+            sig_path.push(".sig");
+            match std::fs::read(sig_path) {
+                Ok(sig) => {
+                    desc.pgpsig =
+                        Some(base64::engine::general_purpose::STANDARD.encode(sig).into());
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        // Backup entries are only meaningful for installed packages
+        pkg.backup.clear();
+        let outcome = match self.entries.insert(pkg.desc.name.clone(), pkg) {
+            Some(old) => AddOutcome::Replaced {
+                old_version: old.desc.version,
+            },
+            None => AddOutcome::Added,
+        };
+        Ok(outcome)
+    }
+    /// Remove the package `name`, returning whether it was in the repository
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.entries.remove(name).is_some()
+    }
+    /// Write `{name}.db.tar.gz` and `{name}.files.tar.gz`,
+    /// along with the `{name}.db` and `{name}.files` symlinks pacman expects
+    ///
+    /// Each archive and symlink is created under a temporary name first,
+    /// then moved into place, so pacman never sees either of them missing.
+    pub fn write(&self) -> Result<(), RepoError> {
+        for files in [false, true] {
+            let kind = if files { "files" } else { "db" };
+            let archive_name = format!("{}.{kind}.tar.gz", self.name);
+            let path = self.dir.join(&archive_name);
+            let tmp_path = self.dir.join(format!(".{archive_name}.tmp"));
+            self.write_archive(&tmp_path, files)?;
+            std::fs::rename(&tmp_path, &path)?;
+            let link_name = format!("{}.{kind}", self.name);
+            let tmp_link = self.dir.join(format!(".{link_name}.tmp"));
+            // Left behind by an interrupted write
+            if tmp_link.symlink_metadata().is_ok() {
+                std::fs::remove_file(&tmp_link)?;
+            }
+            std::os::unix::fs::symlink(&archive_name, &tmp_link)?;
+            std::fs::rename(&tmp_link, self.dir.join(link_name))?;
+        }
+        Ok(())
+    }
+    fn write_archive(&self, path: &Path, with_files: bool) -> std::io::Result<()> {
+        let enc = GzEncoder::new(std::fs::File::create(path)?, Compression::default());
+        let mut builder = tar::Builder::new(enc);
+        for pkg in self.entries.values() {
+            let dir = format!("{}-{}", pkg.desc.name, pkg.desc.version);
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Directory);
+            header.set_mode(0o755);
+            header.set_mtime(pkg.desc.build_date);
+            header.set_size(0);
+            builder.append_data(&mut header, format!("{dir}/"), std::io::empty())?;
+            let mut append_file = |name: &str, contents: &[u8]| {
+                let mut header = tar::Header::new_gnu();
+                header.set_mode(0o644);
+                header.set_mtime(pkg.desc.build_date);
+                header.set_size(contents.len() as u64);
+                builder.append_data(&mut header, format!("{dir}/{name}"), contents)
+            };
+            append_file("desc", pkg.desc.to_sync_desc().as_bytes())?;
+            if with_files {
+                let mut files = String::from("%FILES%\n");
+                for file in &pkg.files {
+                    files.push_str(file);
+                    files.push('\n');
+                }
+                append_file("files", files.as_bytes())?;
+            }
+        }
+        builder.into_inner()?.finish()?.flush()
+    }
+}
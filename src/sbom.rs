@@ -0,0 +1,481 @@
+//! Software bill of materials export, as SPDX 2.3 or `CycloneDX` 1.5 JSON
+
+use {
+    crate::{
+        Pkg, PkgDesc,
+        dep::{VersionSet, name_index},
+        json::Json,
+        mtree::{self, EntryKind, MtreeEntry},
+    },
+    sha2::{Digest as _, Sha256},
+    smol_str::SmolStr,
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        fmt::Write as _,
+        path::Path,
+    },
+};
+
+/// Package URL of a package, like `pkg:alpm/arch/pacman@6.1.0-3?arch=x86_64`
+#[must_use]
+pub fn purl(desc: &PkgDesc) -> String {
+    format!(
+        "pkg:alpm/arch/{}@{}?arch={}",
+        percent_encode(&desc.name),
+        percent_encode(desc.version.as_str()),
+        percent_encode(&desc.arch)
+    )
+}
+
+/// A bill of materials for a set of installed packages
+pub struct Sbom<'a> {
+    name: String,
+    pkgs: &'a [Pkg],
+    created: jiff::Timestamp,
+    /// `mtree` entries by package name
+    files: BTreeMap<SmolStr, Vec<MtreeEntry>>,
+}
+
+impl<'a> Sbom<'a> {
+    /// A bill of materials called `name` (e.g. the host or image name) for `pkgs`
+    #[must_use]
+    pub fn new(name: &str, pkgs: &'a [Pkg]) -> Self {
+        Self {
+            name: name.to_owned(),
+            pkgs,
+            created: jiff::Timestamp::now(),
+            files: BTreeMap::new(),
+        }
+    }
+    /// Set the creation time, which is the current time by default
+    #[must_use]
+    pub fn created(mut self, created: jiff::Timestamp) -> Self {
+        self.created = created;
+        self
+    }
+    /// Include the files of the package `name`, with their checksums
+    ///
+    /// Files are only listed in `CycloneDX` documents: SPDX 2.3 requires a SHA1
+    /// checksum for every file, which `mtree` files don't have.
+    #[must_use]
+    pub fn files(mut self, name: &str, entries: Vec<MtreeEntry>) -> Self {
+        self.files.insert(name.into(), entries);
+        self
+    }
+    /// Include the files of all packages that have an `mtree` file in the local
+    /// database under `db_path`
+    pub fn read_files(mut self, db_path: impl AsRef<Path>) -> std::io::Result<Self> {
+        for pkg in self.pkgs {
+            if let Some(entries) = mtree::read_local(db_path.as_ref(), &pkg.desc)? {
+                self.files.insert(pkg.desc.name.clone(), entries);
+            }
+        }
+        Ok(self)
+    }
+    /// Regular files of a package, without the package metadata files
+    fn pkg_files(&self, pkg: &Pkg) -> impl Iterator<Item = &MtreeEntry> {
+        self.files
+            .get(&pkg.desc.name)
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.kind == EntryKind::File && !entry.is_metadata())
+    }
+    /// Indices of the packages each package depends on
+    fn dependencies(&self) -> Vec<BTreeSet<usize>> {
+        let index = name_index(self.pkgs.iter().map(|pkg| &pkg.desc));
+        self.pkgs
+            .iter()
+            .enumerate()
+            .map(|(i, pkg)| {
+                let mut deps = BTreeSet::new();
+                for dep in &pkg.desc.depends {
+                    let set = dep
+                        .ver
+                        .as_ref()
+                        .map_or_else(VersionSet::any, VersionSet::from);
+                    deps.extend(
+                        index
+                            .get(dep.name.as_str())
+                            .into_iter()
+                            .flatten()
+                            .copied()
+                            .filter(|&j| j != i && set.matches(&dep.name, &self.pkgs[j].desc)),
+                    );
+                }
+                deps
+            })
+            .collect()
+    }
+    /// A UUID derived from the contents, so the same input gives the same document
+    fn uuid(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.name.as_bytes());
+        hasher.update(self.created.as_second().to_le_bytes());
+        for pkg in self.pkgs {
+            hasher.update(pkg.desc.name.as_bytes());
+            hasher.update([0]);
+            hasher.update(pkg.desc.version.as_str());
+            hasher.update([0]);
+        }
+        let mut bytes = hasher.finalize();
+        // Version 8 (custom), RFC 4122 variant
+        bytes[6] = (bytes[6] & 0x0f) | 0x80;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        let hex: String = bytes[..16].iter().fold(String::new(), |mut s, b| {
+            let _ = write!(s, "{b:02x}");
+            s
+        });
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+    fn timestamp(&self) -> String {
+        self.created.strftime("%Y-%m-%dT%H:%M:%SZ").to_string()
+    }
+    /// The bill of materials as an SPDX 2.3 JSON document
+    #[must_use]
+    pub fn to_spdx_json(&self) -> String {
+        let pkg_id = |i: usize| format!("SPDXRef-Package-{i}-{}", spdx_id(&self.pkgs[i].desc.name));
+        let mut packages = Vec::new();
+        let mut relationships = Vec::new();
+        let mut license_refs = BTreeMap::new();
+        for (i, pkg) in self.pkgs.iter().enumerate() {
+            packages.push(spdx_package(&pkg_id(i), &pkg.desc, &mut license_refs));
+            relationships.push(relationship("SPDXRef-DOCUMENT", "DESCRIBES", pkg_id(i)));
+        }
+        for (i, deps) in self.dependencies().into_iter().enumerate() {
+            for j in deps {
+                relationships.push(relationship(&pkg_id(i), "DEPENDS_ON", pkg_id(j)));
+            }
+        }
+        let mut doc = vec![
+            ("spdxVersion", Json::str("SPDX-2.3")),
+            ("dataLicense", Json::str("CC0-1.0")),
+            ("SPDXID", Json::str("SPDXRef-DOCUMENT")),
+            ("name", Json::str(&self.name)),
+            (
+                "documentNamespace",
+                Json::str(format!(
+                    "https://spdx.org/spdxdocs/{}-{}",
+                    spdx_id(&self.name),
+                    self.uuid()
+                )),
+            ),
+            (
+                "creationInfo",
+                Json::object([
+                    ("created", Json::str(self.timestamp())),
+                    (
+                        "creators",
+                        Json::Array(vec![Json::str(concat!(
+                            "Tool: alpacka-",
+                            env!("CARGO_PKG_VERSION")
+                        ))]),
+                    ),
+                ]),
+            ),
+            ("packages", Json::Array(packages)),
+            ("relationships", Json::Array(relationships)),
+        ];
+        if !license_refs.is_empty() {
+            let infos = license_refs
+                .into_iter()
+                .map(|(id, pkg_name)| {
+                    Json::object([
+                        ("licenseId", Json::str(&id)),
+                        ("name", Json::str(&id)),
+                        (
+                            "extractedText",
+                            Json::str(format!("See /usr/share/licenses/{pkg_name}")),
+                        ),
+                    ])
+                })
+                .collect();
+            doc.push(("hasExtractedLicensingInfos", Json::Array(infos)));
+        }
+        Json::object(doc).to_string()
+    }
+    /// The bill of materials as a `CycloneDX` 1.5 JSON document
+    #[must_use]
+    pub fn to_cyclonedx_json(&self) -> String {
+        let purls: Vec<String> = self.pkgs.iter().map(|pkg| purl(&pkg.desc)).collect();
+        let mut components = Vec::new();
+        for (pkg, purl) in self.pkgs.iter().zip(&purls) {
+            let desc = &pkg.desc;
+            let mut fields = cyclonedx_fields(desc, purl);
+            let files: Vec<Json> = self
+                .pkg_files(pkg)
+                .map(|entry| {
+                    let hashes = [("SHA-256", &entry.sha256), ("MD5", &entry.md5)]
+                        .into_iter()
+                        .filter_map(|(alg, sum)| {
+                            Some(Json::object([
+                                ("alg", Json::str(alg)),
+                                ("content", Json::str(&**sum.as_ref()?)),
+                            ]))
+                        })
+                        .collect();
+                    Json::object([
+                        ("type", Json::str("file")),
+                        ("name", Json::str(format!("/{}", entry.path))),
+                        ("hashes", Json::Array(hashes)),
+                    ])
+                })
+                .collect();
+            if !files.is_empty() {
+                fields.push(("components", Json::Array(files)));
+            }
+            components.push(Json::object(fields));
+        }
+        let dependencies = self
+            .dependencies()
+            .into_iter()
+            .enumerate()
+            .map(|(i, deps)| {
+                Json::object([
+                    ("ref", Json::str(&purls[i])),
+                    (
+                        "dependsOn",
+                        Json::Array(deps.into_iter().map(|j| Json::str(&purls[j])).collect()),
+                    ),
+                ])
+            })
+            .collect();
+        Json::object([
+            ("bomFormat", Json::str("CycloneDX")),
+            ("specVersion", Json::str("1.5")),
+            (
+                "serialNumber",
+                Json::str(format!("urn:uuid:{}", self.uuid())),
+            ),
+            ("version", Json::Num(1.0)),
+            (
+                "metadata",
+                Json::object([
+                    ("timestamp", Json::str(self.timestamp())),
+                    (
+                        "tools",
+                        Json::object([(
+                            "components",
+                            Json::Array(vec![Json::object([
+                                ("type", Json::str("application")),
+                                ("name", Json::str("alpacka")),
+                                ("version", Json::str(env!("CARGO_PKG_VERSION"))),
+                            ])]),
+                        )]),
+                    ),
+                    (
+                        "component",
+                        Json::object([
+                            ("type", Json::str("operating-system")),
+                            ("name", Json::str(&self.name)),
+                        ]),
+                    ),
+                ]),
+            ),
+            ("components", Json::Array(components)),
+            ("dependencies", Json::Array(dependencies)),
+        ])
+        .to_string()
+    }
+}
+
+/// An SPDX package, adding the `LicenseRef-` identifiers it uses to `license_refs`
+fn spdx_package(id: &str, desc: &PkgDesc, license_refs: &mut BTreeMap<String, SmolStr>) -> Json {
+    let licenses = license_expression(&desc.licenses, |license_id| {
+        license_refs.insert(license_id.to_owned(), desc.name.clone());
+    });
+    let mut fields = vec![
+        ("SPDXID", Json::str(id)),
+        ("name", Json::str(&*desc.name)),
+        ("versionInfo", Json::str(desc.version.as_str())),
+        (
+            "supplier",
+            Json::str(desc.packager.as_deref().map_or_else(
+                || "NOASSERTION".into(),
+                |packager| format!("Person: {}", spdx_person(packager)),
+            )),
+        ),
+        ("downloadLocation", Json::str("NOASSERTION")),
+        ("filesAnalyzed", Json::Bool(false)),
+        ("licenseConcluded", Json::str("NOASSERTION")),
+        (
+            "licenseDeclared",
+            Json::str(licenses.unwrap_or_else(|| "NOASSERTION".into())),
+        ),
+        ("copyrightText", Json::str("NOASSERTION")),
+        ("comment", Json::str(format!("Architecture: {}", desc.arch))),
+        (
+            "externalRefs",
+            Json::Array(vec![Json::object([
+                ("referenceCategory", Json::str("PACKAGE-MANAGER")),
+                ("referenceType", Json::str("purl")),
+                ("referenceLocator", Json::str(purl(desc))),
+            ])]),
+        ),
+    ];
+    if let Some(url) = &desc.url {
+        fields.push(("homepage", Json::str(&**url)));
+    }
+    if let Some(summary) = &desc.desc {
+        fields.push(("summary", Json::str(&**summary)));
+    }
+    Json::object(fields)
+}
+
+/// The fields of a `CycloneDX` component for a package
+fn cyclonedx_fields<'a>(desc: &PkgDesc, purl: &str) -> Vec<(&'a str, Json)> {
+    let mut fields = vec![
+        ("type", Json::str("library")),
+        ("bom-ref", Json::str(purl)),
+        ("name", Json::str(&*desc.name)),
+        ("version", Json::str(desc.version.as_str())),
+        ("purl", Json::str(purl)),
+        (
+            "properties",
+            Json::Array(vec![Json::object([
+                ("name", Json::str("alpm:arch")),
+                ("value", Json::str(&*desc.arch)),
+            ])]),
+        ),
+    ];
+    if let Some(summary) = &desc.desc {
+        fields.push(("description", Json::str(&**summary)));
+    }
+    if let Some(packager) = &desc.packager {
+        fields.push(("publisher", Json::str(&**packager)));
+    }
+    if let Some(expression) = license_expression(&desc.licenses, |_| {}) {
+        fields.push((
+            "licenses",
+            Json::Array(vec![Json::object([("expression", Json::str(expression))])]),
+        ));
+    }
+    if let Some(url) = &desc.url {
+        fields.push((
+            "externalReferences",
+            Json::Array(vec![Json::object([
+                ("type", Json::str("website")),
+                ("url", Json::str(&**url)),
+            ])]),
+        ));
+    }
+    fields
+}
+
+fn relationship(from: &str, kind: &str, to: String) -> Json {
+    Json::object([
+        ("spdxElementId", Json::str(from)),
+        ("relationshipType", Json::str(kind)),
+        ("relatedSpdxElement", Json::Str(to)),
+    ])
+}
+
+/// License names Arch used before switching to SPDX identifiers, which aren't
+/// valid SPDX identifiers themselves
+const LEGACY_LICENSES: &[&str] = &[
+    "AGPL",
+    "AGPL3",
+    "Apache",
+    "Artistic2.0",
+    "BSD",
+    "CCPL",
+    "CDDL",
+    "CPL",
+    "EPL",
+    "FDL",
+    "FDL1.2",
+    "FDL1.3",
+    "GPL",
+    "GPL2",
+    "GPL3",
+    "LGPL",
+    "LGPL2.1",
+    "LGPL3",
+    "LPPL",
+    "MPL",
+    "MPL2",
+    "PerlArtistic",
+    "PHP",
+    "PSF",
+    "Python",
+    "unknown",
+    "ZPL",
+];
+
+/// An SPDX license expression for the licenses of a package
+///
+/// Licenses that aren't SPDX identifiers (like `custom:foo` or the legacy `GPL`)
+/// become `LicenseRef-` identifiers. Those are passed to `license_ref`, along with
+/// the `LicenseRef-` identifiers the package already uses.
+fn license_expression(licenses: &[SmolStr], mut license_ref: impl FnMut(&str)) -> Option<String> {
+    let parts: Vec<String> = licenses
+        .iter()
+        .map(|license| {
+            if license.contains(' ') {
+                // Already an expression, like `MIT OR LicenseRef-foo`
+                license
+                    .split(|c: char| c.is_whitespace() || matches!(c, '(' | ')'))
+                    .filter(|id| id.starts_with("LicenseRef-"))
+                    .for_each(&mut license_ref);
+                format!("({license})")
+            } else if license.starts_with("LicenseRef-") {
+                license_ref(license);
+                license.to_string()
+            } else if !license.starts_with("custom")
+                && !LEGACY_LICENSES
+                    .iter()
+                    .any(|legacy| legacy.eq_ignore_ascii_case(license))
+                && license
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'))
+            {
+                license.to_string()
+            } else {
+                let id = format!("LicenseRef-{}", spdx_id(license));
+                license_ref(&id);
+                id
+            }
+        })
+        .collect();
+    (!parts.is_empty()).then(|| parts.join(" AND "))
+}
+
+/// `s` with characters not allowed in SPDX identifiers replaced by `-`
+fn spdx_id(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// `Name <email>` as SPDX wants it: `Name (email)`
+fn spdx_person(packager: &str) -> String {
+    match packager.split_once('<') {
+        Some((name, email)) => format!("{} ({})", name.trim(), email.trim_end_matches('>')),
+        None => packager.to_owned(),
+    }
+}
+
+/// Percent encode everything but unreserved characters, for package URLs
+fn percent_encode(s: &str) -> String {
+    let mut out = String::new();
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'.' | b'-' | b'_' | b'~') {
+            out.push(char::from(b));
+        } else {
+            let _ = write!(out, "%{b:02X}");
+        }
+    }
+    out
+}
@@ -0,0 +1,278 @@
+//! Parsing of `.SRCINFO` files, the metadata of `PKGBUILD`s used by the AUR
+
+use {
+    crate::{PkgDesc, version::Version},
+    smol_str::SmolStr,
+    std::{collections::BTreeMap, path::Path},
+};
+
+/// Keys that can be overridden by split packages, as makepkg's `srcinfo.sh`
+/// writes them in `pkgname` sections
+const PACKAGE_KEYS: [&str; 15] = [
+    "pkgdesc",
+    "url",
+    "install",
+    "changelog",
+    "arch",
+    "groups",
+    "license",
+    "checkdepends",
+    "depends",
+    "optdepends",
+    "provides",
+    "conflicts",
+    "replaces",
+    "options",
+    "backup",
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum SrcInfoError {
+    #[error("Io error")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid line {0}")]
+    InvalidLine(usize),
+    #[error("Line {0}: key outside of a pkgbase or pkgname section")]
+    NoSection(usize),
+    #[error("Line {0}: key not allowed in a pkgname section")]
+    NotOverridable(usize),
+    #[error("Missing {0}")]
+    Missing(&'static str),
+}
+
+/// A `pkgbase` or `pkgname` section
+#[derive(Debug, Clone, Default)]
+pub struct Section {
+    pub name: SmolStr,
+    /// Values by key, in file order
+    ///
+    /// Architecture specific keys keep their suffix, like `depends_x86_64`.
+    /// An empty value means the key was set to an empty array.
+    pub fields: BTreeMap<SmolStr, Vec<SmolStr>>,
+}
+
+impl Section {
+    fn get(&self, key: &str) -> Option<impl Iterator<Item = &str>> {
+        self.fields.get(key).map(|values| {
+            values
+                .iter()
+                .map(SmolStr::as_str)
+                .filter(|value| !value.is_empty())
+        })
+    }
+}
+
+/// A parsed `.SRCINFO` file
+#[derive(Debug, Clone)]
+pub struct SrcInfo {
+    pub base: Section,
+    /// The packages built from this base, which can override some keys of the base
+    pub packages: Vec<Section>,
+}
+
+impl SrcInfo {
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, SrcInfoError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+    pub fn parse(src: &str) -> Result<Self, SrcInfoError> {
+        let mut base: Option<Section> = None;
+        let mut packages: Vec<Section> = Vec::new();
+        for (i, line) in src.lines().enumerate() {
+            let lineno = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(SrcInfoError::InvalidLine(lineno));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                "pkgbase" => {
+                    base = Some(Section {
+                        name: value.into(),
+                        ..Section::default()
+                    });
+                }
+                "pkgname" => packages.push(Section {
+                    name: value.into(),
+                    ..Section::default()
+                }),
+                _ => {
+                    let section = match packages.last_mut() {
+                        Some(package) => {
+                            if !overridable(key) {
+                                return Err(SrcInfoError::NotOverridable(lineno));
+                            }
+                            package
+                        }
+                        None => base.as_mut().ok_or(SrcInfoError::NoSection(lineno))?,
+                    };
+                    section
+                        .fields
+                        .entry(key.into())
+                        .or_default()
+                        .push(value.into());
+                }
+            }
+        }
+        let base = base.ok_or(SrcInfoError::Missing("pkgbase"))?;
+        for key in ["pkgver", "pkgrel"] {
+            if base.get(key).is_none() {
+                return Err(SrcInfoError::Missing(key));
+            }
+        }
+        if packages.is_empty() {
+            return Err(SrcInfoError::Missing("pkgname"));
+        }
+        Ok(Self { base, packages })
+    }
+    /// The full version, `[epoch:]pkgver-pkgrel`
+    #[must_use]
+    pub fn version(&self) -> Version {
+        let field = |key| self.base.get(key).and_then(|mut v| v.next());
+        let mut version = String::new();
+        if let Some(epoch) = field("epoch").filter(|&epoch| epoch != "0") {
+            version.push_str(epoch);
+            version.push(':');
+        }
+        version.push_str(field("pkgver").unwrap_or_default());
+        version.push('-');
+        version.push_str(field("pkgrel").unwrap_or_default());
+        Version::new(version)
+    }
+    /// Names of the packages built from this base
+    pub fn pkg_names(&self) -> impl Iterator<Item = &str> {
+        self.packages.iter().map(|package| package.name.as_str())
+    }
+    /// The values of a key for a package, with a split package's override applied
+    ///
+    /// If `arch` is given, the values of the architecture specific key
+    /// (like `depends_x86_64`) are appended.
+    /// Returns `None` if there is no such package.
+    #[must_use]
+    pub fn values(&self, pkgname: &str, key: &str, arch: Option<&str>) -> Option<Vec<&str>> {
+        let package = self
+            .packages
+            .iter()
+            .find(|package| package.name == pkgname)?;
+        let lookup = |key: &str| -> Vec<&str> {
+            match package.get(key) {
+                Some(values) => values.collect(),
+                None => self
+                    .base
+                    .get(key)
+                    .map(Iterator::collect)
+                    .unwrap_or_default(),
+            }
+        };
+        let mut values = lookup(key);
+        if let Some(arch) = arch {
+            values.extend(lookup(&format!("{key}_{arch}")));
+        }
+        Some(values)
+    }
+    /// The values of a key of the base, like `source`, `makedepends` or `sha256sums`
+    ///
+    /// If `arch` is given, the values of the architecture specific key
+    /// (like `source_x86_64`) are appended.
+    #[must_use]
+    pub fn base_values(&self, key: &str, arch: Option<&str>) -> Vec<&str> {
+        let mut values: Vec<&str> = self
+            .base
+            .get(key)
+            .map(Iterator::collect)
+            .unwrap_or_default();
+        if let Some(arch) = arch
+            && let Some(arch_values) = self.base.get(&format!("{key}_{arch}"))
+        {
+            values.extend(arch_values);
+        }
+        values
+    }
+    /// Sources for `arch`, paired with their checksums of `kind` (like `sha256sums`)
+    ///
+    /// Sources without a checksum of that kind are paired with `None`.
+    #[must_use]
+    pub fn sources(&self, kind: &str, arch: &str) -> Vec<(&str, Option<&str>)> {
+        let mut sources = Vec::new();
+        for arch in [None, Some(arch)] {
+            let key = |key: &str| match arch {
+                Some(arch) => format!("{key}_{arch}"),
+                None => key.to_owned(),
+            };
+            let mut sums = self
+                .base
+                .get(&key(kind))
+                .map(Iterator::collect::<Vec<_>>)
+                .unwrap_or_default()
+                .into_iter();
+            if let Some(srcs) = self.base.get(&key("source")) {
+                sources.extend(srcs.map(|src| (src, sums.next())));
+            }
+        }
+        sources
+    }
+    /// The package description of `pkgname`, as it would be when built for `arch`
+    ///
+    /// Returns `None` if there is no such package, or it can't be built for `arch`.
+    /// Only the fields known without building are set, so sizes, dates and
+    /// the packager are empty.
+    #[must_use]
+    pub fn pkg_desc(&self, pkgname: &str, arch: &str) -> Option<PkgDesc> {
+        let archs = self.values(pkgname, "arch", None)?;
+        let arch = if archs.contains(&"any") {
+            "any"
+        } else if archs.contains(&arch) {
+            arch
+        } else {
+            return None;
+        };
+        let mut desc = PkgDesc::empty();
+        desc.name = pkgname.into();
+        desc.version = self.version();
+        desc.arch = arch.into();
+        if self.base.name != pkgname {
+            desc.base = Some(self.base.name.clone());
+        }
+        let fields = [
+            ("pkgdesc", "DESC"),
+            ("url", "URL"),
+            ("license", "LICENSE"),
+            ("groups", "GROUPS"),
+            ("checkdepends", "CHECKDEPENDS"),
+            ("depends", "DEPENDS"),
+            ("optdepends", "OPTDEPENDS"),
+            ("provides", "PROVIDES"),
+            ("conflicts", "CONFLICTS"),
+            ("replaces", "REPLACES"),
+        ];
+        for (key, section) in fields {
+            for value in self.values(pkgname, key, Some(arch))? {
+                desc.set_field(section, value);
+            }
+        }
+        for value in self.base_values("makedepends", Some(arch)) {
+            desc.set_field("MAKEDEPENDS", value);
+        }
+        desc.install_script = !self.values(pkgname, "install", None)?.is_empty();
+        Some(desc)
+    }
+    /// Package descriptions of all packages that can be built for `arch`
+    #[must_use]
+    pub fn pkg_descs(&self, arch: &str) -> Vec<PkgDesc> {
+        self.pkg_names()
+            .filter_map(|name| self.pkg_desc(name, arch))
+            .collect()
+    }
+}
+
+/// Whether a split package can override `key`, including architecture specific ones
+fn overridable(key: &str) -> bool {
+    PACKAGE_KEYS.iter().any(|&base| {
+        key == base
+            || key
+                .strip_prefix(base)
+                .is_some_and(|rest| rest.starts_with('_'))
+    })
+}
@@ -0,0 +1,716 @@
+//! Simulation of install and removal transactions, like `pacman -S` and `pacman -R`,
+//! without touching the system
+
+use {
+    crate::{Backup, BackupStatus, Depend, InstallReason, Pkg, SyncDb, dep::VersionSet},
+    smol_str::SmolStr,
+    std::{
+        cmp::Ordering,
+        collections::{BTreeMap, BTreeSet},
+        path::{Path, PathBuf},
+    },
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum PlanError {
+    #[error("Target not found: {0}")]
+    TargetNotFound(SmolStr),
+    #[error("Unable to satisfy dependency '{dep}' required by {pkg}")]
+    Unresolvable { pkg: SmolStr, dep: Depend },
+    #[error("{pkg} and {other} are in conflict")]
+    Conflict { pkg: SmolStr, other: SmolStr },
+}
+
+/// What installing a package does to the installed version
+#[derive(Debug, Clone, Copy)]
+pub enum PlanAction<'a> {
+    Install,
+    Upgrade(&'a Pkg),
+    Downgrade(&'a Pkg),
+    Reinstall(&'a Pkg),
+}
+
+/// Why a package is part of the transaction
+#[derive(Debug, Clone)]
+pub enum PlanReason {
+    Target,
+    /// Pulled in to satisfy a dependency of another package in the transaction
+    Dependency {
+        of: SmolStr,
+        dep: Depend,
+    },
+}
+
+/// A package to be installed from a sync database
+#[derive(Debug, Clone)]
+pub struct PlannedPkg<'a> {
+    pub pkg: &'a Pkg,
+    pub repo: &'a str,
+    pub action: PlanAction<'a>,
+    pub reason: PlanReason,
+}
+
+impl PlannedPkg<'_> {
+    /// The install reason the package will be recorded with
+    ///
+    /// Upgrades keep the reason of the installed package.
+    #[must_use]
+    pub fn install_reason(&self) -> InstallReason {
+        match (self.action, &self.reason) {
+            (
+                PlanAction::Upgrade(old) | PlanAction::Downgrade(old) | PlanAction::Reinstall(old),
+                _,
+            ) => old.desc.install_reason,
+            (PlanAction::Install, PlanReason::Target) => InstallReason::Explicit,
+            (PlanAction::Install, PlanReason::Dependency { .. }) => InstallReason::Dep,
+        }
+    }
+}
+
+/// Why an installed package is removed by the transaction
+#[derive(Debug, Clone)]
+pub enum RemovalReason {
+    /// The package is listed in the `replaces` of a package being installed
+    Replaced { by: SmolStr },
+    /// The package conflicts with a package being installed, or the other way around
+    Conflict { with: SmolStr, conflict: Depend },
+}
+
+#[derive(Debug, Clone)]
+pub struct Removal<'a> {
+    pub pkg: &'a Pkg,
+    pub reason: RemovalReason,
+}
+
+/// The provider picked for a dependency that no package of that name satisfies
+#[derive(Debug, Clone)]
+pub struct ProviderChoice<'a> {
+    pub dep: Depend,
+    pub chosen: &'a Pkg,
+    /// Other packages of the sync databases that would also satisfy it
+    pub alternatives: Vec<&'a Pkg>,
+}
+
+/// The outcome of a simulated transaction
+#[derive(Debug, Clone, Default)]
+pub struct InstallPlan<'a> {
+    /// Packages to install, with dependencies before the packages needing them
+    pub install: Vec<PlannedPkg<'a>>,
+    pub remove: Vec<Removal<'a>>,
+    pub providers: Vec<ProviderChoice<'a>>,
+    /// Targets skipped because they are already installed in the same version
+    pub up_to_date: Vec<&'a Pkg>,
+}
+
+impl InstallPlan<'_> {
+    /// Total compressed size of the packages to install
+    #[must_use]
+    pub fn download_size(&self) -> u64 {
+        self.install
+            .iter()
+            .map(|planned| planned.pkg.desc.c_size)
+            .sum()
+    }
+    /// Change of the installed size, in bytes
+    #[must_use]
+    pub fn size_delta(&self) -> i64 {
+        let size = |pkg: &Pkg| i64::try_from(pkg.desc.size).unwrap_or(i64::MAX);
+        let installed: i64 = self.install.iter().map(|planned| size(planned.pkg)).sum();
+        let replaced: i64 = self
+            .install
+            .iter()
+            .filter_map(|planned| match planned.action {
+                PlanAction::Install => None,
+                PlanAction::Upgrade(old)
+                | PlanAction::Downgrade(old)
+                | PlanAction::Reinstall(old) => Some(size(old)),
+            })
+            .sum();
+        let removed: i64 = self.remove.iter().map(|removal| size(removal.pkg)).sum();
+        installed - replaced - removed
+    }
+}
+
+/// Computes what installing a set of targets would do
+///
+/// Like pacman, the sync databases are searched in order, so the first
+/// repository containing a package wins.
+pub struct Planner<'a> {
+    local: &'a [Pkg],
+    sync_dbs: &'a [SyncDb],
+    needed: bool,
+    /// Preferred provider for each dependency name
+    providers: BTreeMap<SmolStr, SmolStr>,
+}
+
+impl<'a> Planner<'a> {
+    #[must_use]
+    pub fn new(local: &'a [Pkg], sync_dbs: &'a [SyncDb]) -> Self {
+        Self {
+            local,
+            sync_dbs,
+            needed: false,
+            providers: BTreeMap::new(),
+        }
+    }
+    /// Skip targets that are already installed in the same version (`--needed`)
+    #[must_use]
+    pub fn needed(mut self, needed: bool) -> Self {
+        self.needed = needed;
+        self
+    }
+    /// Prefer the package `pkg` when a provider of `name` has to be chosen
+    ///
+    /// Without a preference, the first provider in database order is chosen,
+    /// like pacman does with `--noconfirm`.
+    #[must_use]
+    pub fn provider(mut self, name: &str, pkg: &str) -> Self {
+        self.providers.insert(name.into(), pkg.into());
+        self
+    }
+    /// Plan the installation of `targets`
+    ///
+    /// Targets are package names, optionally prefixed with a repository (`core/pacman`)
+    /// or with a version requirement (`bash>=5`).
+    /// Targets can also be satisfied by a provider, like dependencies.
+    pub fn install(&self, targets: &[&str]) -> Result<InstallPlan<'a>, PlanError> {
+        let mut state = State {
+            planner: self,
+            plan: InstallPlan::default(),
+            planned: BTreeMap::new(),
+        };
+        for target in targets {
+            let (repo, target) = match target.split_once('/') {
+                Some((repo, target)) => (Some(repo), target),
+                None => (None, *target),
+            };
+            let dep = Depend::parse(target);
+            let (pkg, repo) = match repo {
+                Some(repo) => self
+                    .sync_dbs
+                    .iter()
+                    .filter(|db| db.name == repo)
+                    .find_map(|db| {
+                        let set = ver_set(&dep);
+                        db.pkgs
+                            .iter()
+                            .find(|pkg| set.matches(&dep.name, &pkg.desc))
+                            .map(|pkg| (pkg, db.name.as_str()))
+                    }),
+                None => state.find_satisfier(&dep),
+            }
+            .ok_or_else(|| PlanError::TargetNotFound(target.into()))?;
+            if self.needed
+                && let Some(local) = self.local_pkg(&pkg.desc.name)
+                && local.desc.version == pkg.desc.version
+            {
+                state.plan.up_to_date.push(local);
+                continue;
+            }
+            state.add(pkg, repo, PlanReason::Target)?;
+        }
+        state.find_removals()?;
+        state.check_deps()?;
+        Ok(state.plan)
+    }
+    fn local_pkg(&self, name: &str) -> Option<&'a Pkg> {
+        self.local.iter().find(|pkg| pkg.desc.name == name)
+    }
+}
+
+fn ver_set(dep: &Depend) -> VersionSet {
+    dep.ver
+        .as_ref()
+        .map_or_else(VersionSet::any, VersionSet::from)
+}
+
+struct State<'p, 'a> {
+    planner: &'p Planner<'a>,
+    plan: InstallPlan<'a>,
+    /// Packages added to the plan so far, including ones whose dependencies
+    /// are still being resolved
+    planned: BTreeMap<SmolStr, &'a Pkg>,
+}
+
+impl<'a> State<'_, 'a> {
+    /// Add `pkg` to the plan, after the packages needed for its dependencies
+    fn add(&mut self, pkg: &'a Pkg, repo: &'a str, reason: PlanReason) -> Result<(), PlanError> {
+        if self.planned.contains_key(&pkg.desc.name) {
+            // A target that was already pulled in as a dependency is still a target
+            if matches!(reason, PlanReason::Target)
+                && let Some(planned) = self
+                    .plan
+                    .install
+                    .iter_mut()
+                    .find(|planned| planned.pkg.desc.name == pkg.desc.name)
+            {
+                planned.reason = reason;
+            }
+            return Ok(());
+        }
+        self.planned.insert(pkg.desc.name.clone(), pkg);
+        for dep in &pkg.desc.depends {
+            if self.is_satisfied(dep) {
+                continue;
+            }
+            let (dep_pkg, dep_repo) =
+                self.find_satisfier(dep)
+                    .ok_or_else(|| PlanError::Unresolvable {
+                        pkg: pkg.desc.name.clone(),
+                        dep: dep.clone(),
+                    })?;
+            let reason = PlanReason::Dependency {
+                of: pkg.desc.name.clone(),
+                dep: dep.clone(),
+            };
+            self.add(dep_pkg, dep_repo, reason)?;
+        }
+        let action = match self.planner.local_pkg(&pkg.desc.name) {
+            None => PlanAction::Install,
+            Some(old) => match old.desc.version.cmp(&pkg.desc.version) {
+                Ordering::Less => PlanAction::Upgrade(old),
+                Ordering::Equal => PlanAction::Reinstall(old),
+                Ordering::Greater => PlanAction::Downgrade(old),
+            },
+        };
+        self.plan.install.push(PlannedPkg {
+            pkg,
+            repo,
+            action,
+            reason,
+        });
+        Ok(())
+    }
+    /// Installed packages that stay installed, unless they are removed
+    fn kept_local(&self) -> impl Iterator<Item = &'a Pkg> {
+        self.planner
+            .local
+            .iter()
+            .filter(|pkg| !self.planned.contains_key(&pkg.desc.name))
+    }
+    /// Whether a planned package or an installed package that isn't being
+    /// replaced satisfies `dep`
+    fn is_satisfied(&self, dep: &Depend) -> bool {
+        let set = ver_set(dep);
+        self.planned
+            .values()
+            .copied()
+            .chain(self.kept_local())
+            .any(|pkg| set.matches(&dep.name, &pkg.desc))
+    }
+    /// Find a sync package for `dep`, preferring a package with that name,
+    /// then the preferred provider, then the first provider
+    fn find_satisfier(&mut self, dep: &Depend) -> Option<(&'a Pkg, &'a str)> {
+        let set = ver_set(dep);
+        let by_name = self
+            .sync_pkgs()
+            .into_iter()
+            .find(|(pkg, _)| pkg.desc.name == dep.name && set.contains(&pkg.desc.version));
+        if by_name.is_some() {
+            return by_name;
+        }
+        let providers: Vec<_> = self
+            .sync_pkgs()
+            .into_iter()
+            .filter(|(pkg, _)| set.matches(&dep.name, &pkg.desc))
+            .collect();
+        let preferred = self.planner.providers.get(&dep.name);
+        let chosen = preferred
+            .and_then(|name| providers.iter().find(|(pkg, _)| pkg.desc.name == *name))
+            .or_else(|| providers.first())
+            .copied()?;
+        self.plan.providers.push(ProviderChoice {
+            dep: dep.clone(),
+            chosen: chosen.0,
+            alternatives: providers
+                .iter()
+                .map(|(pkg, _)| *pkg)
+                .filter(|pkg| !std::ptr::eq(*pkg, chosen.0))
+                .collect(),
+        });
+        Some(chosen)
+    }
+    /// Packages of the sync databases in order, skipping names already seen in earlier ones
+    fn sync_pkgs(&self) -> Vec<(&'a Pkg, &'a str)> {
+        let mut seen = BTreeSet::new();
+        let mut pkgs = Vec::new();
+        for db in self.planner.sync_dbs {
+            for pkg in &db.pkgs {
+                if seen.insert(&pkg.desc.name) {
+                    pkgs.push((pkg, db.name.as_str()));
+                }
+            }
+        }
+        pkgs
+    }
+    /// Remove installed packages that are replaced by or conflict with planned packages
+    fn find_removals(&mut self) -> Result<(), PlanError> {
+        let mut removals = Vec::new();
+        for planned in &self.plan.install {
+            let pkg = planned.pkg;
+            for replaced in &pkg.desc.replaces {
+                let replaced = Depend::parse(replaced);
+                let set = ver_set(&replaced);
+                for local in self.kept_local() {
+                    if local.desc.name == replaced.name && set.contains(&local.desc.version) {
+                        removals.push(Removal {
+                            pkg: local,
+                            reason: RemovalReason::Replaced {
+                                by: pkg.desc.name.clone(),
+                            },
+                        });
+                    }
+                }
+            }
+            for conflict in &pkg.desc.conflicts {
+                let conflict = Depend::parse(conflict);
+                let set = ver_set(&conflict);
+                for other in &self.plan.install {
+                    if !std::ptr::eq(other.pkg, pkg) && set.matches(&conflict.name, &other.pkg.desc)
+                    {
+                        return Err(PlanError::Conflict {
+                            pkg: pkg.desc.name.clone(),
+                            other: other.pkg.desc.name.clone(),
+                        });
+                    }
+                }
+                for local in self.kept_local() {
+                    if set.matches(&conflict.name, &local.desc) {
+                        removals.push(Removal {
+                            pkg: local,
+                            reason: RemovalReason::Conflict {
+                                with: pkg.desc.name.clone(),
+                                conflict: conflict.clone(),
+                            },
+                        });
+                    }
+                }
+            }
+        }
+        for local in self.kept_local() {
+            for conflict in &local.desc.conflicts {
+                let conflict = Depend::parse(conflict);
+                let set = ver_set(&conflict);
+                if let Some(planned) = self
+                    .plan
+                    .install
+                    .iter()
+                    .find(|planned| set.matches(&conflict.name, &planned.pkg.desc))
+                {
+                    removals.push(Removal {
+                        pkg: local,
+                        reason: RemovalReason::Conflict {
+                            with: planned.pkg.desc.name.clone(),
+                            conflict,
+                        },
+                    });
+                }
+            }
+        }
+        let mut seen = BTreeSet::new();
+        removals.retain(|removal| seen.insert(removal.pkg.desc.name.clone()));
+        self.plan.remove = removals;
+        Ok(())
+    }
+    /// Check that the transaction doesn't leave any package with unmet dependencies
+    ///
+    /// Dependencies of installed packages that were already unmet are ignored.
+    fn check_deps(&self) -> Result<(), PlanError> {
+        let removed: BTreeSet<&str> = self
+            .plan
+            .remove
+            .iter()
+            .map(|removal| removal.pkg.desc.name.as_str())
+            .collect();
+        let result: Vec<&Pkg> = self
+            .planned
+            .values()
+            .copied()
+            .chain(self.kept_local())
+            .filter(|pkg| !removed.contains(pkg.desc.name.as_str()))
+            .collect();
+        for pkg in &result {
+            let is_planned = self.planned.contains_key(&pkg.desc.name);
+            for dep in &pkg.desc.depends {
+                let set = ver_set(dep);
+                let met_by = |pkg: &&Pkg| set.matches(&dep.name, &pkg.desc);
+                if !result.iter().any(met_by)
+                    && (is_planned || self.planner.local.iter().any(|pkg| met_by(&pkg)))
+                {
+                    return Err(PlanError::Unresolvable {
+                        pkg: pkg.desc.name.clone(),
+                        dep: dep.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why a package is removed by a removal transaction
+#[derive(Debug, Clone)]
+pub enum RemoveReason {
+    Target,
+    /// Depends on a removed package (`--cascade`)
+    Dependent {
+        of: SmolStr,
+        dep: Depend,
+    },
+    /// A dependency of a removed package that nothing else needs (`--recursive`)
+    Unneeded {
+        dep_of: SmolStr,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct PlannedRemoval<'a> {
+    pub pkg: &'a Pkg,
+    pub reason: RemoveReason,
+}
+
+/// An installed package that would be left with an unmet dependency
+#[derive(Debug, Clone)]
+pub struct Blocker<'a> {
+    pub pkg: &'a Pkg,
+    pub dep: &'a Depend,
+    /// The removed package that satisfied the dependency
+    pub removed: &'a Pkg,
+}
+
+/// The outcome of a simulated removal
+#[derive(Debug, Clone, Default)]
+pub struct RemovePlan<'a> {
+    pub remove: Vec<PlannedRemoval<'a>>,
+    /// Packages that would be left with unmet dependencies, which makes the removal fail
+    pub blockers: Vec<Blocker<'a>>,
+    /// Targets kept because other packages need them (`--unneeded`)
+    pub kept: Vec<&'a Pkg>,
+    /// Modified backup files that would be saved as `.pacsave`
+    pub pacsave: Vec<(&'a Pkg, &'a Backup)>,
+}
+
+impl RemovePlan<'_> {
+    /// Whether the removal can go ahead
+    #[must_use]
+    pub fn is_possible(&self) -> bool {
+        self.blockers.is_empty()
+    }
+    /// Installed size freed by the removal
+    #[must_use]
+    pub fn freed_size(&self) -> u64 {
+        self.remove
+            .iter()
+            .map(|removal| removal.pkg.desc.size)
+            .sum()
+    }
+}
+
+/// Computes what removing a set of installed packages would do
+// The flags mirror pacman's independent command line options
+#[expect(clippy::struct_excessive_bools)]
+pub struct RemovePlanner<'a> {
+    local: &'a [Pkg],
+    cascade: bool,
+    recursive: bool,
+    unneeded: bool,
+    nosave: bool,
+    root: PathBuf,
+}
+
+impl<'a> RemovePlanner<'a> {
+    #[must_use]
+    pub fn new(local: &'a [Pkg]) -> Self {
+        Self {
+            local,
+            cascade: false,
+            recursive: false,
+            unneeded: false,
+            nosave: false,
+            root: PathBuf::from("/"),
+        }
+    }
+    /// Also remove the packages depending on removed packages (`--cascade`)
+    #[must_use]
+    pub fn cascade(mut self, cascade: bool) -> Self {
+        self.cascade = cascade;
+        self
+    }
+    /// Also remove dependencies that were not explicitly installed
+    /// and that nothing else needs (`--recursive`)
+    #[must_use]
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+    /// Keep targets that other packages need, instead of failing (`--unneeded`)
+    #[must_use]
+    pub fn unneeded(mut self, unneeded: bool) -> Self {
+        self.unneeded = unneeded;
+        self
+    }
+    /// Remove modified backup files instead of saving them as `.pacsave` (`--nosave`)
+    #[must_use]
+    pub fn nosave(mut self, nosave: bool) -> Self {
+        self.nosave = nosave;
+        self
+    }
+    /// Root directory the backup files are checked under
+    #[must_use]
+    pub fn root(mut self, root: impl AsRef<Path>) -> Self {
+        root.as_ref().clone_into(&mut self.root);
+        self
+    }
+    /// Plan the removal of the installed packages named `targets`
+    pub fn remove(&self, targets: &[&str]) -> Result<RemovePlan<'a>, PlanError> {
+        let mut plan = RemovePlan::default();
+        for target in targets {
+            let pkg = self
+                .local
+                .iter()
+                .find(|pkg| pkg.desc.name == *target)
+                .ok_or_else(|| PlanError::TargetNotFound((*target).into()))?;
+            if !plan
+                .remove
+                .iter()
+                .any(|removal| std::ptr::eq(removal.pkg, pkg))
+            {
+                plan.remove.push(PlannedRemoval {
+                    pkg,
+                    reason: RemoveReason::Target,
+                });
+            }
+        }
+        if self.unneeded {
+            loop {
+                let blockers = self.blockers(&plan.remove);
+                let before = plan.remove.len();
+                plan.remove.retain(|removal| {
+                    let needed = blockers
+                        .iter()
+                        .any(|blocker| std::ptr::eq(blocker.removed, removal.pkg));
+                    if needed {
+                        plan.kept.push(removal.pkg);
+                    }
+                    !needed
+                });
+                if plan.remove.len() == before {
+                    break;
+                }
+            }
+        }
+        if self.cascade {
+            loop {
+                let blockers = self.blockers(&plan.remove);
+                if blockers.is_empty() {
+                    break;
+                }
+                for blocker in blockers {
+                    if !plan
+                        .remove
+                        .iter()
+                        .any(|removal| std::ptr::eq(removal.pkg, blocker.pkg))
+                    {
+                        plan.remove.push(PlannedRemoval {
+                            pkg: blocker.pkg,
+                            reason: RemoveReason::Dependent {
+                                of: blocker.removed.desc.name.clone(),
+                                dep: blocker.dep.clone(),
+                            },
+                        });
+                    }
+                }
+            }
+        }
+        if self.recursive {
+            self.add_unneeded_deps(&mut plan.remove);
+        }
+        plan.blockers = self.blockers(&plan.remove);
+        if !self.nosave {
+            for removal in &plan.remove {
+                for backup in &removal.pkg.backup {
+                    if backup.status(&self.root) == BackupStatus::Modified {
+                        plan.pacsave.push((removal.pkg, backup));
+                    }
+                }
+            }
+        }
+        Ok(plan)
+    }
+    fn is_removed(removals: &[PlannedRemoval], pkg: &Pkg) -> bool {
+        removals
+            .iter()
+            .any(|removal| std::ptr::eq(removal.pkg, pkg))
+    }
+    /// Dependencies of kept packages that only removed packages satisfy
+    ///
+    /// Dependencies that were already unmet are ignored.
+    fn blockers(&self, removals: &[PlannedRemoval<'a>]) -> Vec<Blocker<'a>> {
+        let mut blockers = Vec::new();
+        let kept = || {
+            self.local
+                .iter()
+                .filter(|pkg| !Self::is_removed(removals, pkg))
+        };
+        for pkg in kept() {
+            for dep in &pkg.desc.depends {
+                let set = ver_set(dep);
+                if kept().any(|other| set.matches(&dep.name, &other.desc)) {
+                    continue;
+                }
+                if let Some(removal) = removals
+                    .iter()
+                    .find(|removal| set.matches(&dep.name, &removal.pkg.desc))
+                {
+                    blockers.push(Blocker {
+                        pkg,
+                        dep,
+                        removed: removal.pkg,
+                    });
+                }
+            }
+        }
+        blockers
+    }
+    /// Add the dependencies of removed packages that were installed as dependencies
+    /// and aren't needed by any kept package, until there are no more
+    fn add_unneeded_deps(&self, removals: &mut Vec<PlannedRemoval<'a>>) {
+        let mut i = 0;
+        while let Some(removal) = removals.get(i) {
+            let removed = removal.pkg;
+            for dep in &removed.desc.depends {
+                let set = ver_set(dep);
+                let Some(dep_pkg) = self
+                    .local
+                    .iter()
+                    .find(|pkg| set.matches(&dep.name, &pkg.desc))
+                else {
+                    continue;
+                };
+                if dep_pkg.desc.install_reason == InstallReason::Explicit
+                    || Self::is_removed(removals, dep_pkg)
+                {
+                    continue;
+                }
+                let needed = self.local.iter().any(|pkg| {
+                    !std::ptr::eq(pkg, dep_pkg)
+                        && !Self::is_removed(removals, pkg)
+                        && pkg
+                            .desc
+                            .depends
+                            .iter()
+                            .any(|dep| ver_set(dep).matches(&dep.name, &dep_pkg.desc))
+                });
+                if !needed {
+                    removals.push(PlannedRemoval {
+                        pkg: dep_pkg,
+                        reason: RemoveReason::Unneeded {
+                            dep_of: removed.desc.name.clone(),
+                        },
+                    });
+                }
+            }
+            i += 1;
+        }
+    }
+}
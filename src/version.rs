@@ -0,0 +1,256 @@
+use {
+    smol_str::SmolStr,
+    std::{
+        cmp::Ordering,
+        fmt,
+        hash::{Hash, Hasher},
+        str::FromStr,
+    },
+};
+
+/// A package version, `[epoch:]pkgver[-pkgrel]`
+///
+/// Ordering follows [`vercmp`], except that a version without a pkgrel is older
+/// than the same version with one, which keeps the ordering total.
+/// Dependency version requirements still ignore a missing pkgrel, like alpm does.
+#[derive(Debug, Clone)]
+pub struct Version(SmolStr);
+
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid version: {0:?}")]
+pub struct InvalidVersion(pub SmolStr);
+
+impl Version {
+    /// Wrap a version without validating it, for versions read from package metadata
+    pub(crate) fn new(version: impl Into<SmolStr>) -> Self {
+        Self(version.into())
+    }
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+    /// The epoch, if there is one
+    #[must_use]
+    pub fn epoch(&self) -> Option<&str> {
+        split_evr(&self.0).0
+    }
+    /// The upstream version
+    #[must_use]
+    pub fn pkgver(&self) -> &str {
+        split_evr(&self.0).1
+    }
+    /// The release number, which may have a sub-release, like `2.1`
+    #[must_use]
+    pub fn pkgrel(&self) -> Option<&str> {
+        split_evr(&self.0).2
+    }
+}
+
+impl FromStr for Version {
+    type Err = InvalidVersion;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let version = Self::new(s);
+        let (epoch, pkgver, pkgrel) = split_evr(s);
+        // A colon is only allowed after a non-empty epoch, and a dash only before the pkgrel
+        let valid = !s.contains(char::is_whitespace)
+            && epoch.is_some() == s.contains(':')
+            && !pkgver.is_empty()
+            && !pkgver.contains(['-', ':'])
+            && pkgrel.is_none_or(|pkgrel| !pkgrel.is_empty() && !pkgrel.contains(':'));
+        if valid {
+            Ok(version)
+        } else {
+            Err(InvalidVersion(version.0))
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (epoch_a, ver_a, rel_a) = split_evr(&self.0);
+        let (epoch_b, ver_b, rel_b) = split_evr(&other.0);
+        rpmvercmp(epoch_a.unwrap_or("0"), epoch_b.unwrap_or("0"))
+            .then_with(|| rpmvercmp(ver_a, ver_b))
+            .then_with(|| match (rel_a, rel_b) {
+                (Some(rel_a), Some(rel_b)) => rpmvercmp(rel_a, rel_b),
+                _ => rel_a.is_some().cmp(&rel_b.is_some()),
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Version {}
+
+impl Hash for Version {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let (epoch, ver, rel) = split_evr(&self.0);
+        hash_segments(epoch.unwrap_or("0"), state);
+        hash_segments(ver, state);
+        rel.is_some().hash(state);
+        if let Some(rel) = rel {
+            hash_segments(rel, state);
+        }
+    }
+}
+
+/// Hash the parts of a version that [`rpmvercmp`] looks at, so that versions
+/// comparing equal hash the same
+fn hash_segments<H: Hasher>(s: &str, state: &mut H) {
+    let mut rest = s.as_bytes();
+    loop {
+        let separator_len = rest
+            .iter()
+            .take_while(|c| !c.is_ascii_alphanumeric())
+            .count();
+        rest = &rest[separator_len..];
+        if rest.is_empty() {
+            // Trailing separators only matter against a version without them
+            (separator_len != 0).hash(state);
+            return;
+        }
+        let is_num = rest[0].is_ascii_digit();
+        let segment_len = rest
+            .iter()
+            .take_while(|c| {
+                if is_num {
+                    c.is_ascii_digit()
+                } else {
+                    c.is_ascii_alphabetic()
+                }
+            })
+            .count();
+        let mut segment = &rest[..segment_len];
+        if is_num {
+            while let [b'0', tail @ ..] = segment {
+                segment = tail;
+            }
+        }
+        (separator_len, is_num, segment).hash(state);
+        rest = &rest[segment_len..];
+    }
+}
+
+/// Compare two package versions, like `alpm_pkg_vercmp`
+///
+/// Versions are of the form `[epoch:]pkgver[-pkgrel]`.
+/// The epoch defaults to 0, and the pkgrel is only compared if both versions have one.
+#[must_use]
+pub fn vercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+    let (epoch_a, ver_a, rel_a) = split_evr(a);
+    let (epoch_b, ver_b, rel_b) = split_evr(b);
+    rpmvercmp(epoch_a.unwrap_or("0"), epoch_b.unwrap_or("0"))
+        .then_with(|| rpmvercmp(ver_a, ver_b))
+        .then_with(|| match (rel_a, rel_b) {
+            (Some(rel_a), Some(rel_b)) => rpmvercmp(rel_a, rel_b),
+            _ => Ordering::Equal,
+        })
+}
+
+/// Split a version into epoch, pkgver and pkgrel
+pub(crate) fn split_evr(evr: &str) -> (Option<&str>, &str, Option<&str>) {
+    let digits_end = evr.find(|c: char| !c.is_ascii_digit()).unwrap_or(evr.len());
+    let (epoch, rest) = match evr[digits_end..].strip_prefix(':') {
+        Some(rest) if digits_end == 0 => (None, rest),
+        Some(rest) => (Some(&evr[..digits_end]), rest),
+        None => (None, evr),
+    };
+    match rest.rsplit_once('-') {
+        Some((ver, rel)) => (epoch, ver, Some(rel)),
+        None => (epoch, rest, None),
+    }
+}
+
+/// Segment-wise version comparison, as done by rpm and alpm
+pub(crate) fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut one, mut two) = (0, 0);
+    while one < a.len() && two < b.len() {
+        let (sep_start_one, sep_start_two) = (one, two);
+        while one < a.len() && !a[one].is_ascii_alphanumeric() {
+            one += 1;
+        }
+        while two < b.len() && !b[two].is_ascii_alphanumeric() {
+            two += 1;
+        }
+        if one == a.len() || two == b.len() {
+            break;
+        }
+        // Differing separator lengths decide the comparison
+        let (sep_one, sep_two) = (one - sep_start_one, two - sep_start_two);
+        if sep_one != sep_two {
+            return sep_one.cmp(&sep_two);
+        }
+        let is_num = a[one].is_ascii_digit();
+        let class: fn(&u8) -> bool = if is_num {
+            u8::is_ascii_digit
+        } else {
+            u8::is_ascii_alphabetic
+        };
+        let seg_end =
+            |s: &[u8], start: usize| start + s[start..].iter().take_while(|c| class(c)).count();
+        let (end_one, end_two) = (seg_end(a, one), seg_end(b, two));
+        // Numeric segments are always newer than alpha segments
+        if end_two == two {
+            return if is_num {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+        let (mut lhs, mut rhs) = (&a[one..end_one], &b[two..end_two]);
+        if is_num {
+            while let [b'0', rest @ ..] = lhs {
+                lhs = rest;
+            }
+            while let [b'0', rest @ ..] = rhs {
+                rhs = rest;
+            }
+            let len_ord = lhs.len().cmp(&rhs.len());
+            if len_ord.is_ne() {
+                return len_ord;
+            }
+        }
+        let ord = lhs.cmp(rhs);
+        if ord.is_ne() {
+            return ord;
+        }
+        one = end_one;
+        two = end_two;
+    }
+    let (rest_one, rest_two) = (&a[one..], &b[two..]);
+    if rest_one.is_empty() && rest_two.is_empty() {
+        return Ordering::Equal;
+    }
+    // A remaining alpha segment never beats an empty string
+    if (rest_one.is_empty() && !rest_two[0].is_ascii_alphabetic())
+        || rest_one.first().is_some_and(u8::is_ascii_alphabetic)
+    {
+        Ordering::Less
+    } else {
+        Ordering::Greater
+    }
+}
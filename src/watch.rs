@@ -0,0 +1,259 @@
+//! Change notifications for the local and sync databases, using inotify
+
+use {
+    crate::{DbReadError, Pkg, read_db_archive, read_local_entry, version::Version},
+    inotify::{EventMask, Inotify, WatchDescriptor, WatchMask},
+    smol_str::SmolStr,
+    std::{
+        cmp::Ordering,
+        collections::{BTreeMap, BTreeSet},
+        path::{Path, PathBuf},
+    },
+};
+
+/// A change to the database, as observed by [`DbWatcher`]
+#[derive(Debug)]
+pub enum DbEvent {
+    Installed(Pkg),
+    Removed {
+        name: SmolStr,
+        version: Version,
+    },
+    Upgraded {
+        old_version: Version,
+        pkg: Pkg,
+    },
+    Downgraded {
+        old_version: Version,
+        pkg: Pkg,
+    },
+    /// The entry was replaced by one with the same version
+    Reinstalled(Pkg),
+    /// A sync database was replaced, e.g. by `pacman -Sy`
+    RepoRefreshed {
+        repo: SmolStr,
+        pkgs: Vec<Pkg>,
+    },
+}
+
+impl DbEvent {
+    /// The event for the entry of the package `name` changing from `old_version`
+    /// to `pkg`, where `None` means there was no entry
+    #[must_use]
+    pub fn new(name: &str, old_version: Option<Version>, pkg: Option<Pkg>) -> Option<Self> {
+        Some(match (old_version, pkg) {
+            (None, None) => return None,
+            (Some(version), None) => Self::Removed {
+                name: name.into(),
+                version,
+            },
+            (None, Some(pkg)) => Self::Installed(pkg),
+            (Some(old_version), Some(pkg)) => match old_version.cmp(&pkg.desc.version) {
+                Ordering::Less => Self::Upgraded { old_version, pkg },
+                Ordering::Greater => Self::Downgraded { old_version, pkg },
+                Ordering::Equal => Self::Reinstalled(pkg),
+            },
+        })
+    }
+}
+
+/// Watches a pacman database for changes
+///
+/// Changes are collected until pacman releases its lock (`db.lck`),
+/// then only the affected entries are re-read.
+/// Entries written without holding the lock should be renamed into place as
+/// whole directories, like [`crate::write::LocalDbWriter`] does, so that no
+/// entry is read half written.
+pub struct DbWatcher {
+    inotify: Inotify,
+    db_path: PathBuf,
+    local_wd: WatchDescriptor,
+    sync_wd: Option<WatchDescriptor>,
+    /// Installed version of each package, by name
+    installed: BTreeMap<SmolStr, Version>,
+    /// Packages with changed entries since the last batch of events
+    touched_pkgs: BTreeSet<SmolStr>,
+    /// Sync databases replaced since the last batch of events
+    touched_repos: BTreeSet<SmolStr>,
+    buffer: Vec<u8>,
+}
+
+/// Split a local entry directory name `{name}-{pkgver}-{pkgrel}` into name and version
+#[must_use]
+pub fn split_entry_name(dir_name: &str) -> Option<(&str, &str)> {
+    let name = dir_name.rsplitn(3, '-').nth(2)?;
+    Some((name, &dir_name[name.len() + 1..]))
+}
+
+/// Entries of the local database, as (name, version, path)
+fn local_entries(db_path: &Path) -> std::io::Result<Vec<(SmolStr, Version, PathBuf)>> {
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(db_path.join("local"))? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let Some(file_name) = file_name.to_str() else {
+            continue;
+        };
+        // Package names can't start with a dot, so hidden directories aren't entries
+        if file_name.starts_with('.') || !entry.file_type()?.is_dir() {
+            continue;
+        }
+        if let Some((name, version)) = split_entry_name(file_name) {
+            entries.push((name.into(), Version::new(version), entry.path()));
+        }
+    }
+    Ok(entries)
+}
+
+impl DbWatcher {
+    /// Watch the database at `/var/lib/pacman`
+    pub fn new() -> std::io::Result<Self> {
+        Self::with_db_path("/var/lib/pacman")
+    }
+    /// Watch the database under a custom database path
+    pub fn with_db_path(db_path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let db_path = db_path.as_ref().to_owned();
+        let inotify = Inotify::init()?;
+        let mut watches = inotify.watches();
+        // To notice the lock being released
+        watches.add(&db_path, WatchMask::DELETE | WatchMask::ONLYDIR)?;
+        let local_wd = watches.add(
+            db_path.join("local"),
+            WatchMask::CREATE
+                | WatchMask::DELETE
+                | WatchMask::MOVED_FROM
+                | WatchMask::MOVED_TO
+                | WatchMask::ONLYDIR,
+        )?;
+        let sync_dir = db_path.join("sync");
+        let sync_wd = if sync_dir.is_dir() {
+            Some(watches.add(
+                sync_dir,
+                WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::ONLYDIR,
+            )?)
+        } else {
+            None
+        };
+        let installed = local_entries(&db_path)?
+            .into_iter()
+            .map(|(name, version, _)| (name, version))
+            .collect();
+        Ok(Self {
+            inotify,
+            db_path,
+            local_wd,
+            sync_wd,
+            installed,
+            touched_pkgs: BTreeSet::new(),
+            touched_repos: BTreeSet::new(),
+            buffer: vec![0; 64 * 1024],
+        })
+    }
+    /// Block until the database changes, and return the changes
+    ///
+    /// If pacman holds the lock, this waits until it is released.
+    pub fn wait(&mut self) -> Result<Vec<DbEvent>, DbReadError> {
+        loop {
+            let mut overflowed = false;
+            for event in self.inotify.read_events_blocking(&mut self.buffer)? {
+                if event.mask.contains(EventMask::Q_OVERFLOW) {
+                    overflowed = true;
+                    continue;
+                }
+                let Some(name) = event.name.and_then(|name| name.to_str()) else {
+                    continue;
+                };
+                if event.wd == self.local_wd {
+                    // Hidden directories can't be entries, but other tools may use them
+                    if event.mask.contains(EventMask::ISDIR)
+                        && !name.starts_with('.')
+                        && let Some((pkg_name, _)) = split_entry_name(name)
+                    {
+                        self.touched_pkgs.insert(pkg_name.into());
+                    }
+                } else if self.sync_wd.as_ref() == Some(&event.wd)
+                    && let Some(repo) = name.strip_suffix(".db")
+                {
+                    self.touched_repos.insert(repo.into());
+                }
+            }
+            if overflowed {
+                self.touch_all()?;
+            }
+            let pending = !self.touched_pkgs.is_empty() || !self.touched_repos.is_empty();
+            if pending && !self.db_path.join("db.lck").exists() {
+                let events = self.collect_events()?;
+                if !events.is_empty() {
+                    return Ok(events);
+                }
+            }
+        }
+    }
+    /// Mark everything as changed, after events were lost
+    fn touch_all(&mut self) -> std::io::Result<()> {
+        self.touched_pkgs.extend(self.installed.keys().cloned());
+        for (name, _, _) in local_entries(&self.db_path)? {
+            self.touched_pkgs.insert(name);
+        }
+        let sync_dir = self.db_path.join("sync");
+        if self.sync_wd.is_some() {
+            for entry in std::fs::read_dir(sync_dir)? {
+                if let Some(repo) = entry?
+                    .file_name()
+                    .to_str()
+                    .and_then(|n| n.strip_suffix(".db"))
+                {
+                    self.touched_repos.insert(repo.into());
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Re-read the touched entries and turn them into events
+    ///
+    /// The watcher state is only updated if everything could be read.
+    fn collect_events(&mut self) -> Result<Vec<DbEvent>, DbReadError> {
+        let mut current = BTreeMap::new();
+        for (name, _, path) in local_entries(&self.db_path)? {
+            if self.touched_pkgs.contains(&name) {
+                current.insert(name, path);
+            }
+        }
+        let mut events = Vec::new();
+        for name in &self.touched_pkgs {
+            let pkg = current
+                .get(name)
+                .map(|path| read_local_entry(path))
+                .transpose()?;
+            events.extend(DbEvent::new(name, self.installed.get(name).cloned(), pkg));
+        }
+        let sync_dir = self.db_path.join("sync");
+        for repo in &self.touched_repos {
+            match std::fs::File::open(sync_dir.join(format!("{repo}.db"))) {
+                Ok(file) => events.push(DbEvent::RepoRefreshed {
+                    repo: repo.clone(),
+                    pkgs: read_db_archive(file)?,
+                }),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        for name in std::mem::take(&mut self.touched_pkgs) {
+            self.installed.remove(&name);
+        }
+        self.touched_repos.clear();
+        for event in &events {
+            match event {
+                DbEvent::Installed(pkg)
+                | DbEvent::Upgraded { pkg, .. }
+                | DbEvent::Downgraded { pkg, .. }
+                | DbEvent::Reinstalled(pkg) => {
+                    self.installed
+                        .insert(pkg.desc.name.clone(), pkg.desc.version.clone());
+                }
+                DbEvent::Removed { .. } | DbEvent::RepoRefreshed { .. } => {}
+            }
+        }
+        Ok(events)
+    }
+}
@@ -0,0 +1,181 @@
+//! Writing local database entries, for installing packages without pacman
+
+use {
+    crate::{Pkg, SUPPORTED_DB_VERSION},
+    flate2::{Compression, write::GzEncoder},
+    std::{
+        fmt::Write as _,
+        io::Write as _,
+        path::{Path, PathBuf},
+        time::{SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// Extra data of a local database entry that isn't part of [`Pkg`]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EntryExtras<'a> {
+    /// Contents of the package's `.MTREE`.
+    /// Plain text is gzip compressed, gzip data is written as is.
+    pub mtree: Option<&'a [u8]>,
+    /// Contents of the package's `.INSTALL` script
+    pub install: Option<&'a [u8]>,
+}
+
+/// Writes entries into the local database of a target root
+pub struct LocalDbWriter {
+    local_dir: PathBuf,
+    /// Where entries are assembled, next to `local` so that renames stay
+    /// on the same filesystem and readers of `local` never see them
+    staging_dir: PathBuf,
+}
+
+impl LocalDbWriter {
+    /// Create a writer for the local database of the system at `root`
+    ///
+    /// Creates `{root}/var/lib/pacman/local` and its `ALPM_DB_VERSION` file if needed.
+    pub fn new(root: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::with_db_path(root.as_ref().join("var/lib/pacman"))
+    }
+    /// Create a writer for the local database under a custom database path
+    pub fn with_db_path(db_path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let db_path = db_path.as_ref();
+        let local_dir = db_path.join("local");
+        std::fs::create_dir_all(&local_dir)?;
+        let version_path = local_dir.join("ALPM_DB_VERSION");
+        if !version_path.exists() {
+            write_atomic(
+                &version_path,
+                format!("{SUPPORTED_DB_VERSION}\n").as_bytes(),
+            )?;
+        }
+        Ok(Self {
+            local_dir,
+            staging_dir: db_path.join(".alpacka-staging"),
+        })
+    }
+    /// Write the entry for `pkg`, returning the path of the entry directory
+    ///
+    /// The entry is assembled outside of `local` and renamed into place as a whole,
+    /// so readers never observe a partially written entry. Existing entries of the
+    /// package, of any version, are moved out right before, so readers never see
+    /// two versions either.
+    /// `INSTALLDATE` is set to the current time unless `pkg` already has one.
+    pub fn write(&self, pkg: &Pkg, extras: EntryExtras) -> std::io::Result<PathBuf> {
+        let dir_name = format!("{}-{}", pkg.desc.name, pkg.desc.version);
+        let entry_dir = self.local_dir.join(&dir_name);
+        let tmp_dir = self.staging_dir.join(&dir_name);
+        if tmp_dir.exists() {
+            std::fs::remove_dir_all(&tmp_dir)?;
+        }
+        std::fs::create_dir_all(&tmp_dir)?;
+        let mut desc = pkg.desc.clone();
+        if desc.install_date == 0 {
+            desc.install_date = now();
+        }
+        write_synced(&tmp_dir.join("desc"), desc.to_local_desc().as_bytes())?;
+        write_synced(&tmp_dir.join("files"), files_contents(pkg).as_bytes())?;
+        if let Some(mtree) = extras.mtree {
+            if mtree.starts_with(&[0x1f, 0x8b]) {
+                write_synced(&tmp_dir.join("mtree"), mtree)?;
+            } else {
+                let file = std::fs::File::create(tmp_dir.join("mtree"))?;
+                let mut enc = GzEncoder::new(file, Compression::default());
+                enc.write_all(mtree)?;
+                enc.finish()?.sync_all()?;
+            }
+        }
+        if let Some(install) = extras.install {
+            write_synced(&tmp_dir.join("install"), install)?;
+        }
+        sync_dir(&tmp_dir)?;
+        let old_dirs = self.move_out(&pkg.desc.name)?;
+        std::fs::rename(&tmp_dir, &entry_dir)?;
+        sync_dir(&self.local_dir)?;
+        for dir in old_dirs {
+            std::fs::remove_dir_all(dir)?;
+        }
+        Ok(entry_dir)
+    }
+    /// Remove the entry of the package `name`, if there is one
+    pub fn remove(&self, name: &str) -> std::io::Result<()> {
+        let old_dirs = self.move_out(name)?;
+        sync_dir(&self.local_dir)?;
+        for dir in old_dirs {
+            std::fs::remove_dir_all(dir)?;
+        }
+        Ok(())
+    }
+    /// Move the entries of the package `name` from `local` to the staging directory,
+    /// returning their new paths
+    fn move_out(&self, name: &str) -> std::io::Result<Vec<PathBuf>> {
+        std::fs::create_dir_all(&self.staging_dir)?;
+        let mut moved = Vec::new();
+        for entry in std::fs::read_dir(&self.local_dir)? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let Some(file_name) = file_name.to_str() else {
+                continue;
+            };
+            // Entry directories are named `{name}-{pkgver}-{pkgrel}`
+            let entry_name = file_name.rsplitn(3, '-').nth(2);
+            if entry_name == Some(name) && entry.file_type()?.is_dir() {
+                // Hidden, so it can't clash with entries being assembled
+                let old_dir = self.staging_dir.join(format!(".{file_name}"));
+                if old_dir.exists() {
+                    std::fs::remove_dir_all(&old_dir)?;
+                }
+                std::fs::rename(entry.path(), &old_dir)?;
+                moved.push(old_dir);
+            }
+        }
+        Ok(moved)
+    }
+}
+
+/// Contents of the `files` file of a local database entry
+fn files_contents(pkg: &Pkg) -> String {
+    let mut out = String::new();
+    if !pkg.files.is_empty() {
+        out.push_str("%FILES%\n");
+        for file in &pkg.files {
+            let _ = writeln!(out, "{file}");
+        }
+        out.push('\n');
+    }
+    if !pkg.backup.is_empty() {
+        out.push_str("%BACKUP%\n");
+        for backup in &pkg.backup {
+            let _ = writeln!(out, "{}\t{}", backup.path, backup.md5);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Write a file by writing a temporary file next to it, then renaming it into place
+fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let mut file = std::fs::File::create(&tmp_name)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp_name, path)
+}
+
+/// Write a file and flush it to disk
+fn write_synced(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut file = std::fs::File::create(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// Flush a directory to disk, so that renames in it persist
+fn sync_dir(path: &Path) -> std::io::Result<()> {
+    std::fs::File::open(path)?.sync_all()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |dur| dur.as_secs())
+}
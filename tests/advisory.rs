@@ -0,0 +1,103 @@
+mod common;
+
+use alpacka::advisory::{self, AdvisoryError, Severity, Status};
+
+const ALL_JSON: &str = r#"[
+  {
+    "name": "AVG-1",
+    "packages": ["openssl", "lib32-openssl"],
+    "status": "Fixed",
+    "severity": "High",
+    "type": "denial of service",
+    "affected": "3.0.0-1",
+    "fixed": "3.0.2-1",
+    "ticket": null,
+    "issues": ["CVE-2022-0778"],
+    "advisories": ["ASA-202203-1"]
+  },
+  {
+    "name": "AVG-2",
+    "packages": ["curl"],
+    "status": "Vulnerable",
+    "severity": "Critical",
+    "type": "arbitrary code execution",
+    "affected": "8.0.0-1",
+    "fixed": null,
+    "issues": ["CVE-2023-0001"],
+    "advisories": []
+  },
+  {
+    "name": "AVG-3",
+    "packages": ["curl"],
+    "status": "Not affected",
+    "severity": "Low",
+    "type": "information disclosure",
+    "affected": "8.0.0-1",
+    "fixed": null,
+    "issues": [],
+    "advisories": []
+  },
+  {
+    "name": "AVG-4",
+    "packages": ["zlib"],
+    "status": "Vulnerable",
+    "severity": "Medium",
+    "type": "unknown",
+    "affected": "1.2 .13-1",
+    "fixed": null,
+    "issues": [],
+    "advisories": []
+  }
+]"#;
+
+#[test]
+fn parse_skips_invalid_groups() {
+    let (advisories, invalid) = advisory::parse(ALL_JSON).unwrap();
+    assert_eq!(advisories.len(), 3);
+    let openssl = &advisories[0];
+    assert_eq!(openssl.name, "AVG-1");
+    assert_eq!(openssl.packages, ["openssl", "lib32-openssl"]);
+    assert_eq!(openssl.status, Status::Fixed);
+    assert_eq!(openssl.severity, Severity::High);
+    assert_eq!(openssl.kind, "denial of service");
+    assert_eq!(openssl.affected.as_str(), "3.0.0-1");
+    assert_eq!(openssl.fixed.as_ref().map(|v| v.as_str()), Some("3.0.2-1"));
+    assert_eq!(openssl.issues, ["CVE-2022-0778"]);
+    assert_eq!(openssl.advisories, ["ASA-202203-1"]);
+    assert_eq!(advisories[1].fixed, None);
+    assert!(matches!(&invalid[..], [AdvisoryError::Invalid(e)] if e.starts_with("AVG-4")));
+    assert!(matches!(
+        advisory::parse("{}"),
+        Err(AdvisoryError::Invalid(_))
+    ));
+}
+
+#[test]
+fn matches_by_severity_and_fixed_version() {
+    let (advisories, _) = advisory::parse(ALL_JSON).unwrap();
+    let pkgs = [
+        common::pkg("curl", "8.0.1-1", ""),
+        common::pkg("openssl", "3.0.2-1", ""),
+        common::pkg("zlib", "1.2.13-1", ""),
+    ];
+    let matches = advisory::matches(&pkgs, &advisories);
+    let found: Vec<_> = matches
+        .iter()
+        .map(|m| {
+            (
+                m.pkg.desc.name.as_str(),
+                m.advisory.name.as_str(),
+                m.is_vulnerable(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        found,
+        [("curl", "AVG-2", true), ("openssl", "AVG-1", false)]
+    );
+
+    let old = [common::pkg("openssl", "1:3.0.1-1", "")];
+    assert!(advisory::matches(&old, &advisories)[0].fixed);
+    let old = [common::pkg("openssl", "3.0.1-2", "")];
+    assert!(advisory::matches(&old, &advisories)[0].is_vulnerable());
+}
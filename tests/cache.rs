@@ -0,0 +1,241 @@
+mod common;
+
+use {
+    alpacka::cache::{self, CleanupFilter, CleanupOptions, PkgFileName, RemovalPlan, VerifyStatus},
+    std::path::{Path, PathBuf},
+};
+
+/// A cache directory with the given files
+fn cache_dir(name: &str, files: &[(&str, &[u8])]) -> PathBuf {
+    let dir = common::temp_dir(name);
+    for (file_name, data) in files {
+        std::fs::write(dir.join(file_name), data).unwrap();
+    }
+    dir
+}
+
+fn file_names(paths: impl IntoIterator<Item = impl AsRef<Path>>) -> Vec<String> {
+    let mut names: Vec<String> = paths
+        .into_iter()
+        .map(|path| {
+            let name = path.as_ref().file_name().unwrap();
+            name.to_str().unwrap().to_owned()
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+fn plan_names(plan: &RemovalPlan) -> Vec<String> {
+    file_names(plan.paths())
+}
+
+#[test]
+fn parse_file_names() {
+    let parsed = PkgFileName::parse("python-foo-bar-1:2.0.r3.g1234-2.1-any.pkg.tar.zst").unwrap();
+    assert_eq!(parsed.name, "python-foo-bar");
+    assert_eq!(parsed.version.as_str(), "1:2.0.r3.g1234-2.1");
+    assert_eq!(parsed.version.epoch(), Some("1"));
+    assert_eq!(parsed.arch, "any");
+    assert_eq!(parsed.ext, ".pkg.tar.zst");
+    let parsed = PkgFileName::parse("glibc-2.39-1-x86_64.pkg.tar").unwrap();
+    assert_eq!(
+        (
+            parsed.name.as_str(),
+            parsed.version.as_str(),
+            parsed.ext.as_str()
+        ),
+        ("glibc", "2.39-1", ".pkg.tar")
+    );
+    for file_name in [
+        // Signatures are not package files themselves
+        "glibc-2.39-1-x86_64.pkg.tar.zst.sig",
+        "glibc-2.39-1-x86_64.pkg.tar.rar",
+        "glibc-2.39-1-x86_64.pkg.tar.zst.part",
+        "glibc-2.39-1-x86_64.tar.zst",
+        // Missing pkgrel
+        "glibc-2.39-x86_64.pkg.tar.zst",
+        "-2.39-1-x86_64.pkg.tar.zst",
+    ] {
+        assert_eq!(PkgFileName::parse(file_name), None, "{file_name}");
+    }
+}
+
+#[test]
+fn plan_cleanup() {
+    let dir = cache_dir(
+        "cache-cleanup",
+        &[
+            ("foo-1.0-1-x86_64.pkg.tar.zst", b"1"),
+            ("foo-1.0-1-x86_64.pkg.tar.zst.sig", b"s"),
+            ("foo-1.1-1-x86_64.pkg.tar.zst", b"22"),
+            ("foo-1.1-1-x86_64.pkg.tar.zst.sig", b"s"),
+            ("foo-2.0-1-x86_64.pkg.tar.zst", b"333"),
+            ("bar-1-1-any.pkg.tar.zst", b"4444"),
+            ("bar-2-1-any.pkg.tar.zst", b"55555"),
+            // Signatures whose package file was removed by hand
+            ("foo-0.9-1-x86_64.pkg.tar.zst.sig", b"s"),
+            ("baz-1-1-any.pkg.tar.zst.sig", b"s"),
+            ("foo-3.0-1-x86_64.pkg.tar.zst.part", b"partial"),
+        ],
+    );
+    let cache = cache::scan(&dir).unwrap();
+    assert_eq!(cache.pkgs.len(), 5);
+    assert_eq!(
+        file_names(&cache.other),
+        ["foo-3.0-1-x86_64.pkg.tar.zst.part"]
+    );
+    let local = [common::pkg("foo", "2.0-1", "")];
+
+    let plan = cache.plan_cleanup(
+        &local,
+        CleanupOptions {
+            keep: 1,
+            filter: CleanupFilter::All,
+        },
+    );
+    assert_eq!(
+        plan_names(&plan),
+        [
+            "bar-1-1-any.pkg.tar.zst",
+            "baz-1-1-any.pkg.tar.zst.sig",
+            "foo-0.9-1-x86_64.pkg.tar.zst.sig",
+            "foo-1.0-1-x86_64.pkg.tar.zst",
+            "foo-1.0-1-x86_64.pkg.tar.zst.sig",
+            "foo-1.1-1-x86_64.pkg.tar.zst",
+            "foo-1.1-1-x86_64.pkg.tar.zst.sig",
+        ]
+    );
+    assert_eq!(plan.size, 4 + 1 + 1 + 1 + 1 + 2 + 1);
+
+    // Like `paccache -rk2 -i`
+    let plan = cache.plan_cleanup(
+        &local,
+        CleanupOptions {
+            keep: 2,
+            filter: CleanupFilter::Installed,
+        },
+    );
+    assert_eq!(
+        plan_names(&plan),
+        [
+            "foo-0.9-1-x86_64.pkg.tar.zst.sig",
+            "foo-1.0-1-x86_64.pkg.tar.zst",
+            "foo-1.0-1-x86_64.pkg.tar.zst.sig",
+        ]
+    );
+
+    // Like `paccache -ruk0`
+    let plan = cache.plan_cleanup(
+        &local,
+        CleanupOptions {
+            keep: 0,
+            filter: CleanupFilter::Uninstalled,
+        },
+    );
+    assert_eq!(
+        plan_names(&plan),
+        [
+            "bar-1-1-any.pkg.tar.zst",
+            "bar-2-1-any.pkg.tar.zst",
+            "baz-1-1-any.pkg.tar.zst.sig",
+        ]
+    );
+    assert_eq!(plan.size, 4 + 5 + 1);
+
+    // The default keeps 3 versions of everything
+    let plan = cache.plan_cleanup(&local, CleanupOptions::default());
+    assert_eq!(
+        plan_names(&plan),
+        [
+            "baz-1-1-any.pkg.tar.zst.sig",
+            "foo-0.9-1-x86_64.pkg.tar.zst.sig",
+        ]
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+/// SHA-256 of `hello`
+const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+/// A sync database entry for the package file `name-1-1-any.pkg.tar.zst`
+fn sync_pkg(name: &str, size: u64, sha256sum: Option<&str>) -> alpacka::Pkg {
+    let mut extra = format!("%FILENAME%\n{name}-1-1-any.pkg.tar.zst\n\n%CSIZE%\n{size}\n\n");
+    if let Some(sha256sum) = sha256sum {
+        extra += &format!("%SHA256SUM%\n{sha256sum}\n\n");
+    }
+    common::pkg(name, "1-1", &extra)
+}
+
+#[test]
+fn verify() {
+    let dir = cache_dir(
+        "cache-verify",
+        &[
+            ("good-1-1-any.pkg.tar.zst", b"hello"),
+            ("upper-1-1-any.pkg.tar.zst", b"hello"),
+            ("nosum-1-1-any.pkg.tar.zst", b"hello"),
+            ("short-1-1-any.pkg.tar.zst", b"hel"),
+            ("long-1-1-any.pkg.tar.zst", b"hello!"),
+            ("bad-1-1-any.pkg.tar.zst", b"HELLO"),
+            ("stray-1-1-any.pkg.tar.zst", b"hello"),
+            ("gone-1-1-any.pkg.tar.zst", b"hello"),
+        ],
+    );
+    let sync = [
+        sync_pkg("good", 5, Some(HELLO_SHA256)),
+        sync_pkg("upper", 5, Some(&HELLO_SHA256.to_uppercase())),
+        sync_pkg("nosum", 5, None),
+        sync_pkg("short", 5, Some(HELLO_SHA256)),
+        sync_pkg("long", 5, Some(HELLO_SHA256)),
+        sync_pkg("bad", 5, Some(HELLO_SHA256)),
+        sync_pkg("gone", 5, Some(HELLO_SHA256)),
+    ];
+    let cache = cache::scan(&dir).unwrap();
+    // Deleted between scanning and verifying, which only fails this file
+    std::fs::remove_file(dir.join("gone-1-1-any.pkg.tar.zst")).unwrap();
+    let results = cache.verify(&sync);
+    let status = |name: &str| {
+        let result = results
+            .iter()
+            .find(|result| result.pkg.file_name.name == name)
+            .unwrap();
+        assert_eq!(
+            result.sync.map(|pkg| pkg.desc.name.as_str()),
+            (name != "stray").then_some(name)
+        );
+        result.status.clone()
+    };
+    assert_eq!(results.len(), 8);
+    assert_eq!(status("good"), VerifyStatus::Ok);
+    assert_eq!(status("upper"), VerifyStatus::Ok);
+    assert_eq!(status("nosum"), VerifyStatus::NoChecksum);
+    assert_eq!(
+        status("short"),
+        VerifyStatus::Truncated {
+            expected: 5,
+            actual: 3
+        }
+    );
+    assert_eq!(
+        status("long"),
+        VerifyStatus::SizeMismatch {
+            expected: 5,
+            actual: 6
+        }
+    );
+    assert!(matches!(
+        status("bad"),
+        VerifyStatus::ChecksumMismatch { expected, actual }
+            if expected == HELLO_SHA256 && actual.len() == 64 && actual != HELLO_SHA256
+    ));
+    assert_eq!(status("stray"), VerifyStatus::NoSyncEntry);
+    assert!(matches!(
+        status("gone"),
+        VerifyStatus::Unreadable {
+            kind: std::io::ErrorKind::NotFound,
+            ..
+        }
+    ));
+    std::fs::remove_dir_all(dir).unwrap();
+}
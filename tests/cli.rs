@@ -0,0 +1,61 @@
+mod common;
+
+use std::{os::unix::fs::symlink, path::Path, process::Command};
+
+/// A root with `coreutils` installed and `/bin` linked to `usr/bin`
+fn root_with_coreutils(root: &Path) {
+    std::fs::create_dir_all(root.join("usr/bin")).unwrap();
+    std::fs::create_dir_all(root.join("usr/share")).unwrap();
+    std::fs::create_dir_all(root.join("etc")).unwrap();
+    std::fs::write(root.join("usr/bin/ls"), "").unwrap();
+    symlink("usr/bin", root.join("bin")).unwrap();
+    std::fs::write(root.join("etc/pacman.conf"), "[options]\n").unwrap();
+    let local = root.join("var/lib/pacman/local");
+    let entry = local.join("coreutils-9.5-1");
+    std::fs::create_dir_all(&entry).unwrap();
+    std::fs::write(local.join("ALPM_DB_VERSION"), "9\n").unwrap();
+    std::fs::write(
+        entry.join("desc"),
+        "%NAME%\ncoreutils\n\n%VERSION%\n9.5-1\n\n%ARCH%\nx86_64\n\n",
+    )
+    .unwrap();
+    std::fs::write(
+        entry.join("files"),
+        "%FILES%\nusr/\nusr/bin/\nusr/bin/ls\n\n",
+    )
+    .unwrap();
+}
+
+fn alpacka(root: &Path, current_dir: &Path, args: &[&str]) -> (String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_alpacka"))
+        .current_dir(current_dir)
+        .arg("--root")
+        .arg(root)
+        .arg("--dbpath")
+        .arg(root.join("var/lib/pacman"))
+        .arg("--config")
+        .arg(root.join("etc/pacman.conf"))
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn owns_resolves_parent_directories() {
+    let root = common::temp_dir("cli-owns").canonicalize().unwrap();
+    root_with_coreutils(&root);
+    let expected = format!(
+        "{} is owned by coreutils 9.5-1\n",
+        root.join("usr/bin/ls").display()
+    );
+    let through_symlink = root.join("bin/ls");
+    let (stdout, stderr) = alpacka(&root, &root, &["-Qo", through_symlink.to_str().unwrap()]);
+    assert_eq!((stdout.as_str(), stderr.as_str()), (expected.as_str(), ""));
+    let (stdout, stderr) = alpacka(&root, &root.join("usr/share"), &["-Qo", "../bin/ls"]);
+    assert_eq!((stdout.as_str(), stderr.as_str()), (expected.as_str(), ""));
+    std::fs::remove_dir_all(root).unwrap();
+}
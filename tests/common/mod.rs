@@ -0,0 +1,31 @@
+//! Helpers shared by the integration tests
+
+#![allow(dead_code)]
+
+use alpacka::{Pkg, PkgDesc};
+
+/// A package with the given name and version, and extra `desc` sections
+/// like `"%DEPENDS%\nglibc\n\n"`
+pub fn pkg(name: &str, version: &str, extra: &str) -> Pkg {
+    let desc = format!("%NAME%\n{name}\n\n%VERSION%\n{version}\n\n%ARCH%\nx86_64\n\n{extra}");
+    Pkg {
+        desc: PkgDesc::parse(&desc, false).unwrap(),
+        files: Vec::new(),
+        backup: Vec::new(),
+    }
+}
+
+/// Names of `pkgs`, in order
+pub fn names<'a>(pkgs: impl IntoIterator<Item = &'a Pkg>) -> Vec<&'a str> {
+    pkgs.into_iter().map(|pkg| pkg.desc.name.as_str()).collect()
+}
+
+/// An empty temporary directory, unique to this test process
+pub fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("alpacka-{name}-{}", std::process::id()));
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
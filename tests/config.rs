@@ -0,0 +1,114 @@
+mod common;
+
+use {
+    alpacka::config::{Config, ConfigError},
+    std::{collections::BTreeMap, path::Path},
+};
+
+fn repo_names(config: &Config) -> Vec<&str> {
+    config.repo_names().collect()
+}
+
+#[test]
+fn include_globs() {
+    let dir = common::temp_dir("config-glob");
+    let conf_d = dir.join("conf.d");
+    std::fs::create_dir(&conf_d).unwrap();
+    for (name, contents) in [
+        ("20-extra.conf", "[extra]\nInclude = mirrors/mirrorlist-?\n"),
+        (
+            "10-core.conf",
+            "[core]\nServer = https://core.example.org\n",
+        ),
+        ("30-notes.txt", "[notes]\n"),
+        (".hidden.conf", "[hidden]\n"),
+    ] {
+        std::fs::write(conf_d.join(name), contents).unwrap();
+    }
+    let mirrors = dir.join("mirrors");
+    std::fs::create_dir(&mirrors).unwrap();
+    for (name, contents) in [
+        ("mirrorlist-b", "Server = https://b.example.org/$repo\n"),
+        ("mirrorlist-a", "Server = https://a.example.org/$repo\n"),
+        ("mirrorlist-10", "Server = https://10.example.org/$repo\n"),
+    ] {
+        std::fs::write(mirrors.join(name), contents).unwrap();
+    }
+    let path = dir.join("pacman.conf");
+    std::fs::write(
+        &path,
+        format!("[options]\nInclude = {}/*.conf\n", conf_d.display()),
+    )
+    .unwrap();
+    // Relative includes are relative to the current directory, like in pacman
+    std::env::set_current_dir(&dir).unwrap();
+    let config = Config::read(&path).unwrap();
+    assert_eq!(repo_names(&config), ["core", "extra"]);
+    assert_eq!(
+        config.repos[1].servers,
+        ["https://a.example.org/extra", "https://b.example.org/extra"]
+    );
+
+    // Like glob(3), a pattern without matches is read as it is
+    std::fs::write(&path, "[options]\nInclude = conf.d/*.ini\n").unwrap();
+    let Err(ConfigError::Io { path, source }) = Config::read(&path) else {
+        panic!("a glob without matches is read literally");
+    };
+    assert_eq!(path, Path::new("conf.d/*.ini"));
+    assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn include_globs_with_custom_files() {
+    let files = BTreeMap::from([
+        (
+            "/etc/pacman.conf",
+            "[options]\nInclude = /etc/pacman.d/*.conf\n",
+        ),
+        ("/etc/pacman.d/core.conf", "[core]\n"),
+        ("/etc/pacman.d/extra.conf", "[extra]\n"),
+    ]);
+    let config = Config::read_with(
+        "/etc/pacman.conf",
+        |path| {
+            let path = path.to_str().unwrap();
+            let contents = files.get(path).ok_or(std::io::ErrorKind::NotFound)?;
+            Ok((*contents).to_owned())
+        },
+        |dir| {
+            // Unsorted, the config reader sorts
+            assert_eq!(dir, Path::new("/etc/pacman.d"));
+            Ok(vec![
+                "extra.conf".into(),
+                "core.conf".into(),
+                "gnupg".into(),
+            ])
+        },
+    )
+    .unwrap();
+    assert_eq!(repo_names(&config), ["core", "extra"]);
+}
+
+#[test]
+fn recursive_includes_are_an_error() {
+    let dir = common::temp_dir("config-recursive");
+    let path = dir.join("pacman.conf");
+    std::fs::write(&path, format!("[options]\nInclude = {}\n", path.display())).unwrap();
+    assert!(matches!(
+        Config::read(&path),
+        Err(ConfigError::IncludeDepth { .. })
+    ));
+    // Also through other files
+    let other = dir.join("other.conf");
+    std::fs::write(&path, format!("[options]\nInclude = {}\n", other.display())).unwrap();
+    std::fs::write(&other, format!("Include = {}\n", path.display())).unwrap();
+    assert!(matches!(
+        Config::read(&path),
+        Err(ConfigError::IncludeDepth { .. })
+    ));
+    // Nesting that ends is fine
+    std::fs::write(&other, "[core]\n").unwrap();
+    assert_eq!(repo_names(&Config::read(&path).unwrap()), ["core"]);
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,27 @@
+mod common;
+
+use alpacka::DbReadError;
+
+#[test]
+fn legacy_entry_missing_fields_is_an_error() {
+    let db_path = common::temp_dir("legacy-db");
+    let entry = db_path.join("local/foo-1.0-1");
+    std::fs::create_dir_all(&entry).unwrap();
+    // Very old entries keep their dependencies in a separate `depends` file
+    std::fs::write(entry.join("desc"), "%NAME%\nfoo\n\n%ARCH%\nx86_64\n\n").unwrap();
+    std::fs::write(entry.join("depends"), "%DEPENDS%\nglibc\n\n").unwrap();
+    std::fs::write(entry.join("files"), "").unwrap();
+
+    let err = alpacka::read_local_db_with_format(&db_path).unwrap_err();
+    assert!(matches!(err, DbReadError::MissingFields(ref dir) if dir.ends_with("foo-1.0-1")));
+
+    std::fs::write(
+        entry.join("desc"),
+        "%NAME%\nfoo\n\n%VERSION%\n1.0-1\n\n%ARCH%\nx86_64\n\n",
+    )
+    .unwrap();
+    let (pkgs, format) = alpacka::read_local_db_with_format(&db_path).unwrap();
+    assert!(format.split_depends);
+    assert_eq!(pkgs[0].desc.depends[0].name, "glibc");
+    std::fs::remove_dir_all(db_path).unwrap();
+}
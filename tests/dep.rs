@@ -0,0 +1,211 @@
+mod common;
+
+use alpacka::{
+    DepVer, ReqCmp, SyncDb,
+    dep::{self, Problem, VersionSet},
+    version::Version,
+};
+
+fn describe(problems: &[Problem]) -> Vec<String> {
+    problems.iter().map(ToString::to_string).collect()
+}
+
+fn ver(ver: &str) -> Version {
+    ver.parse().unwrap()
+}
+
+/// The versions meeting all of `reqs`, like `[">=1.2", "<2"]`
+fn version_set(reqs: &[&str]) -> VersionSet {
+    let mut set = VersionSet::any();
+    for req in reqs {
+        let (req_cmp, rest) = [
+            ("<=", ReqCmp::LtEq),
+            (">=", ReqCmp::GtEq),
+            ("<", ReqCmp::Lt),
+            (">", ReqCmp::Gt),
+            ("=", ReqCmp::Eq),
+        ]
+        .into_iter()
+        .find_map(|(op, req_cmp)| Some((req_cmp, req.strip_prefix(op)?)))
+        .unwrap();
+        set.insert(&DepVer {
+            req_cmp,
+            ver: ver(rest),
+        });
+    }
+    set
+}
+
+fn describe_chains(chains: &[dep::Chain]) -> Vec<String> {
+    chains.iter().map(ToString::to_string).collect()
+}
+
+#[test]
+fn check_installed() {
+    let mut pkgs = vec![
+        common::pkg("app", "1.0-1", "%DEPENDS%\nlibfoo>=2\nsh\n\n"),
+        common::pkg("libfoo", "1.5-1", "%CONFLICTS%\nlibfoo-git\n\n"),
+        common::pkg(
+            "libfoo-git",
+            "2.0-1",
+            "%PROVIDES%\nlibfoo=2.0\n\n%CONFLICTS%\nlibfoo\n\n",
+        ),
+        common::pkg("bash", "5.2-1", "%PROVIDES%\nsh\n\n%CONFLICTS%\nsh\n\n"),
+    ];
+    pkgs[1].files = vec!["usr/".into(), "usr/lib/libfoo.so".into()];
+    pkgs[2].files = vec!["usr/".into(), "usr/lib/libfoo.so".into()];
+    let problems = dep::check(&pkgs);
+    // The mutual conflict is reported once, bash's conflict with itself not at all
+    assert_eq!(
+        describe(&problems),
+        [
+            "libfoo and libfoo-git are in conflict (libfoo-git)",
+            "/usr/lib/libfoo.so is owned by libfoo, libfoo-git",
+        ]
+    );
+
+    pkgs.remove(2);
+    let problems = dep::check(&pkgs);
+    assert_eq!(describe(&problems), ["app requires libfoo>=2"]);
+    let Problem::Unsatisfied { candidates, .. } = &problems[0] else {
+        panic!("expected an unsatisfied dependency");
+    };
+    assert_eq!(common::names(candidates.iter().copied()), ["libfoo"]);
+}
+
+#[test]
+fn check_sync_dbs() {
+    let dbs = [
+        SyncDb {
+            name: "core".into(),
+            pkgs: vec![
+                common::pkg("glibc", "2.40-1", ""),
+                common::pkg("bash", "5.2-1", "%DEPENDS%\nglibc\nreadline\n\n"),
+            ],
+        },
+        SyncDb {
+            name: "extra".into(),
+            pkgs: vec![common::pkg(
+                "vim",
+                "9.1-1",
+                "%DEPENDS%\nglibc\nbash\ngpm>=1.20\n\n",
+            )],
+        },
+    ];
+    assert_eq!(
+        describe(&dep::check_sync(&dbs)),
+        ["bash requires readline", "vim requires gpm>=1.20"]
+    );
+}
+
+#[test]
+fn why_chains() {
+    let pkgs = [
+        common::pkg("app", "1.0-1", "%DEPENDS%\nlibfoo\n\n"),
+        common::pkg("libfoo", "1.0-1", "%DEPENDS%\nlibbar\n\n%REASON%\n1\n\n"),
+        common::pkg("libbar", "1.0-1", "%REASON%\n1\n\n"),
+        common::pkg(
+            "editor",
+            "1.0-1",
+            "%OPTDEPENDS%\nlibbar: spell checking\n\n",
+        ),
+        common::pkg("viewer", "1.0-1", "%OPTDEPENDS%\nlibfoo\n\n"),
+        // An unversioned provision doesn't meet a versioned dependency
+        common::pkg("shell", "1.0-1", "%DEPENDS%\nsh>=5\n\n"),
+        common::pkg("bash", "5.2-1", "%PROVIDES%\nsh\n\n%REASON%\n1\n\n"),
+    ];
+    let why = |name: &str| {
+        dep::why(
+            &pkgs.iter().find(|pkg| pkg.desc.name == name).unwrap().desc,
+            &pkgs,
+        )
+    };
+
+    let libbar = why("libbar");
+    assert!(!libbar.explicit && !libbar.is_orphan());
+    assert_eq!(
+        describe_chains(&libbar.required),
+        ["libbar <- libfoo <- app (explicit)"]
+    );
+    assert_eq!(
+        describe_chains(&libbar.optional),
+        [
+            "libbar <~ editor (explicit)",
+            "libbar <- libfoo <~ viewer (explicit)",
+        ]
+    );
+    assert_eq!(libbar.required[0].root().desc.name, "app");
+    assert_eq!(libbar.optional[1].optional, [false, true]);
+
+    assert!(why("app").explicit);
+    assert!(why("bash").is_orphan());
+}
+
+#[test]
+fn version_set_narrows_to_exact_version() {
+    let set = version_set(&[">=1.2", "<2", "=1.5-1"]);
+    assert!(set.is_satisfiable());
+    assert_eq!(set.to_string(), "=1.5-1");
+    assert!(set.contains(&ver("1.5-1")));
+    assert!(!set.contains(&ver("1.5-2")));
+    assert!(!set.contains(&ver("1.4-1")));
+    // Intersecting the single requirements gives the same set
+    let intersection = version_set(&[">=1.2"])
+        .intersection(&version_set(&["<2"]))
+        .intersection(&version_set(&["=1.5-1"]));
+    assert_eq!(intersection.to_string(), "=1.5-1");
+    assert_eq!(version_set(&[">=1.2", "<2"]).to_string(), ">=1.2,<2");
+}
+
+#[test]
+fn version_set_empty_intersections() {
+    for reqs in [
+        [">2", "<1"],
+        [">1", "<=1"],
+        [">=1", "<1"],
+        [">1-2", "<=1-2"],
+    ] {
+        let set = version_set(&reqs);
+        assert!(!set.is_satisfiable(), "{reqs:?}");
+        for version in ["0.5-1", "1", "1-1", "1-2", "1.5-1", "3-1"] {
+            assert!(!set.contains(&ver(version)), "{reqs:?} contains {version}");
+        }
+    }
+    assert!(version_set(&[">=1", "<=1"]).is_satisfiable());
+    assert!(version_set(&[">=1-2", "<=1-2"]).is_satisfiable());
+}
+
+#[test]
+fn version_set_without_pkgrel() {
+    // A requirement without a pkgrel is met by every release of that pkgver
+    let set = version_set(&["=1.5"]);
+    assert!(set.contains(&ver("1.5-1")));
+    assert!(set.contains(&ver("1.5-3")));
+    assert!(!set.contains(&ver("1.6-1")));
+    assert!(!set.contains(&ver("1.4-9")));
+    let set = version_set(&["=1.5", "<1.5-3"]);
+    assert!(set.is_satisfiable());
+    assert!(set.contains(&ver("1.5-2")));
+    assert!(!set.contains(&ver("1.5-3")));
+    assert!(!version_set(&[">1.5", "<=1.5-3"]).is_satisfiable());
+    assert!(version_set(&[">=1.5", "<=1.5-3"]).contains(&ver("1.5-3")));
+}
+
+#[test]
+fn version_set_epochs() {
+    let set = version_set(&[">=1:1.0"]);
+    assert!(!set.contains(&ver("2.0-1")));
+    assert!(!set.contains(&ver("1:0.9-1")));
+    assert!(set.contains(&ver("1:1.0-1")));
+    assert!(set.contains(&ver("2:0.1-1")));
+    // A missing epoch is epoch 0
+    let set = version_set(&["<1:0"]);
+    assert!(set.contains(&ver("99.0-1")));
+    assert!(set.contains(&ver("0:99.0-1")));
+    assert!(!set.contains(&ver("1:0-1")));
+    assert!(!version_set(&[">1:1.0", "<2.0"]).is_satisfiable());
+    assert_eq!(
+        version_set(&["=1:1.5-1", ">=1:1.0"]).to_string(),
+        "=1:1.5-1"
+    );
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use alpacka::diff::{self, ChangeKind, FieldChange, ListChange};
+
+#[test]
+fn diff_package_sets() {
+    let old = [
+        common::pkg("bash", "5.2.026-2", "%DEPENDS%\nglibc\nreadline\n\n"),
+        // 3.10 is newer than 3.9, although it sorts before it as a string
+        common::pkg("grep", "3.9-1", ""),
+        common::pkg("nano", "8.0-1", ""),
+        common::pkg("vim", "9.1.0-1", ""),
+        common::pkg("zlib", "1:1.3.1-1", ""),
+        common::pkg("zsh", "5.9-5", ""),
+    ];
+    let new = [
+        common::pkg("bash", "5.2.026-2", "%DEPENDS%\nglibc\nncurses\n\n"),
+        common::pkg("grep", "3.10-1", ""),
+        common::pkg("less", "661-1", ""),
+        common::pkg("vim", "9.1.0-1", ""),
+        // The epoch wins over the higher pkgver
+        common::pkg("zlib", "1.3.2-1", ""),
+        common::pkg("zsh", "5.9-5", ""),
+    ];
+    let diffs = diff::diff(&old, &new);
+    let summary: Vec<(&str, ChangeKind, Option<&str>, Option<&str>)> = diffs
+        .iter()
+        .map(|diff| {
+            (
+                diff.name.as_str(),
+                diff.kind,
+                diff.old.map(|pkg| pkg.desc.version.as_str()),
+                diff.new.map(|pkg| pkg.desc.version.as_str()),
+            )
+        })
+        .collect();
+    // Unchanged packages are left out
+    assert_eq!(
+        summary,
+        [
+            (
+                "bash",
+                ChangeKind::Modified,
+                Some("5.2.026-2"),
+                Some("5.2.026-2")
+            ),
+            ("grep", ChangeKind::Upgraded, Some("3.9-1"), Some("3.10-1")),
+            ("less", ChangeKind::Added, None, Some("661-1")),
+            ("nano", ChangeKind::Removed, Some("8.0-1"), None),
+            (
+                "zlib",
+                ChangeKind::Downgraded,
+                Some("1:1.3.1-1"),
+                Some("1.3.2-1")
+            ),
+        ]
+    );
+    assert_eq!(
+        diffs[0].fields,
+        [FieldChange::Depends(ListChange {
+            added: vec!["ncurses".into()],
+            removed: vec!["readline".into()],
+        })]
+    );
+    assert!(diffs[1..].iter().all(|diff| diff.fields.is_empty()));
+}
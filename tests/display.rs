@@ -0,0 +1,94 @@
+//! Golden tests for `alpacka::display`
+//!
+//! The expected output in `tests/golden` follows pacman's `-Qi`/`-Si` layout
+//! for the fixture databases in `tests/fixtures`, in the UTC time zone.
+
+use {
+    alpacka::{
+        Pkg, PkgDesc,
+        display::{DateFormat, PkgInfo},
+    },
+    jiff::tz::TimeZone,
+    std::path::Path,
+};
+
+const ROOT: &str = "tests/fixtures/root";
+
+fn local_pkgs() -> Vec<Pkg> {
+    alpacka::read_local_db_at(Path::new(ROOT).join("var/lib/pacman")).unwrap()
+}
+
+fn find<'a>(pkgs: &'a [Pkg], name: &str) -> &'a Pkg {
+    pkgs.iter().find(|pkg| pkg.desc.name == name).unwrap()
+}
+
+fn sync_pkg(repo: &str, entry: &str) -> Pkg {
+    let path = format!("tests/fixtures/sync/{repo}/{entry}/desc");
+    Pkg {
+        desc: PkgDesc::parse(&std::fs::read_to_string(path).unwrap(), false).unwrap(),
+        files: Vec::new(),
+        backup: Vec::new(),
+    }
+}
+
+fn assert_golden(info: &PkgInfo, golden: &str) {
+    let expected = std::fs::read_to_string(format!("tests/golden/{golden}")).unwrap();
+    assert_eq!(info.to_string(), expected, "mismatch with {golden}");
+}
+
+#[test]
+fn local_info_unwrapped() {
+    let pkgs = local_pkgs();
+    let info = PkgInfo::local(find(&pkgs, "pacman"), &pkgs).time_zone(TimeZone::UTC);
+    assert_golden(&info, "pacman-Qi.txt");
+}
+
+#[test]
+fn local_info_wrapped_with_backup_files() {
+    let pkgs = local_pkgs();
+    let info = PkgInfo::local(find(&pkgs, "pacman"), &pkgs)
+        .time_zone(TimeZone::UTC)
+        .cols(80)
+        .backup_files(Path::new(ROOT));
+    assert_golden(&info, "pacman-Qii-80.txt");
+}
+
+#[test]
+fn local_info_narrow_posix_dates() {
+    let pkgs = local_pkgs();
+    let info = PkgInfo::local(find(&pkgs, "glibc"), &pkgs)
+        .time_zone(TimeZone::UTC)
+        .date_format(DateFormat::Posix)
+        .cols(60);
+    assert_golden(&info, "glibc-Qi-60-posix.txt");
+}
+
+#[test]
+fn local_info_backup_status() {
+    let pkgs = local_pkgs();
+    let info = PkgInfo::local(find(&pkgs, "sudo"), &pkgs)
+        .time_zone(TimeZone::UTC)
+        .backup_files(Path::new(ROOT));
+    assert_golden(&info, "sudo-Qii.txt");
+}
+
+#[test]
+fn sync_info() {
+    let pkg = sync_pkg("core", "pacman-6.1.0-3");
+    let local = local_pkgs();
+    let info = PkgInfo::sync(&pkg, "core")
+        .local_pkgs(&local)
+        .time_zone(TimeZone::UTC)
+        .cols(80);
+    assert_golden(&info, "pacman-Si-80.txt");
+}
+
+#[test]
+fn humanize_size() {
+    use alpacka::display::humanize_size;
+    assert_eq!(humanize_size(2048, None), (2048.0, "B"));
+    assert_eq!(humanize_size(2049, None).1, "KiB");
+    assert_eq!(humanize_size(1024 * 1024, None), (1024.0, "KiB"));
+    assert_eq!(humanize_size(3 * 1024 * 1024, Some('K')), (3072.0, "KiB"));
+    assert_eq!(humanize_size(-1, Some('M')).0, 0.0);
+}
@@ -0,0 +1,105 @@
+"""Crafts Ed25519 keys that gpg refuses to make, for the negative key usage tests"""
+
+import hashlib
+import struct
+
+from cryptography.hazmat.primitives.asymmetric.ed25519 import Ed25519PrivateKey
+from cryptography.hazmat.primitives.serialization import Encoding, PublicFormat
+
+CREATED = 1577836800  # 2020-01-01
+ED25519_OID = bytes.fromhex("2b06010401da470f01")
+DATA = open("data", "rb").read()
+
+
+def packet(tag, body):
+    assert len(body) < 8384
+    if len(body) < 192:
+        length = bytes([len(body)])
+    else:
+        length = bytes([((len(body) - 192) >> 8) + 192, (len(body) - 192) & 0xFF])
+    return bytes([0xC0 | tag]) + length + body
+
+
+def mpi(data):
+    data = data.lstrip(b"\0")
+    bits = (len(data) - 1) * 8 + data[0].bit_length() if data else 0
+    return struct.pack(">H", bits) + data
+
+
+def subpacket(kind, content):
+    length = len(content) + 1
+    assert length < 8384
+    if length < 192:
+        return bytes([length, kind]) + content
+    return bytes([((length - 192) >> 8) + 192, (length - 192) & 0xFF, kind]) + content
+
+
+class Key:
+    def __init__(self, seed):
+        self.private = Ed25519PrivateKey.from_private_bytes(bytes([seed]) * 32)
+        point = self.private.public_key().public_bytes(Encoding.Raw, PublicFormat.Raw)
+        self.body = (
+            struct.pack(">BIB", 4, CREATED, 22)
+            + bytes([len(ED25519_OID)])
+            + ED25519_OID
+            + mpi(b"\x40" + point)
+        )
+        self.fingerprint = hashlib.sha1(self.hashed()).digest()
+
+    def hashed(self):
+        return b"\x99" + struct.pack(">H", len(self.body)) + self.body
+
+    def sign(self, sig_type, prefix, hashed_subpackets=b"", unhashed_subpackets=b""):
+        hashed_subpackets = (
+            subpacket(2, struct.pack(">I", CREATED))
+            + subpacket(33, b"\x04" + self.fingerprint)
+            + hashed_subpackets
+        )
+        hashed = (
+            bytes([4, sig_type, 22, 8])
+            + struct.pack(">H", len(hashed_subpackets))
+            + hashed_subpackets
+        )
+        trailer = b"\x04\xff" + struct.pack(">I", len(hashed))
+        digest = hashlib.sha256(prefix + hashed + trailer).digest()
+        sig = self.private.sign(digest)
+        unhashed = subpacket(16, self.fingerprint[12:]) + unhashed_subpackets
+        return (
+            hashed
+            + struct.pack(">H", len(unhashed))
+            + unhashed
+            + digest[:2]
+            + mpi(sig[:32])
+            + mpi(sig[32:])
+        )
+
+
+def user_id(primary, uid, flags):
+    uid_hashed = b"\xb4" + struct.pack(">I", len(uid)) + uid
+    sig = primary.sign(0x13, primary.hashed() + uid_hashed, subpacket(27, bytes([flags])))
+    return packet(13, uid) + packet(2, sig)
+
+
+def subkey(primary, sub, back_sign, nested=False):
+    prefix = primary.hashed() + sub.hashed()
+    hashed = subpacket(27, b"\x02")
+    if back_sign:
+        # Real back-signatures never embed another signature themselves
+        unhashed = subpacket(32, sub.sign(0x19, prefix)) if nested else b""
+        hashed += subpacket(32, sub.sign(0x19, prefix, unhashed_subpackets=unhashed))
+    return packet(14, sub.body) + packet(2, primary.sign(0x18, prefix, hashed))
+
+
+primary, sub = Key(1), Key(2)
+uid = user_id(primary, b"Crafted Tester <crafted@example.org>", 0x01)
+with open("back-signed.gpg", "wb") as f:
+    f.write(packet(6, primary.body) + uid + subkey(primary, sub, True))
+with open("nested-back-signed.gpg", "wb") as f:
+    f.write(packet(6, primary.body) + uid + subkey(primary, sub, True, nested=True))
+with open("not-back-signed.gpg", "wb") as f:
+    f.write(packet(6, primary.body) + uid + subkey(primary, sub, False))
+with open("data.subkey.sig", "wb") as f:
+    f.write(packet(2, sub.sign(0x00, DATA)))
+# The primary key may only certify, but signs data anyway
+with open("data.cert-only.sig", "wb") as f:
+    f.write(packet(2, primary.sign(0x00, DATA)))
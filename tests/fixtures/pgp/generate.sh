@@ -0,0 +1,48 @@
+#!/bin/sh
+# Regenerates the gpg made keys and signatures in this directory
+set -eu
+cd "$(dirname "$0")"
+GNUPGHOME=$(mktemp -d)
+export GNUPGHOME
+trap 'rm -rf "$GNUPGHOME"' EXIT
+gpg="gpg --batch --quiet --pinentry-mode loopback --passphrase="
+fpr() {
+    $gpg --with-colons --list-keys "$1" | awk -F: '$1 == "fpr" { print $10 }' | sed -n "${2:-1}p"
+}
+
+printf 'The quick brown fox jumps over the lazy dog\n' > data
+
+# RSA primary key that signs by itself
+$gpg --faked-system-time 20190101T000000 --quick-gen-key 'RSA Tester <rsa@example.org>' rsa2048 sign,cert never
+rsa=$(fpr rsa@example.org)
+$gpg --armor --export "$rsa" > rsa.asc
+$gpg --local-user "$rsa" --detach-sign --output data.rsa.sig data
+
+# Ed25519 certification key with a signing subkey, certified by the RSA key
+$gpg --quick-gen-key 'Ed25519 Tester <ed25519@example.org>' ed25519 cert never
+ed25519=$(fpr ed25519@example.org)
+$gpg --quick-add-key "$ed25519" ed25519 sign never
+$gpg --local-user "$(fpr ed25519@example.org 2)!" --detach-sign --output data.ed25519.sig data
+$gpg --local-user "$rsa" --quick-sign-key "$ed25519"
+$gpg --export "$ed25519" > ed25519.gpg
+sleep 2
+$gpg --local-user "$rsa" --quick-revoke-sig "$ed25519" "$rsa"
+$gpg --export "$ed25519" > ed25519-cert-revoked.gpg
+
+# A signature that expired a day after it was made
+$gpg --faked-system-time 20200101T000000 --default-sig-expire 1d \
+    --local-user "$rsa" --detach-sign --output data.sig-expired.sig data
+
+# A signing subkey without expiry, whose primary key expired
+$gpg --faked-system-time 20200101T000000 \
+    --quick-gen-key 'Expired Tester <expired@example.org>' ed25519 cert 1y
+expired=$(fpr expired@example.org)
+$gpg --faked-system-time 20200101T000000 --quick-add-key "$expired" ed25519 sign never
+$gpg --export "$expired" > primary-expired.gpg
+$gpg --faked-system-time 20200201T000000 --local-user "$(fpr expired@example.org 2)!" \
+    --detach-sign --output data.primary-expired.sig data
+
+python3 craft.py
+# Check that the crafted keys are sound, gpg accepts the back-signed subkey
+$gpg --import back-signed.gpg
+$gpg --verify data.subkey.sig data
@@ -0,0 +1,17 @@
+mod common;
+
+use {alpacka::group, common::pkg};
+
+#[test]
+fn groups_list_each_name_once_in_repository_order() {
+    // Like `core-testing` followed by `core`
+    let pkgs = [
+        pkg("linux", "6.9-1", "%GROUPS%\nbase\n\n"),
+        pkg("bash", "5.2-1", "%GROUPS%\nbase\n\n"),
+        pkg("linux", "6.8-1", "%GROUPS%\nbase\n\n"),
+    ];
+    let groups = group::groups(pkgs.iter());
+    let base = &groups["base"];
+    assert_eq!(common::names(base.iter().copied()), ["bash", "linux"]);
+    assert_eq!(base[1].desc.version.as_str(), "6.9-1");
+}
@@ -0,0 +1,207 @@
+mod common;
+
+use {
+    alpacka::image::{ContainerImage, ImageError},
+    std::path::Path,
+};
+
+enum Entry<'a> {
+    File(&'a str, &'a [u8]),
+    Symlink(&'a str, &'a str),
+    HardLink(&'a str, &'a str),
+}
+
+fn tarball(entries: &[Entry]) -> Vec<u8> {
+    let mut builder = tar::Builder::new(Vec::new());
+    for entry in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(0o644);
+        match *entry {
+            Entry::File(path, data) => {
+                header.set_size(data.len() as u64);
+                builder.append_data(&mut header, path, data).unwrap();
+            }
+            Entry::Symlink(path, target) => {
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_size(0);
+                builder.append_link(&mut header, path, target).unwrap();
+            }
+            Entry::HardLink(path, target) => {
+                header.set_entry_type(tar::EntryType::Link);
+                header.set_size(0);
+                builder.append_link(&mut header, path, target).unwrap();
+            }
+        }
+    }
+    builder.into_inner().unwrap()
+}
+
+/// An OCI image layout directory with the given layers, from the bottom up
+fn oci_layout(dir: &Path, layers: &[Vec<u8>]) {
+    let blobs = dir.join("blobs/sha256");
+    std::fs::create_dir_all(&blobs).unwrap();
+    let mut descriptors = Vec::new();
+    for (i, layer) in layers.iter().enumerate() {
+        std::fs::write(blobs.join(format!("layer{i}")), layer).unwrap();
+        descriptors.push(format!(r#"{{"digest":"sha256:layer{i}"}}"#));
+    }
+    let manifest = format!(r#"{{"layers":[{}]}}"#, descriptors.join(","));
+    std::fs::write(blobs.join("manifest"), manifest).unwrap();
+    let index = r#"{"manifests":[{"digest":"sha256:manifest"}]}"#;
+    std::fs::write(dir.join("index.json"), index).unwrap();
+}
+
+/// A `docker save` tarball with the given layers, from the bottom up
+fn docker_save(layers: &[Vec<u8>]) -> Vec<u8> {
+    let paths: Vec<String> = (0..layers.len())
+        .map(|i| format!("layer{i}/layer.tar"))
+        .collect();
+    let manifest = format!(
+        r#"[{{"Config":"config.json","Layers":["{}"]}}]"#,
+        paths.join(r#"",""#)
+    );
+    let mut entries = vec![Entry::File("manifest.json", manifest.as_bytes())];
+    for (path, layer) in paths.iter().zip(layers) {
+        entries.push(Entry::File(path, layer));
+    }
+    tarball(&entries)
+}
+
+fn desc(name: &str) -> String {
+    format!("%NAME%\n{name}\n\n%VERSION%\n1.0-1\n\n%ARCH%\nx86_64\n\n")
+}
+
+/// Open `image` from a file in a fresh temporary directory
+fn open_tarball(name: &str, image: &[u8]) -> ContainerImage {
+    let dir = common::temp_dir(name);
+    let path = dir.join("image.tar");
+    std::fs::write(&path, image).unwrap();
+    let image = ContainerImage::open(&path).unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+    image
+}
+
+#[test]
+fn symlinks_and_db_path_are_followed() {
+    let dir = common::temp_dir("image");
+    let sync_db = tarball(&[Entry::File("bar-1.0-1/desc", desc("bar").as_bytes())]);
+    let conf = "[options]\nDBPath = /usr/lib/sysimage/pacman/\n\n[core]\n";
+    let base = tarball(&[
+        Entry::File("usr/share/pacman/pacman.conf", conf.as_bytes()),
+        Entry::Symlink("etc/pacman.conf", "../usr/share/pacman/pacman.conf"),
+        Entry::File("usr/lib/sysimage/pacman/local/ALPM_DB_VERSION", b"9\n"),
+        Entry::File(
+            "usr/lib/sysimage/pacman/local/foo-1.0-1/desc",
+            desc("foo").as_bytes(),
+        ),
+        Entry::File("usr/lib/sysimage/pacman/local/foo-1.0-1/files", b""),
+        Entry::File("usr/lib/sysimage/pacman/sync/core.db", &sync_db),
+        Entry::File("etc/motd", b"old\n"),
+        Entry::File("etc/issue", b"Arch Linux\n"),
+    ]);
+    // A file replaced by a symlink in a later layer
+    let update = tarball(&[Entry::Symlink("etc/motd", "/etc/issue")]);
+    oci_layout(&dir, &[base, update]);
+
+    let image = ContainerImage::open(&dir).unwrap();
+    assert_eq!(image.file("/etc/motd"), Some(&b"Arch Linux\n"[..]));
+    assert!(image.config().unwrap().is_some());
+    let local = image.local_db().unwrap();
+    assert_eq!(common::names(&local), ["foo"]);
+    let sync = image.sync_dbs().unwrap();
+    assert_eq!(sync[0].name, "core");
+    assert_eq!(common::names(&sync[0].pkgs), ["bar"]);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn docker_save_whiteouts_remove_lower_files() {
+    let base = tarball(&[
+        Entry::File("var/lib/pacman/local/ALPM_DB_VERSION", b"9\n"),
+        Entry::File(
+            "var/lib/pacman/local/foo-1.0-1/desc",
+            desc("foo").as_bytes(),
+        ),
+        Entry::File("var/lib/pacman/local/foo-1.0-1/files", b""),
+        Entry::File(
+            "var/lib/pacman/local/bar-1.0-1/desc",
+            desc("bar").as_bytes(),
+        ),
+        Entry::File("var/lib/pacman/local/bar-1.0-1/files", b""),
+        Entry::File("etc/motd", b"hello\n"),
+    ]);
+    // `pacman -R foo` and `rm /etc/motd` in a later layer
+    let update = tarball(&[
+        Entry::File("var/lib/pacman/local/.wh.foo-1.0-1", b""),
+        Entry::File("etc/.wh.motd", b""),
+    ]);
+    let image = open_tarball("image-whiteout", &docker_save(&[base, update]));
+    assert_eq!(common::names(&image.local_db().unwrap()), ["bar"]);
+    assert_eq!(image.file("etc/motd"), None);
+}
+
+#[test]
+fn opaque_dirs_hide_lower_layers() {
+    let base = tarball(&[
+        Entry::File("etc/pacman.d/mirrorlist", b"old\n"),
+        Entry::File("etc/pacman.d/gnupg/pubring.gpg", b"keys"),
+        Entry::File("etc/pacman.conf", b"[options]\n"),
+    ]);
+    let update = tarball(&[
+        Entry::File("etc/pacman.d/.wh..wh..opq", b""),
+        Entry::File("etc/pacman.d/mirrorlist", b"new\n"),
+    ]);
+    let image = open_tarball("image-opaque", &docker_save(&[base, update]));
+    assert_eq!(image.file("etc/pacman.d/mirrorlist"), Some(&b"new\n"[..]));
+    assert_eq!(image.file("etc/pacman.d/gnupg/pubring.gpg"), None);
+    assert_eq!(image.file("etc/pacman.conf"), Some(&b"[options]\n"[..]));
+}
+
+#[test]
+fn hard_links_share_contents() {
+    let base = tarball(&[Entry::File("etc/pacman.conf", b"[options]\n")]);
+    let update = tarball(&[
+        Entry::File("etc/makepkg.conf", b"CARCH=x86_64\n"),
+        Entry::HardLink("etc/makepkg.conf.bak", "etc/makepkg.conf"),
+        // Hard links may also refer to files of lower layers
+        Entry::HardLink("etc/pacman.conf.bak", "etc/pacman.conf"),
+    ]);
+    let image = open_tarball("image-hardlink", &docker_save(&[base, update]));
+    assert_eq!(
+        image.file("etc/makepkg.conf.bak"),
+        Some(&b"CARCH=x86_64\n"[..])
+    );
+    assert_eq!(image.file("etc/pacman.conf.bak"), Some(&b"[options]\n"[..]));
+}
+
+#[test]
+fn docker_save_layer_paths_are_normalized() {
+    let layer = tarball(&[Entry::File("etc/pacman.conf", b"[options]\n")]);
+    let manifest = r#"[{"Layers":["./layer0//layer.tar"]}]"#;
+    let image = tarball(&[
+        Entry::File("manifest.json", manifest.as_bytes()),
+        Entry::File("layer0/layer.tar", &layer),
+    ]);
+    let image = open_tarball("image-normalized", &image);
+    assert_eq!(image.file("etc/pacman.conf"), Some(&b"[options]\n"[..]));
+}
+
+#[test]
+fn docker_save_layers_outside_the_tarball_are_rejected() {
+    let dir = common::temp_dir("image-escape");
+    for layer in ["/layer.tar", "../layer.tar", "layer0/../../layer.tar"] {
+        let manifest = format!(r#"[{{"Layers":["{layer}"]}}]"#);
+        let path = dir.join("image.tar");
+        std::fs::write(
+            &path,
+            tarball(&[Entry::File("manifest.json", manifest.as_bytes())]),
+        )
+        .unwrap();
+        let result = ContainerImage::open(&path);
+        assert!(
+            matches!(result, Err(ImageError::Invalid(_))),
+            "{layer}: {result:?}"
+        );
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,38 @@
+use alpacka::json::Json;
+
+#[test]
+fn parse_and_print() {
+    let src = r#"{"name":"pacman","size":4928512,"tags":["a\"b",null,true],"empty":{}}"#;
+    let json = Json::parse(src).unwrap();
+    assert_eq!(json.get("name").and_then(Json::as_str), Some("pacman"));
+    assert_eq!(json.get("size"), Some(&Json::uint(4_928_512)));
+    assert_eq!(json.get("tags").map(Json::as_array).unwrap().len(), 3);
+    assert_eq!(json.to_string(), src);
+}
+
+#[test]
+fn deep_nesting_is_rejected() {
+    let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+    assert!(Json::parse(&nested(128)).is_ok());
+    assert_eq!(Json::parse(&nested(129)).unwrap_err().0, 128);
+    assert!(Json::parse(&"[".repeat(1_000_000)).is_err());
+}
+
+#[test]
+fn unicode_escapes() {
+    let parse = |src: &str| Json::parse(src).map(|json| json.as_str().unwrap().to_owned());
+    assert_eq!(parse(r#""\u00e9\u00C9""#).unwrap(), "éÉ");
+    assert_eq!(parse(r#""\ud83d\ude00""#).unwrap(), "😀");
+    // Only hex digits, no sign
+    assert!(parse(r#""\u+0e9""#).is_err());
+    assert!(parse(r#""\u-0e9""#).is_err());
+    assert!(parse(r#""\u00g9""#).is_err());
+    assert!(parse(r#""\u00e""#).is_err());
+    // A high surrogate must be followed by a low surrogate
+    assert!(parse(r#""\ud83d""#).is_err());
+    assert!(parse(r#""\ud83dx""#).is_err());
+    assert!(parse(r#""\ud83dA""#).is_err());
+    assert!(parse(r#""\ud83d\ud83d""#).is_err());
+    // And a low surrogate can't stand alone
+    assert!(parse(r#""\ude00""#).is_err());
+}
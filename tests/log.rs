@@ -0,0 +1,154 @@
+use {
+    alpacka::log::{
+        self, LogEntry, LogEvent, LogSource, LogTime, PkgAction, PkgChange, TransactionOutcome,
+    },
+    jiff::{Timestamp, civil::date, tz::TimeZone},
+};
+
+const LOG: &str = include_str!("fixtures/pacman.log");
+
+fn entries() -> Vec<LogEntry> {
+    log::parse(LOG).filter_map(Result::ok).collect()
+}
+
+fn ts(src: &str) -> LogTime {
+    LogTime::Timestamp(src.parse::<Timestamp>().unwrap())
+}
+
+/// A change as `action name old -> new`, for comparing in one go
+fn describe(change: &PkgChange) -> String {
+    format!(
+        "{:?} {} {} -> {}",
+        change.action,
+        change.name,
+        change.old_version.as_deref().unwrap_or("-"),
+        change.new_version.as_deref().unwrap_or("-")
+    )
+}
+
+#[test]
+fn malformed_lines_are_errors() {
+    let errors: Vec<_> = log::parse(LOG).filter_map(Result::err).collect();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].line, 31);
+    assert_eq!(errors[0].content, "[2019-05-02T08:41");
+    // The empty line is skipped
+    assert_eq!(entries().len(), 29);
+}
+
+#[test]
+fn timestamps() {
+    let entries = entries();
+    // Before pacman 5.1: local time, minute precision
+    let old = entries[0].time;
+    assert_eq!(old, LogTime::Local(date(2012, 3, 4).at(10, 11, 0, 0)));
+    assert_eq!(
+        old.to_timestamp(&TimeZone::fixed(jiff::tz::offset(1)))
+            .unwrap(),
+        "2012-03-04T09:11:00Z".parse::<Timestamp>().unwrap()
+    );
+    // ISO 8601 with the UTC offset
+    assert_eq!(entries[4].time, ts("2019-05-01T10:00:00Z"));
+    assert_eq!(
+        entries[4].time.to_timestamp(&TimeZone::UTC).unwrap(),
+        "2019-05-01T10:00:00Z".parse::<Timestamp>().unwrap()
+    );
+}
+
+#[test]
+fn sources() {
+    let sources: Vec<LogSource> = entries().into_iter().map(|entry| entry.source).collect();
+    assert_eq!(sources[0], LogSource::Untagged);
+    assert_eq!(sources[4], LogSource::Pacman);
+    assert_eq!(sources[6], LogSource::Alpm);
+    assert_eq!(sources[9], LogSource::AlpmScriptlet);
+    assert_eq!(sources[12], LogSource::Other("yay".into()));
+}
+
+#[test]
+fn events() {
+    let entries = entries();
+    assert!(matches!(&entries[0].event, LogEvent::Command(cmd) if cmd == "pacman -S vim"));
+    assert!(
+        matches!(&entries[5].event, LogEvent::Message(msg) if msg == "synchronizing package lists")
+    );
+    assert!(matches!(entries[6].event, LogEvent::TransactionStarted));
+    let LogEvent::Package(change) = &entries[8].event else {
+        panic!("{:?}", entries[8].event);
+    };
+    assert_eq!(change.action, PkgAction::Downgrade);
+    assert_eq!(describe(change), "Downgrade zlib 1:1.2.11-4 -> 1:1.2.11-3");
+    assert!(matches!(&entries[9].event, LogEvent::Message(msg) if msg == ">>> Updated hwdb"));
+    assert!(matches!(&entries[10].event, LogEvent::Warning(msg) if msg.ends_with(".pacnew")));
+    assert!(matches!(entries[11].event, LogEvent::TransactionCompleted));
+    assert!(matches!(
+        entries[24].event,
+        LogEvent::TransactionInterrupted
+    ));
+    assert!(matches!(&entries[27].event, LogEvent::Error(msg) if msg.starts_with("failed")));
+    assert!(matches!(entries[28].event, LogEvent::TransactionFailed));
+}
+
+#[test]
+fn transactions() {
+    let transactions = log::transactions(&entries());
+    let summary: Vec<(Option<&str>, TransactionOutcome, Vec<String>)> = transactions
+        .iter()
+        .map(|tr| {
+            (
+                tr.command.as_deref(),
+                tr.outcome,
+                tr.changes.iter().map(describe).collect(),
+            )
+        })
+        .collect();
+    // The failed transaction changed nothing, so it's left out
+    assert_eq!(
+        summary,
+        [
+            (
+                Some("pacman -S vim"),
+                TransactionOutcome::Unknown,
+                vec!["Install vim - -> 7.3.446-1".to_owned()]
+            ),
+            (
+                Some("pacman -R nano"),
+                TransactionOutcome::Unknown,
+                vec!["Remove nano 2.2.6-1 -> -".to_owned()]
+            ),
+            (
+                Some("pacman -Syu"),
+                TransactionOutcome::Completed,
+                vec![
+                    "Upgrade bash 5.0.003-1 -> 5.0.007-1".to_owned(),
+                    "Downgrade zlib 1:1.2.11-4 -> 1:1.2.11-3".to_owned(),
+                ]
+            ),
+            // Never completed: the next transaction started first
+            (
+                Some("pacman -U /tmp/foo-1.0-1-x86_64.pkg.tar.zst"),
+                TransactionOutcome::Unknown,
+                vec![
+                    "Install foo - -> 1.0-1".to_owned(),
+                    "Reinstall bar 2.0-1 -> 2.0-1".to_owned(),
+                ]
+            ),
+            (
+                Some("pacman -S baz"),
+                TransactionOutcome::Completed,
+                vec!["Install baz - -> 3.0-1".to_owned()]
+            ),
+            (
+                Some("pacman -Rs qux"),
+                TransactionOutcome::Interrupted,
+                vec!["Remove qux 1-1 -> -".to_owned()]
+            ),
+        ]
+    );
+    let upgrade = &transactions[2];
+    assert_eq!(upgrade.started, ts("2019-05-01T10:00:05Z"));
+    assert_eq!(upgrade.ended, Some(ts("2019-05-01T10:00:06Z")));
+    assert_eq!(upgrade.warnings.len(), 1);
+    assert_eq!(upgrade.scriptlet_output, [">>> Updated hwdb"]);
+    assert_eq!(transactions[3].ended, None);
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use {
+    alpacka::mtree::{self, Mismatch},
+    std::os::unix::fs::PermissionsExt as _,
+};
+
+#[test]
+fn compare_entries_with_files_on_disk() {
+    let root = common::temp_dir("mtree");
+    std::fs::create_dir(root.join("etc")).unwrap();
+    std::fs::set_permissions(root.join("etc"), std::fs::Permissions::from_mode(0o755)).unwrap();
+    std::fs::write(root.join("etc/motd"), "hello\n").unwrap();
+    std::fs::write(root.join("etc/issue"), "Arch Linux\n").unwrap();
+    std::fs::set_permissions(
+        root.join("etc/issue"),
+        std::fs::Permissions::from_mode(0o600),
+    )
+    .unwrap();
+    std::os::unix::fs::symlink("motd", root.join("etc/motd.link")).unwrap();
+    // The checksums are the sha256 of "hello\n"
+    let entries = mtree::parse(
+        "#mtree\n\
+         /set type=file uid=0 gid=0 mode=644\n\
+         ./.PKGINFO size=100\n\
+         ./etc type=dir mode=755\n\
+         ./etc/motd size=6 sha256digest=5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03\n\
+         ./etc/issue size=6 sha256digest=5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03\n\
+         ./etc/motd.link type=link link=issue\n\
+         ./etc/hostname size=6\n",
+    );
+    let compare = |path: &str| {
+        let entry = entries.iter().find(|entry| &*entry.path == path).unwrap();
+        entry.compare(&root)
+    };
+    assert_eq!(compare("etc").unwrap(), []);
+    assert_eq!(compare("etc/motd").unwrap(), []);
+    assert_eq!(
+        compare("etc/issue").unwrap(),
+        [
+            Mismatch::Mode {
+                expected: 0o644,
+                actual: 0o600
+            },
+            Mismatch::Size {
+                expected: 6,
+                actual: 11
+            },
+        ]
+    );
+    assert_eq!(compare("etc/motd.link").unwrap(), [Mismatch::Link]);
+    assert_eq!(
+        compare("etc/hostname").unwrap_err().kind(),
+        std::io::ErrorKind::NotFound
+    );
+
+    std::fs::write(root.join("etc/motd"), "hullo\n").unwrap();
+    assert_eq!(compare("etc/motd").unwrap(), [Mismatch::Checksum]);
+    std::fs::remove_file(root.join("etc/motd")).unwrap();
+    std::fs::create_dir(root.join("etc/motd")).unwrap();
+    assert_eq!(compare("etc/motd").unwrap(), [Mismatch::Type]);
+    std::fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,170 @@
+#![cfg(feature = "pgp")]
+
+use {
+    alpacka::pgp::{Keyring, SigCheck, SigStatus, Trust},
+    std::path::Path,
+};
+
+const FIXTURES: &str = "tests/fixtures/pgp";
+const DATA: &[u8] = b"The quick brown fox jumps over the lazy dog\n";
+
+fn keyring(files: &[&str]) -> Keyring {
+    let mut keyring = Keyring::default();
+    for file in files {
+        let keys = std::fs::read(Path::new(FIXTURES).join(file)).unwrap();
+        keyring.add_keys(&keys).unwrap();
+    }
+    keyring
+}
+
+fn verify(keyring: &Keyring, data: &[u8], sig: &str) -> SigCheck {
+    let sig = std::fs::read(Path::new(FIXTURES).join(sig)).unwrap();
+    let mut checks = keyring.verify(data, &sig).unwrap();
+    assert_eq!(checks.len(), 1);
+    checks.remove(0)
+}
+
+fn trust_rsa(keyring: &mut Keyring) {
+    let rsa = keyring.fingerprints().next().unwrap();
+    keyring.add_owner_trust(&format!("{rsa}:5:\n"));
+}
+
+#[test]
+fn rsa_signature() {
+    let keyring = keyring(&["rsa.asc"]);
+    let check = verify(&keyring, DATA, "data.rsa.sig");
+    assert_eq!(check.status, SigStatus::Valid);
+    assert_eq!(
+        check.user_id.as_deref(),
+        Some("RSA Tester <rsa@example.org>")
+    );
+    assert_eq!(check.primary_fingerprint, keyring.fingerprints().next());
+    assert_eq!(check.validity, Trust::Unknown);
+    assert!(!check.is_trusted());
+}
+
+#[test]
+fn ed25519_subkey_signature() {
+    let keyring = keyring(&["ed25519.gpg"]);
+    let check = verify(&keyring, DATA, "data.ed25519.sig");
+    assert_eq!(check.status, SigStatus::Valid);
+    assert_eq!(check.primary_fingerprint, keyring.fingerprints().next());
+    assert_ne!(
+        Some(check.key_id),
+        check.primary_fingerprint.map(|fpr| fpr.key_id())
+    );
+}
+
+#[test]
+fn tampered_data_is_bad() {
+    let keyring = keyring(&["rsa.asc", "ed25519.gpg"]);
+    let tampered = b"The quick brown fox jumps over the lazy cat\n";
+    for sig in ["data.rsa.sig", "data.ed25519.sig"] {
+        assert_eq!(verify(&keyring, tampered, sig).status, SigStatus::Bad);
+    }
+}
+
+#[test]
+fn missing_key_is_unknown() {
+    let keyring = keyring(&["rsa.asc"]);
+    let check = verify(&keyring, DATA, "data.ed25519.sig");
+    assert_eq!(check.status, SigStatus::UnknownKey);
+    assert_eq!(check.primary_fingerprint, None);
+}
+
+#[test]
+fn expired_signature() {
+    let keyring = keyring(&["rsa.asc"]);
+    let check = verify(&keyring, DATA, "data.sig-expired.sig");
+    assert_eq!(check.status, SigStatus::SigExpired);
+}
+
+#[test]
+fn subkey_expires_with_primary_key() {
+    let keyring = keyring(&["primary-expired.gpg"]);
+    let check = verify(&keyring, DATA, "data.primary-expired.sig");
+    assert_eq!(check.status, SigStatus::KeyExpired);
+}
+
+#[test]
+fn revoked_key() {
+    let mut keyring = keyring(&["rsa.asc"]);
+    let rsa = keyring.fingerprints().next().unwrap();
+    keyring.add_revoked(&format!("{rsa}\n"));
+    let check = verify(&keyring, DATA, "data.rsa.sig");
+    assert_eq!(check.status, SigStatus::KeyRevoked);
+    assert_eq!(check.owner_trust, Trust::Never);
+}
+
+#[test]
+fn signing_subkey_needs_back_signature() {
+    let keyring_ok = keyring(&["back-signed.gpg"]);
+    let check = verify(&keyring_ok, DATA, "data.subkey.sig");
+    assert_eq!(check.status, SigStatus::Valid);
+    let keyring = keyring(&["not-back-signed.gpg"]);
+    let check = verify(&keyring, DATA, "data.subkey.sig");
+    assert_eq!(check.status, SigStatus::UnusableKey);
+}
+
+#[test]
+fn back_signature_cannot_embed_another_signature() {
+    let keyring = keyring(&["nested-back-signed.gpg"]);
+    let check = verify(&keyring, DATA, "data.subkey.sig");
+    assert_eq!(check.status, SigStatus::UnusableKey);
+}
+
+#[test]
+fn deeply_nested_embedded_signatures() {
+    // Each signature embeds the previous one in its unhashed area
+    let mut sig = Vec::new();
+    for _ in 0..3000 {
+        let mut subpacket = vec![255];
+        subpacket.extend(u32::try_from(sig.len() + 1).unwrap().to_be_bytes());
+        subpacket.push(32);
+        subpacket.append(&mut sig);
+        sig = vec![4, 0x00, 22, 8, 0, 0];
+        sig.extend(u16::try_from(subpacket.len()).unwrap().to_be_bytes());
+        sig.extend(subpacket);
+        sig.extend([0, 0, 0, 0, 0, 0]);
+    }
+    let mut packet = vec![0xC2, 0xFF];
+    packet.extend(u32::try_from(sig.len()).unwrap().to_be_bytes());
+    packet.extend(sig);
+    let checks = keyring(&["rsa.asc"]).verify(DATA, &packet).unwrap();
+    assert_eq!(checks[0].status, SigStatus::UnknownKey);
+}
+
+#[test]
+fn certification_only_key_cannot_sign() {
+    let keyring = keyring(&["back-signed.gpg"]);
+    let check = verify(&keyring, DATA, "data.cert-only.sig");
+    assert_eq!(check.status, SigStatus::UnusableKey);
+}
+
+#[test]
+fn certified_key_is_valid() {
+    let mut keyring = keyring(&["rsa.asc", "ed25519.gpg"]);
+    trust_rsa(&mut keyring);
+    let introducer = verify(&keyring, DATA, "data.rsa.sig");
+    assert_eq!(
+        (introducer.validity, introducer.owner_trust),
+        (Trust::Full, Trust::Full)
+    );
+    let check = verify(&keyring, DATA, "data.ed25519.sig");
+    assert_eq!(check.status, SigStatus::Valid);
+    assert_eq!(
+        (check.validity, check.owner_trust),
+        (Trust::Full, Trust::Unknown)
+    );
+    assert!(check.is_trusted());
+}
+
+#[test]
+fn revoked_certification_does_not_count() {
+    let mut keyring = keyring(&["rsa.asc", "ed25519-cert-revoked.gpg"]);
+    trust_rsa(&mut keyring);
+    let check = verify(&keyring, DATA, "data.ed25519.sig");
+    assert_eq!(check.status, SigStatus::Valid);
+    assert_eq!(check.validity, Trust::Unknown);
+    assert!(!check.is_trusted());
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use {
+    alpacka::{
+        read_syncdb_bytes,
+        repo::{AddOutcome, RepoBuilder},
+    },
+    std::path::{Path, PathBuf},
+};
+
+/// Write an uncompressed package file for `name` in `dir`, containing `usr/bin/{name}`
+fn package(dir: &Path, name: &str, version: &str) -> PathBuf {
+    let pkginfo = format!("pkgname = {name}\npkgver = {version}\narch = x86_64\n");
+    let mut builder = tar::Builder::new(Vec::new());
+    for (path, data) in [
+        (".PKGINFO", pkginfo.as_bytes()),
+        (&*format!("usr/bin/{name}"), b"#!/bin/sh\n"),
+    ] {
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(0o644);
+        header.set_size(data.len() as u64);
+        builder.append_data(&mut header, path, data).unwrap();
+    }
+    let path = dir.join(format!("{name}-{version}-x86_64.pkg.tar"));
+    std::fs::write(&path, builder.into_inner().unwrap()).unwrap();
+    path
+}
+
+#[test]
+fn round_trip() {
+    let dir = common::temp_dir("repo");
+    let mut repo = RepoBuilder::open(&dir, "test").unwrap();
+    assert_eq!(
+        repo.add(package(&dir, "foo", "1.0-1")).unwrap(),
+        AddOutcome::Added
+    );
+    assert_eq!(
+        repo.add(package(&dir, "bar", "2.0-1")).unwrap(),
+        AddOutcome::Added
+    );
+    assert_eq!(
+        repo.add(package(&dir, "baz", "1-1")).unwrap(),
+        AddOutcome::Added
+    );
+    assert_eq!(
+        repo.add_if_newer(package(&dir, "foo", "0.9-1")).unwrap(),
+        AddOutcome::Skipped {
+            existing_version: "1.0-1".parse().unwrap()
+        }
+    );
+    assert!(repo.remove("baz"));
+    assert!(!repo.remove("baz"));
+    repo.write().unwrap();
+
+    for (link, archive) in [
+        ("test.db", "test.db.tar.gz"),
+        ("test.files", "test.files.tar.gz"),
+    ] {
+        assert_eq!(
+            std::fs::read_link(dir.join(link)).unwrap(),
+            Path::new(archive)
+        );
+    }
+    let db = read_syncdb_bytes(&std::fs::read(dir.join("test.db")).unwrap()).unwrap();
+    assert_eq!(common::names(&db), ["bar", "foo"]);
+    let foo = &db[1].desc;
+    assert_eq!(foo.version.as_str(), "1.0-1");
+    assert_eq!(foo.filename.as_deref(), Some("foo-1.0-1-x86_64.pkg.tar"));
+    assert_eq!(
+        foo.c_size,
+        std::fs::metadata(dir.join("foo-1.0-1-x86_64.pkg.tar"))
+            .unwrap()
+            .len()
+    );
+    assert!(foo.sha256sum.is_some());
+    let files = read_syncdb_bytes(&std::fs::read(dir.join("test.files")).unwrap()).unwrap();
+    assert_eq!(
+        files[1]
+            .files
+            .iter()
+            .map(|file| &**file)
+            .collect::<Vec<_>>(),
+        ["usr/bin/foo"]
+    );
+
+    // Reopening picks up the written entries, and writing again replaces the symlinks
+    let mut repo = RepoBuilder::open(&dir, "test").unwrap();
+    assert_eq!(common::names(repo.entries()), ["bar", "foo"]);
+    assert_eq!(
+        repo.add_if_newer(package(&dir, "foo", "1.1-1")).unwrap(),
+        AddOutcome::Replaced {
+            old_version: "1.0-1".parse().unwrap()
+        }
+    );
+    repo.write().unwrap();
+    let db = read_syncdb_bytes(&std::fs::read(dir.join("test.db")).unwrap()).unwrap();
+    assert_eq!(db[1].desc.version.as_str(), "1.1-1");
+    // No temporary files are left behind
+    let leftovers: Vec<String> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with('.'))
+        .collect();
+    assert!(leftovers.is_empty(), "{leftovers:?}");
+    std::fs::remove_dir_all(dir).unwrap();
+}
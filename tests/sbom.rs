@@ -0,0 +1,188 @@
+mod common;
+
+use {
+    alpacka::{
+        json::Json,
+        sbom::{Sbom, purl},
+    },
+    std::io::Write as _,
+};
+
+fn spdx(pkgs: &[alpacka::Pkg]) -> Json {
+    let created = "2024-01-01T00:00:00Z".parse().unwrap();
+    Json::parse(&Sbom::new("host", pkgs).created(created).to_spdx_json()).unwrap()
+}
+
+fn field<'a>(json: &'a Json, key: &str) -> &'a str {
+    json.get(key).and_then(Json::as_str).unwrap()
+}
+
+#[test]
+fn spdx_licenses() {
+    let pkgs = [
+        common::pkg("foo", "1.0-1", "%LICENSE%\nGPL\nMIT\n\n"),
+        common::pkg("bar", "1.0-1", "%LICENSE%\nLicenseRef-Bar\n\n"),
+        common::pkg(
+            "baz",
+            "1.0-1",
+            "%LICENSE%\nApache-2.0 OR LicenseRef-Baz\ncustom:baz\n\n",
+        ),
+    ];
+    let doc = spdx(&pkgs);
+    let declared: Vec<&str> = doc
+        .get("packages")
+        .unwrap()
+        .as_array()
+        .iter()
+        .map(|pkg| field(pkg, "licenseDeclared"))
+        .collect();
+    assert_eq!(
+        declared,
+        [
+            "LicenseRef-GPL AND MIT",
+            "LicenseRef-Bar",
+            "(Apache-2.0 OR LicenseRef-Baz) AND LicenseRef-custom-baz",
+        ]
+    );
+    let refs: Vec<&str> = doc
+        .get("hasExtractedLicensingInfos")
+        .unwrap()
+        .as_array()
+        .iter()
+        .map(|info| field(info, "licenseId"))
+        .collect();
+    assert_eq!(
+        refs,
+        [
+            "LicenseRef-Bar",
+            "LicenseRef-Baz",
+            "LicenseRef-GPL",
+            "LicenseRef-custom-baz"
+        ]
+    );
+}
+
+#[test]
+fn spdx_dependencies_respect_versions() {
+    let pkgs = [
+        common::pkg("app", "1.0-1", "%DEPENDS%\nlibfoo>=2\nsh\n\n"),
+        common::pkg("libfoo", "1.5-1", ""),
+        common::pkg("bash", "5.2-1", "%PROVIDES%\nsh\n\n"),
+    ];
+    let doc = spdx(&pkgs);
+    let depends: Vec<&str> = doc
+        .get("relationships")
+        .unwrap()
+        .as_array()
+        .iter()
+        .filter(|rel| field(rel, "relationshipType") == "DEPENDS_ON")
+        .map(|rel| field(rel, "relatedSpdxElement"))
+        .collect();
+    assert_eq!(depends, ["SPDXRef-Package-2-bash"]);
+    assert!(doc.get("files").is_none());
+}
+
+#[test]
+fn purls_are_percent_encoded() {
+    let pkg = common::pkg("libc++", "1:18.1.8-1", "");
+    assert_eq!(
+        purl(&pkg.desc),
+        "pkg:alpm/arch/libc%2B%2B@1%3A18.1.8-1?arch=x86_64"
+    );
+}
+
+#[test]
+fn cyclonedx_files_and_dependencies() {
+    // The checksums are the sha256 and md5 of "hello\n"
+    let mtree = "#mtree\n\
+        /set type=file uid=0 gid=0 mode=644\n\
+        ./.PKGINFO size=100 sha256digest=0000000000000000000000000000000000000000000000000000000000000000\n\
+        ./usr type=dir mode=755\n\
+        ./usr/lib type=dir mode=755\n\
+        ./usr/lib/libc++.so.1 size=6 md5digest=b1946ac92492d2347c6235b4d2611184 sha256digest=5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03\n\
+        ./usr/lib/libc++.so type=link link=libc++.so.1\n";
+    let db_path = common::temp_dir("sbom");
+    let entry = db_path.join("local/libc++-1:18.1.8-1");
+    std::fs::create_dir_all(&entry).unwrap();
+    let mut gz = flate2::write::GzEncoder::new(
+        std::fs::File::create(entry.join("mtree")).unwrap(),
+        flate2::Compression::default(),
+    );
+    gz.write_all(mtree.as_bytes()).unwrap();
+    gz.finish().unwrap();
+
+    let pkgs = [
+        common::pkg("app", "1.0-1", "%DEPENDS%\nlibc++>=18\nsh\n\n"),
+        common::pkg("libc++", "1:18.1.8-1", ""),
+        common::pkg("bash", "5.2-1", "%PROVIDES%\nsh\n\n"),
+    ];
+    let created = "2024-01-01T00:00:00Z".parse().unwrap();
+    let sbom = Sbom::new("host", &pkgs)
+        .created(created)
+        .read_files(&db_path)
+        .unwrap();
+    let doc = Json::parse(&sbom.to_cyclonedx_json()).unwrap();
+    std::fs::remove_dir_all(db_path).unwrap();
+
+    let components = doc.get("components").unwrap().as_array();
+    let libcxx = &components[1];
+    assert_eq!(
+        field(libcxx, "purl"),
+        "pkg:alpm/arch/libc%2B%2B@1%3A18.1.8-1?arch=x86_64"
+    );
+    // Only regular files are listed, without the package metadata
+    let files = libcxx.get("components").unwrap().as_array();
+    assert_eq!(files.len(), 1);
+    assert_eq!(field(&files[0], "type"), "file");
+    assert_eq!(field(&files[0], "name"), "/usr/lib/libc++.so.1");
+    let hashes: Vec<(&str, &str)> = files[0]
+        .get("hashes")
+        .unwrap()
+        .as_array()
+        .iter()
+        .map(|hash| (field(hash, "alg"), field(hash, "content")))
+        .collect();
+    assert_eq!(
+        hashes,
+        [
+            (
+                "SHA-256",
+                "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03"
+            ),
+            ("MD5", "b1946ac92492d2347c6235b4d2611184"),
+        ]
+    );
+    // Packages without an mtree file have no file list
+    assert!(components[0].get("components").is_none());
+
+    let dependencies: Vec<(&str, Vec<&str>)> = doc
+        .get("dependencies")
+        .unwrap()
+        .as_array()
+        .iter()
+        .map(|dep| {
+            let depends_on = dep.get("dependsOn").unwrap().as_array();
+            (
+                field(dep, "ref"),
+                depends_on
+                    .iter()
+                    .map(|purl| purl.as_str().unwrap())
+                    .collect(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        dependencies,
+        [
+            (
+                "pkg:alpm/arch/app@1.0-1?arch=x86_64",
+                vec![
+                    "pkg:alpm/arch/libc%2B%2B@1%3A18.1.8-1?arch=x86_64",
+                    "pkg:alpm/arch/bash@5.2-1?arch=x86_64",
+                ]
+            ),
+            ("pkg:alpm/arch/libc%2B%2B@1%3A18.1.8-1?arch=x86_64", vec![]),
+            ("pkg:alpm/arch/bash@5.2-1?arch=x86_64", vec![]),
+        ]
+    );
+}
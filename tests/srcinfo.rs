@@ -0,0 +1,122 @@
+use alpacka::srcinfo::{SrcInfo, SrcInfoError};
+
+const SPLIT: &str = "\
+pkgbase = pipewire
+\tpkgdesc = Low-latency audio/video router and processor
+\tpkgver = 1.2.3
+\tpkgrel = 2
+\tepoch = 1
+\turl = https://pipewire.org
+\tarch = x86_64
+\tarch = aarch64
+\tlicense = MIT
+\tcheckdepends = desktop-file-utils
+\tmakedepends = meson
+\tmakedepends_x86_64 = valgrind
+\tdepends = glibc
+\tsource = git+https://gitlab.freedesktop.org/pipewire/pipewire.git
+\tsource_aarch64 = arm.patch
+\tsha256sums = SKIP
+\tsha256sums_aarch64 = 0123abcd
+
+pkgname = pipewire
+\tinstall = pipewire.install
+\tchangelog = pipewire.changelog
+\tdepends = glibc
+\tdepends = libpipewire
+\tdepends_x86_64 = lib32-glibc
+\toptdepends = pipewire-docs: Documentation
+\tbackup = etc/pipewire/pipewire.conf
+
+pkgname = pipewire-docs
+\tpkgdesc = Documentation for PipeWire
+\tarch = any
+\tdepends =
+\tcheckdepends =
+";
+
+#[test]
+fn split_package() {
+    let info = SrcInfo::parse(SPLIT).unwrap();
+    assert_eq!(info.base.name, "pipewire");
+    assert_eq!(info.version().as_str(), "1:1.2.3-2");
+    assert_eq!(
+        info.pkg_names().collect::<Vec<_>>(),
+        ["pipewire", "pipewire-docs"]
+    );
+    assert_eq!(
+        info.values("pipewire", "depends", Some("x86_64")).unwrap(),
+        ["glibc", "libpipewire", "lib32-glibc"]
+    );
+    assert_eq!(
+        info.values("pipewire", "changelog", None).unwrap(),
+        ["pipewire.changelog"]
+    );
+    // Keys not overridden come from the base, empty overrides clear them
+    assert_eq!(
+        info.values("pipewire", "pkgdesc", None).unwrap(),
+        ["Low-latency audio/video router and processor"]
+    );
+    assert!(
+        info.values("pipewire-docs", "depends", None)
+            .unwrap()
+            .is_empty()
+    );
+    assert_eq!(info.values("missing", "depends", None), None);
+    assert_eq!(
+        info.base_values("makedepends", Some("x86_64")),
+        ["meson", "valgrind"]
+    );
+    assert_eq!(
+        info.sources("sha256sums", "aarch64"),
+        [
+            (
+                "git+https://gitlab.freedesktop.org/pipewire/pipewire.git",
+                Some("SKIP")
+            ),
+            ("arm.patch", Some("0123abcd")),
+        ]
+    );
+}
+
+#[test]
+fn split_package_descs() {
+    let info = SrcInfo::parse(SPLIT).unwrap();
+    let descs = info.pkg_descs("x86_64");
+    assert_eq!(descs.len(), 2);
+    let (main, docs) = (&descs[0], &descs[1]);
+    assert_eq!(main.base, None);
+    assert_eq!(main.arch, "x86_64");
+    assert!(main.install_script);
+    assert_eq!(main.depends.len(), 3);
+    assert_eq!(main.check_depends.len(), 1);
+    assert_eq!(main.make_depends.len(), 2);
+    assert_eq!(docs.base.as_deref(), Some("pipewire"));
+    assert_eq!(docs.arch, "any");
+    assert_eq!(docs.desc.as_deref(), Some("Documentation for PipeWire"));
+    assert!(docs.depends.is_empty() && docs.check_depends.is_empty());
+    assert!(!docs.install_script);
+    assert!(info.pkg_desc("pipewire", "riscv64").is_none());
+}
+
+#[test]
+fn invalid_files() {
+    let err = |src: &str| SrcInfo::parse(src).unwrap_err();
+    assert!(matches!(
+        err("pkgbase = a\npkgver = 1\npkgrel = 1\npkgname = a\nsource = x\n"),
+        SrcInfoError::NotOverridable(5)
+    ));
+    assert!(matches!(err("pkgver = 1\n"), SrcInfoError::NoSection(1)));
+    assert!(matches!(
+        err("pkgbase = a\npkgver\n"),
+        SrcInfoError::InvalidLine(2)
+    ));
+    assert!(matches!(
+        err("pkgbase = a\npkgver = 1\npkgname = a\n"),
+        SrcInfoError::Missing("pkgrel")
+    ));
+    assert!(matches!(
+        err("pkgbase = a\npkgver = 1\npkgrel = 1\n"),
+        SrcInfoError::Missing("pkgname")
+    ));
+}
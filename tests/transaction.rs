@@ -0,0 +1,252 @@
+mod common;
+
+use alpacka::{
+    InstallReason, Pkg, SyncDb,
+    transaction::{
+        PlanAction, PlanError, PlanReason, Planner, RemovalReason, RemovePlan, RemovePlanner,
+        RemoveReason,
+    },
+};
+
+fn sync_dbs() -> Vec<SyncDb> {
+    vec![
+        SyncDb {
+            name: "core".into(),
+            pkgs: vec![
+                common::pkg("glibc", "2.40-1", ""),
+                common::pkg("bash", "5.2-2", "%DEPENDS%\nglibc\nreadline\n\n"),
+                common::pkg("readline", "8.2-1", "%DEPENDS%\nglibc\n\n"),
+            ],
+        },
+        SyncDb {
+            name: "extra".into(),
+            pkgs: vec![
+                common::pkg("bash", "5.3-1", ""),
+                common::pkg("vim", "9.1-1", "%DEPENDS%\nbash\nvi-data\n\n"),
+                common::pkg("vim-data", "9.1-1", "%PROVIDES%\nvi-data\n\n"),
+                common::pkg("neovim-data", "0.10-1", "%PROVIDES%\nvi-data\n\n"),
+                common::pkg(
+                    "openssh",
+                    "9.8-1",
+                    "%DEPENDS%\nglibc\n\n%CONFLICTS%\ndropbear\n\n%REPLACES%\nssh\n\n",
+                ),
+                common::pkg("dropbear", "2024.85-1", "%CONFLICTS%\nopenssh\n\n"),
+            ],
+        },
+    ]
+}
+
+fn local() -> Vec<Pkg> {
+    vec![
+        common::pkg("glibc", "2.40-1", "%REASON%\n1\n\n"),
+        common::pkg("bash", "5.2-1", "%DEPENDS%\nglibc\n\n"),
+        common::pkg("ssh", "1.0-1", ""),
+        common::pkg("dropbear", "2024.85-1", "%CONFLICTS%\nopenssh\n\n"),
+    ]
+}
+
+fn installs<'a>(plan: &'a alpacka::transaction::InstallPlan) -> Vec<(&'a str, &'a str)> {
+    plan.install
+        .iter()
+        .map(|planned| (planned.repo, planned.pkg.desc.name.as_str()))
+        .collect()
+}
+
+#[test]
+fn dependencies_come_first() {
+    let (local, sync) = (local(), sync_dbs());
+    let plan = Planner::new(&local, &sync).install(&["vim"]).unwrap();
+    // The installed bash already satisfies vim
+    assert_eq!(installs(&plan), [("extra", "vim-data"), ("extra", "vim")]);
+    let reasons: Vec<_> = plan.install.iter().map(|p| p.install_reason()).collect();
+    assert_eq!(reasons, [InstallReason::Dep, InstallReason::Explicit]);
+    let choice = &plan.providers[0];
+    assert_eq!(choice.dep.name, "vi-data");
+    assert_eq!(choice.chosen.desc.name, "vim-data");
+    assert_eq!(
+        common::names(choice.alternatives.iter().copied()),
+        ["neovim-data"]
+    );
+    assert!(matches!(
+        &plan.install[0].reason,
+        PlanReason::Dependency { of, .. } if of == "vim"
+    ));
+
+    let plan = Planner::new(&local, &sync)
+        .provider("vi-data", "neovim-data")
+        .install(&["vim"])
+        .unwrap();
+    assert_eq!(plan.install[0].pkg.desc.name, "neovim-data");
+}
+
+#[test]
+fn targets_and_repositories() {
+    let (local, sync) = (local(), sync_dbs());
+    // The first repository wins, unless another one is named
+    let plan = Planner::new(&local, &sync).install(&["bash"]).unwrap();
+    assert_eq!(installs(&plan), [("core", "readline"), ("core", "bash")]);
+    assert!(
+        matches!(plan.install[1].action, PlanAction::Upgrade(old) if old.desc.version.as_str() == "5.2-1")
+    );
+    let plan = Planner::new(&local, &sync)
+        .install(&["extra/bash"])
+        .unwrap();
+    assert_eq!(installs(&plan), [("extra", "bash")]);
+    let plan = Planner::new(&local, &sync)
+        .install(&["bash>=5.2-2"])
+        .unwrap();
+    assert_eq!(installs(&plan), [("core", "readline"), ("core", "bash")]);
+
+    let plan = Planner::new(&local, &sync)
+        .needed(true)
+        .install(&["glibc", "readline"])
+        .unwrap();
+    assert_eq!(common::names(plan.up_to_date.iter().copied()), ["glibc"]);
+    assert_eq!(installs(&plan), [("core", "readline")]);
+
+    assert!(matches!(
+        Planner::new(&local, &sync).install(&["emacs"]),
+        Err(PlanError::TargetNotFound(name)) if name == "emacs"
+    ));
+}
+
+#[test]
+fn target_pulled_in_as_dependency_stays_a_target() {
+    let (local, sync) = (local(), sync_dbs());
+    let plan = Planner::new(&local, &sync)
+        .install(&["bash", "readline"])
+        .unwrap();
+    assert_eq!(installs(&plan), [("core", "readline"), ("core", "bash")]);
+    assert!(matches!(plan.install[0].reason, PlanReason::Target));
+    assert_eq!(plan.install[0].install_reason(), InstallReason::Explicit);
+}
+
+#[test]
+fn replaces_and_conflicts() {
+    let (local, sync) = (local(), sync_dbs());
+    let plan = Planner::new(&local, &sync).install(&["openssh"]).unwrap();
+    let removals: Vec<_> = plan
+        .remove
+        .iter()
+        .map(|removal| match &removal.reason {
+            RemovalReason::Replaced { by } => (removal.pkg.desc.name.as_str(), "replaced", by),
+            RemovalReason::Conflict { with, .. } => {
+                (removal.pkg.desc.name.as_str(), "conflict", with)
+            }
+        })
+        .collect();
+    assert_eq!(
+        removals,
+        [
+            ("ssh", "replaced", &"openssh".into()),
+            ("dropbear", "conflict", &"openssh".into()),
+        ]
+    );
+    assert!(matches!(
+        Planner::new(&local, &sync).install(&["openssh", "dropbear"]),
+        Err(PlanError::Conflict { .. })
+    ));
+}
+
+#[test]
+fn unresolvable_dependency() {
+    let local = local();
+    let sync = [SyncDb {
+        name: "core".into(),
+        pkgs: vec![common::pkg("bash", "5.2-2", "%DEPENDS%\nreadline>=9\n\n")],
+    }];
+    assert!(matches!(
+        Planner::new(&local, &sync).install(&["bash"]),
+        Err(PlanError::Unresolvable { pkg, dep }) if pkg == "bash" && dep.name == "readline"
+    ));
+}
+
+/// Installed packages for removals: `app` and `tool` are explicitly installed
+fn removal_fixture() -> Vec<Pkg> {
+    let dep = "%REASON%\n1\n\n";
+    vec![
+        common::pkg("app", "1.0-1", "%DEPENDS%\nlibfoo\nlibshared\n\n"),
+        common::pkg("libfoo", "1.0-1", &format!("%DEPENDS%\nlibbar\n\n{dep}")),
+        common::pkg("libbar", "1.0-1", dep),
+        common::pkg("tool", "1.0-1", "%DEPENDS%\nlibbaz\nlibshared\nlibqux\n\n"),
+        common::pkg("libbaz", "1.0-1", ""),
+        common::pkg("libshared", "1.0-1", dep),
+        common::pkg("libqux", "1.0-1", dep),
+    ]
+}
+
+fn removed<'a>(plan: &'a RemovePlan) -> Vec<&'a str> {
+    common::names(plan.remove.iter().map(|removal| removal.pkg))
+}
+
+#[test]
+fn removal_blocked_by_dependents() {
+    let local = removal_fixture();
+    let plan = RemovePlanner::new(&local).remove(&["libfoo"]).unwrap();
+    assert_eq!(removed(&plan), ["libfoo"]);
+    assert!(!plan.is_possible());
+    let blocker = &plan.blockers[0];
+    assert_eq!(
+        (
+            blocker.pkg.desc.name.as_str(),
+            blocker.dep.name.as_str(),
+            blocker.removed.desc.name.as_str()
+        ),
+        ("app", "libfoo", "libfoo")
+    );
+    assert!(matches!(
+        RemovePlanner::new(&local).remove(&["emacs"]),
+        Err(PlanError::TargetNotFound(name)) if name == "emacs"
+    ));
+}
+
+#[test]
+fn cascade_removes_dependents() {
+    let local = removal_fixture();
+    let plan = RemovePlanner::new(&local)
+        .cascade(true)
+        .remove(&["libbar"])
+        .unwrap();
+    assert!(plan.is_possible());
+    assert_eq!(removed(&plan), ["libbar", "libfoo", "app"]);
+    assert!(matches!(
+        &plan.remove[2].reason,
+        RemoveReason::Dependent { of, dep } if of == "libfoo" && dep.name == "libfoo"
+    ));
+}
+
+#[test]
+fn recursive_stops_at_explicit_and_needed_packages() {
+    let local = removal_fixture();
+    let plan = RemovePlanner::new(&local)
+        .recursive(true)
+        .remove(&["tool"])
+        .unwrap();
+    assert!(plan.is_possible());
+    // libbaz is explicitly installed and app still needs libshared
+    assert_eq!(removed(&plan), ["tool", "libqux"]);
+    assert!(matches!(
+        &plan.remove[1].reason,
+        RemoveReason::Unneeded { dep_of } if dep_of == "tool"
+    ));
+    let plan = RemovePlanner::new(&local)
+        .recursive(true)
+        .remove(&["app"])
+        .unwrap();
+    assert_eq!(removed(&plan), ["app", "libfoo", "libbar"]);
+}
+
+#[test]
+fn unneeded_keeps_needed_targets() {
+    let local = removal_fixture();
+    let plan = RemovePlanner::new(&local)
+        .unneeded(true)
+        .remove(&["libfoo", "libbar", "tool", "libqux"])
+        .unwrap();
+    assert!(plan.is_possible());
+    assert_eq!(removed(&plan), ["tool", "libqux"]);
+    assert_eq!(
+        common::names(plan.kept.iter().copied()),
+        ["libfoo", "libbar"]
+    );
+}
@@ -0,0 +1,139 @@
+use {
+    alpacka::version::{Version, vercmp},
+    std::{
+        cmp::Ordering,
+        hash::{BuildHasher, RandomState},
+    },
+};
+
+/// Cases of libalpm's `vercmptest.sh`, as (a, b, vercmp(a, b))
+const VERCMP: &[(&str, &str, i8)] = &[
+    // All similar length, no pkgrel
+    ("1.5.0", "1.5.0", 0),
+    ("1.5.1", "1.5.0", 1),
+    // Mixed length
+    ("1.5.1", "1.5", 1),
+    // With pkgrel, simple
+    ("1.5.0-1", "1.5.0-1", 0),
+    ("1.5.0-1", "1.5.0-2", -1),
+    ("1.5.0-1", "1.5.1-1", -1),
+    ("1.5.0-2", "1.5.1-1", -1),
+    // With pkgrel, mixed lengths
+    ("1.5-1", "1.5.1-1", -1),
+    ("1.5-2", "1.5.1-1", -1),
+    ("1.5-2", "1.5.1-2", -1),
+    // Mixed pkgrel inclusion
+    ("1.5", "1.5-1", 0),
+    ("1.5-1", "1.5", 0),
+    ("1.1-1", "1.1", 0),
+    ("1.0-1", "1.1", -1),
+    ("1.1-1", "1.0", 1),
+    // Alphanumeric versions
+    ("1.5b-1", "1.5-1", -1),
+    ("1.5b", "1.5", -1),
+    ("1.5b-1", "1.5", -1),
+    ("1.5b", "1.5.1", -1),
+    // From the manpage
+    ("1.0a", "1.0alpha", -1),
+    ("1.0alpha", "1.0b", -1),
+    ("1.0b", "1.0beta", -1),
+    ("1.0beta", "1.0rc", -1),
+    ("1.0rc", "1.0", -1),
+    // Alpha-dotted versions
+    ("1.5.a", "1.5", 1),
+    ("1.5.b", "1.5.a", 1),
+    ("1.5.1", "1.5.b", 1),
+    // Alpha dots and dashes
+    ("1.5.b-1", "1.5.b", 0),
+    ("1.5-1", "1.5.b", -1),
+    // Same or similar content, differing separators
+    ("2.0", "2_0", 0),
+    ("2.0_a", "2_0.a", 0),
+    ("2.0a", "2.0.a", -1),
+    ("2___a", "2_a", 1),
+    // Epoch included version comparisons
+    ("0:1.0", "0:1.0", 0),
+    ("0:1.0", "0:1.1", -1),
+    ("1:1.0", "0:1.0", 1),
+    ("1:1.0", "0:1.1", 1),
+    ("1:1.0", "2:1.1", -1),
+    // Epoch and sometimes present pkgrel
+    ("1:1.0", "0:1.0-1", 1),
+    ("1:1.0-1", "0:1.1-1", 1),
+    // Epoch included on one version
+    ("0:1.0", "1.0", 0),
+    ("0:1.0", "1.1", -1),
+    ("0:1.1", "1.0", 1),
+    ("1:1.0", "1.0", 1),
+    ("1:1.0", "1.1", 1),
+    ("1:1.1", "1.1", 1),
+];
+
+fn version(s: &str) -> Version {
+    s.parse().unwrap()
+}
+
+#[test]
+fn vercmp_matches_libalpm() {
+    for &(a, b, expected) in VERCMP {
+        let expected = expected.cmp(&0);
+        assert_eq!(vercmp(a, b), expected, "vercmp {a} {b}");
+        assert_eq!(vercmp(b, a), expected.reverse(), "vercmp {b} {a}");
+    }
+}
+
+#[test]
+fn ord_follows_vercmp() {
+    for &(a, b, _) in VERCMP {
+        let (va, vb) = (version(a), version(b));
+        let ord = va.cmp(&vb);
+        assert_eq!(vb.cmp(&va), ord.reverse(), "{a} {b}");
+        match vercmp(a, b) {
+            Ordering::Equal => {
+                // Only a missing pkgrel breaks ties
+                let pkgrels = (va.pkgrel().is_some(), vb.pkgrel().is_some());
+                assert_eq!(ord, pkgrels.0.cmp(&pkgrels.1), "{a} {b}");
+            }
+            vercmp => assert_eq!(ord, vercmp, "{a} {b}"),
+        }
+    }
+    let mut versions: Vec<Version> = ["1.0-1", "1:0.1-1", "1.0", "1.0a-1", "1.0-2"]
+        .into_iter()
+        .map(version)
+        .collect();
+    versions.sort();
+    let sorted: Vec<&str> = versions.iter().map(Version::as_str).collect();
+    assert_eq!(sorted, ["1.0a-1", "1.0", "1.0-1", "1.0-2", "1:0.1-1"]);
+}
+
+#[test]
+fn equal_versions_hash_equal() {
+    let state = RandomState::new();
+    for &(a, b, _) in VERCMP {
+        let (va, vb) = (version(a), version(b));
+        if va == vb {
+            assert_eq!(state.hash_one(&va), state.hash_one(&vb), "{a} {b}");
+        }
+    }
+    for (a, b) in [("1.0", "1.00"), ("01:1.0-1", "1:1.0-1"), ("1.0.", "1.0_")] {
+        assert_eq!(version(a), version(b));
+        assert_eq!(state.hash_one(version(a)), state.hash_one(version(b)));
+    }
+    assert_ne!(version("1.0."), version("1.0"));
+}
+
+#[test]
+fn parse() {
+    let v = version("2:1.2.3-4.1");
+    assert_eq!(
+        (v.epoch(), v.pkgver(), v.pkgrel()),
+        (Some("2"), "1.2.3", Some("4.1"))
+    );
+    let v = version("1.2");
+    assert_eq!((v.epoch(), v.pkgver(), v.pkgrel()), (None, "1.2", None));
+    for invalid in [
+        "", ":1", ":1-1", "a-b-c", "1:2:3", "x:1", "1.0-", "1-2:3", "-1", "1.0 -1",
+    ] {
+        assert!(invalid.parse::<Version>().is_err(), "{invalid:?}");
+    }
+}
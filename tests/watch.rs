@@ -0,0 +1,135 @@
+#![cfg(feature = "watch")]
+
+mod common;
+
+use {
+    alpacka::{
+        version::Version,
+        watch::{DbEvent, DbWatcher, split_entry_name},
+        write::{EntryExtras, LocalDbWriter},
+    },
+    common::pkg,
+    std::{sync::mpsc, time::Duration},
+};
+
+fn ver(ver: &str) -> Version {
+    ver.parse().unwrap()
+}
+
+/// An event as `kind name old -> new`, for comparing in one go
+fn describe(event: &DbEvent) -> String {
+    match event {
+        DbEvent::Installed(pkg) => format!("installed {} {}", pkg.desc.name, pkg.desc.version),
+        DbEvent::Removed { name, version } => format!("removed {name} {version}"),
+        DbEvent::Upgraded { old_version, pkg } => format!(
+            "upgraded {} {old_version} -> {}",
+            pkg.desc.name, pkg.desc.version
+        ),
+        DbEvent::Downgraded { old_version, pkg } => format!(
+            "downgraded {} {old_version} -> {}",
+            pkg.desc.name, pkg.desc.version
+        ),
+        DbEvent::Reinstalled(pkg) => format!("reinstalled {} {}", pkg.desc.name, pkg.desc.version),
+        DbEvent::RepoRefreshed { repo, pkgs } => format!("refreshed {repo} {}", pkgs.len()),
+    }
+}
+
+/// Wait for the next changes, failing the test instead of blocking forever
+fn next_events(mut watcher: DbWatcher) -> (DbWatcher, Vec<String>) {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let events = watcher.wait().unwrap();
+        tx.send((watcher, events.iter().map(describe).collect()))
+            .unwrap();
+    });
+    rx.recv_timeout(Duration::from_secs(10))
+        .expect("no database change noticed")
+}
+
+#[test]
+fn entry_names() {
+    assert_eq!(split_entry_name("bash-5.2-1"), Some(("bash", "5.2-1")));
+    assert_eq!(
+        split_entry_name("python-foo-bar-1:2.0.r3-2.1"),
+        Some(("python-foo-bar", "1:2.0.r3-2.1"))
+    );
+    assert_eq!(split_entry_name("bash-5.2"), None);
+    assert_eq!(split_entry_name("ALPM_DB_VERSION"), None);
+}
+
+#[test]
+fn entry_changes_to_events() {
+    let event = |old: Option<&str>, new: Option<&str>| {
+        DbEvent::new("bash", old.map(ver), new.map(|new| pkg("bash", new, "")))
+            .as_ref()
+            .map(describe)
+    };
+    assert_eq!(event(None, None), None);
+    assert_eq!(
+        event(None, Some("5.2-1")).as_deref(),
+        Some("installed bash 5.2-1")
+    );
+    assert_eq!(
+        event(Some("5.2-1"), None).as_deref(),
+        Some("removed bash 5.2-1")
+    );
+    assert_eq!(
+        event(Some("5.2-1"), Some("5.2-1")).as_deref(),
+        Some("reinstalled bash 5.2-1")
+    );
+    // Versions compare by vercmp, not as strings
+    assert_eq!(
+        event(Some("5.2.9-1"), Some("5.2.10-1")).as_deref(),
+        Some("upgraded bash 5.2.9-1 -> 5.2.10-1")
+    );
+    assert_eq!(
+        event(Some("1:5.2-1"), Some("6.0-1")).as_deref(),
+        Some("downgraded bash 1:5.2-1 -> 6.0-1")
+    );
+}
+
+#[test]
+fn writer_changes_are_noticed_once() {
+    let db_path = common::temp_dir("watch");
+    let writer = LocalDbWriter::with_db_path(&db_path).unwrap();
+    let watcher = DbWatcher::with_db_path(&db_path).unwrap();
+    writer
+        .write(&pkg("bash", "5.2-1", ""), EntryExtras::default())
+        .unwrap();
+    let (watcher, events) = next_events(watcher);
+    assert_eq!(events, ["installed bash 5.2-1"]);
+    // The old entry is moved out and the new one moved in, which is still one change
+    writer
+        .write(&pkg("bash", "5.2-2", ""), EntryExtras::default())
+        .unwrap();
+    let (watcher, events) = next_events(watcher);
+    assert_eq!(events, ["upgraded bash 5.2-1 -> 5.2-2"]);
+    writer.remove("bash").unwrap();
+    let (_, events) = next_events(watcher);
+    assert_eq!(events, ["removed bash 5.2-2"]);
+    std::fs::remove_dir_all(db_path).unwrap();
+}
+
+#[test]
+fn changes_are_held_back_while_locked() {
+    let db_path = common::temp_dir("watch-lock");
+    let writer = LocalDbWriter::with_db_path(&db_path).unwrap();
+    let watcher = DbWatcher::with_db_path(&db_path).unwrap();
+    let lock = db_path.join("db.lck");
+    std::fs::write(&lock, b"").unwrap();
+    writer
+        .write(&pkg("bash", "5.2-1", ""), EntryExtras::default())
+        .unwrap();
+    let unlock = std::thread::spawn(move || {
+        // Give the watcher time to see the first change while the lock is held
+        std::thread::sleep(Duration::from_millis(200));
+        writer
+            .write(&pkg("zsh", "5.9-5", ""), EntryExtras::default())
+            .unwrap();
+        std::fs::remove_file(lock).unwrap();
+    });
+    let (_, events) = next_events(watcher);
+    unlock.join().unwrap();
+    assert_eq!(events, ["installed bash 5.2-1", "installed zsh 5.9-5"]);
+    std::fs::remove_dir_all(db_path).unwrap();
+}
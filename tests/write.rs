@@ -0,0 +1,43 @@
+mod common;
+
+use {
+    alpacka::write::{EntryExtras, LocalDbWriter},
+    common::pkg,
+};
+
+#[test]
+fn written_entries_read_back() {
+    let db_path = common::temp_dir("write");
+    let writer = LocalDbWriter::with_db_path(&db_path).unwrap();
+    let extras = EntryExtras {
+        install: Some(b"post_install() { :; }\n"),
+        ..EntryExtras::default()
+    };
+    writer.write(&pkg("bash", "5.2-1", ""), extras).unwrap();
+    // Reinstalling the same version replaces the whole entry
+    let entry = writer
+        .write(&pkg("bash", "5.2-1", ""), EntryExtras::default())
+        .unwrap();
+    assert!(!entry.join("install").exists());
+    writer
+        .write(&pkg("bash", "5.2-2", ""), EntryExtras::default())
+        .unwrap();
+
+    let pkgs = alpacka::read_local_db_at(&db_path).unwrap();
+    assert_eq!(common::names(&pkgs), ["bash"]);
+    assert_eq!(pkgs[0].desc.version.as_str(), "5.2-2");
+    assert_ne!(pkgs[0].desc.install_date, 0);
+    let mut entries: Vec<_> = std::fs::read_dir(db_path.join("local"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    entries.sort();
+    assert_eq!(entries, ["ALPM_DB_VERSION", "bash-5.2-2"]);
+
+    writer.remove("bash").unwrap();
+    assert!(alpacka::read_local_db_at(&db_path).unwrap().is_empty());
+    // Old entries are deleted once they are out of `local`
+    let staged = std::fs::read_dir(db_path.join(".alpacka-staging")).unwrap();
+    assert_eq!(staged.count(), 0);
+    std::fs::remove_dir_all(db_path).unwrap();
+}